@@ -0,0 +1,48 @@
+-- Base tables as first deployed, later migrations alter these in place
+CREATE TABLE IF NOT EXISTS transaction (
+    local_id UUID PRIMARY KEY,
+    strategy TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    locker UUID NOT NULL,
+    orders TEXT NOT NULL,
+    entry_time TIMESTAMPTZ NOT NULL,
+    exit_time TIMESTAMPTZ NOT NULL,
+    entry_price DOUBLE PRECISION NOT NULL,
+    exit_price DOUBLE PRECISION NOT NULL,
+    quantity BIGINT NOT NULL,
+    pnl DOUBLE PRECISION NOT NULL,
+    roi DOUBLE PRECISION NOT NULL,
+    cost_basis DOUBLE PRECISION NOT NULL,
+    direction TEXT NOT NULL,
+    status TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS mktorder (
+    local_id UUID PRIMARY KEY,
+    action TEXT NOT NULL,
+    strategy TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    side TEXT NOT NULL,
+    direction TEXT NOT NULL,
+    entry_price DOUBLE PRECISION NOT NULL,
+    fill_price DOUBLE PRECISION NOT NULL,
+    entry_time TIMESTAMPTZ NOT NULL,
+    fill_time TIMESTAMPTZ NOT NULL,
+    quantity BIGINT NOT NULL,
+    status TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS locker (
+    local_id UUID PRIMARY KEY,
+    strategy TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    entry_price DOUBLE PRECISION NOT NULL,
+    stop_price DOUBLE PRECISION NOT NULL,
+    type TEXT NOT NULL,
+    multiplier DOUBLE PRECISION NOT NULL,
+    direction TEXT NOT NULL,
+    watermark DOUBLE PRECISION NOT NULL,
+    zone SMALLINT NOT NULL,
+    status TEXT NOT NULL,
+    transact_type TEXT NOT NULL
+);
@@ -0,0 +1,5 @@
+-- Cancel reasons on transactions and broker filled quantities on orders
+ALTER TABLE transaction ADD COLUMN IF NOT EXISTS reason TEXT;
+
+ALTER TABLE mktorder ADD COLUMN IF NOT EXISTS filled_quantity DOUBLE PRECISION NOT NULL DEFAULT 0;
+UPDATE mktorder SET filled_quantity = quantity WHERE status = 'Filled';
@@ -0,0 +1,9 @@
+-- Persisted halt state, survives restarts until an admin resumes trading
+CREATE TABLE IF NOT EXISTS kill_switch (
+    local_id UUID PRIMARY KEY,
+    halted BOOLEAN NOT NULL,
+    reason TEXT NOT NULL,
+    cancel_orders BOOLEAN NOT NULL,
+    liquidate BOOLEAN NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,9 @@
+-- Start of day equity and high water mark for the circuit breaker, with any breach of the day
+CREATE TABLE IF NOT EXISTS equity_baseline (
+    local_id UUID PRIMARY KEY,
+    trading_day DATE NOT NULL,
+    start_of_day_equity DOUBLE PRECISION NOT NULL,
+    high_water_mark DOUBLE PRECISION NOT NULL,
+    breach TEXT,
+    updated_at TIMESTAMPTZ NOT NULL
+);
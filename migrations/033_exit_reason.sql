@@ -0,0 +1,2 @@
+-- Why a position was sent to close, kept until the close completes or is reopened
+ALTER TABLE transaction ADD COLUMN IF NOT EXISTS exit_reason TEXT;
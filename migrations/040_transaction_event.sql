@@ -0,0 +1,11 @@
+-- Audit trail of transaction status changes
+CREATE TABLE IF NOT EXISTS transaction_event (
+    local_id UUID PRIMARY KEY,
+    transaction UUID NOT NULL,
+    from_status TEXT,
+    to_status TEXT NOT NULL,
+    cause TEXT NOT NULL,
+    event_time TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS transaction_event_transaction_idx ON transaction_event (transaction);
@@ -0,0 +1,3 @@
+-- Fractional share quantities on orders and transactions
+ALTER TABLE mktorder ALTER COLUMN quantity TYPE DOUBLE PRECISION;
+ALTER TABLE transaction ALTER COLUMN quantity TYPE DOUBLE PRECISION;
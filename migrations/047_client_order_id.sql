@@ -0,0 +1,4 @@
+-- Client order ids are persisted ahead of submission so lost posts can be looked up
+ALTER TABLE mktorder ADD COLUMN IF NOT EXISTS client_order_id TEXT;
+
+CREATE INDEX IF NOT EXISTS mktorder_client_order_id_idx ON mktorder (client_order_id text_pattern_ops);
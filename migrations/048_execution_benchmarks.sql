@@ -0,0 +1,4 @@
+-- Benchmark prices captured per order for the execution report
+ALTER TABLE mktorder ADD COLUMN IF NOT EXISTS stop_price DOUBLE PRECISION NOT NULL DEFAULT 0;
+ALTER TABLE mktorder ADD COLUMN IF NOT EXISTS signal_price DOUBLE PRECISION NOT NULL DEFAULT 0;
+ALTER TABLE mktorder ADD COLUMN IF NOT EXISTS arrival_price DOUBLE PRECISION NOT NULL DEFAULT 0;
@@ -1,15 +1,18 @@
 use anyhow::Result;
+use std::env;
 use std::sync::Arc;
 use tokio::sync::broadcast;
 use tokio::sync::broadcast::Receiver;
 use tokio::sync::broadcast::Sender;
 use tokio::sync::Mutex;
 use tokio_util::sync::CancellationToken;
+use tracing::warn;
 
 use super::pub_sub::GcpPubSub;
 use super::web_hook::WebHook;
 use super::Event;
 use super::Settings;
+use crate::platform::ExecutionReports;
 
 pub struct EventClients {
     pubsub: GcpPubSub,
@@ -24,7 +27,17 @@ impl EventClients {
     ) -> Result<Arc<Mutex<Self>>> {
         let (publisher, _) = broadcast::channel(32);
         let pubsub = GcpPubSub::new(shutdown_signal.clone(), settings.clone()).await?;
-        let webhook = WebHook::new(shutdown_signal).await;
+        let reports = ExecutionReports::new(&settings).await?;
+        let admin_token = settings
+            .admin
+            .as_ref()
+            .and_then(|admin| admin.token.clone())
+            .or_else(|| env::var("ADMIN_TOKEN").ok())
+            .filter(|token| !token.is_empty());
+        if admin_token.is_none() {
+            warn!("No admin token configured, admin routes will refuse every request");
+        }
+        let webhook = WebHook::new(shutdown_signal, reports, admin_token).await;
         Ok(Arc::new(Mutex::new(EventClients {
             pubsub,
             webhook,
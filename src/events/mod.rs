@@ -21,6 +21,8 @@ use event_clients::EventClients;
 pub enum PortAction {
     Create,
     Liquidate,
+    Halt,
+    Resume,
 }
 
 impl<'de> serde::Deserialize<'de> for PortAction {
@@ -32,6 +34,8 @@ impl<'de> serde::Deserialize<'de> for PortAction {
         match value {
             1 => Ok(PortAction::Create),
             2 => Ok(PortAction::Liquidate),
+            3 => Ok(PortAction::Halt),
+            4 => Ok(PortAction::Resume),
             _ => Err(serde::de::Error::custom("Invalid PortAction value")),
         }
     }
@@ -147,6 +151,33 @@ pub struct MktSignal {
     pub primary_exchange: Option<String>,
     pub is_dirty: Option<bool>,
     pub amount: Option<f64>,
+    pub take_profit: Option<f64>,
+}
+
+impl MktSignal {
+    pub fn admin_command(&self) -> Option<AdminCommand> {
+        match self.action {
+            PortAction::Halt => Some(AdminCommand::Halt {
+                reason: format!("signal from strategy {}", self.strategy),
+                cancel_orders: None,
+                liquidate: None,
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum AdminCommand {
+    Halt {
+        reason: String,
+        cancel_orders: Option<bool>,
+        liquidate: Option<bool>,
+    },
+    Resume,
+    Adopt {
+        symbol: Option<String>,
+    },
 }
 
 pub struct EventPublisher {
@@ -170,3 +201,34 @@ impl EventPublisher {
         self.event_clients.lock().await.run().await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_signal(action: PortAction) -> MktSignal {
+        MktSignal {
+            strategy: "test".to_string(),
+            symbol: "AAPL".to_string(),
+            side: Side::Buy,
+            action,
+            direction: Direction::Long,
+            source: Source::PubSub,
+            price: 100.0,
+            primary_exchange: None,
+            is_dirty: None,
+            amount: None,
+            take_profit: None,
+        }
+    }
+
+    #[test]
+    fn test_resume_signal_leaves_halt_in_place() {
+        assert!(matches!(
+            build_signal(PortAction::Halt).admin_command(),
+            Some(AdminCommand::Halt { .. })
+        ));
+        assert!(build_signal(PortAction::Resume).admin_command().is_none());
+        assert!(build_signal(PortAction::Create).admin_command().is_none());
+    }
+}
@@ -1,6 +1,9 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 use tokio_util::sync::CancellationToken;
 
+use axum::extract;
+use axum::http::HeaderMap;
 use axum::response;
 use axum::routing;
 use axum::Router;
@@ -15,12 +18,15 @@ use serde_json::{json, Value};
 
 use anyhow::Result;
 
+use super::AdminCommand;
 use super::Direction;
 use super::Event;
 use super::MktSignal;
 use super::PortAction;
 use super::Side;
 use super::Source;
+use crate::platform::ExecutionReports;
+use crate::platform::ReportFilter;
 
 async fn post_event(
     sender: Sender<Event>,
@@ -47,44 +53,219 @@ async fn post_event(
         primary_exchange: None,
         is_dirty: None,
         amount: None,
+        take_profit: payload
+            .get("take_profit")
+            .and_then(|val| str::parse::<f64>(val).ok()),
     };
 
     let event = Event::MktSignal(mktsignal);
     match sender.send(event) {
         Err(err) => {
             error!("{err:?}");
-            response::Json(json!({"response" : 400, "msg": "{err}"}))
+            response::Json(json!({"response" : 400, "msg": format!("{err}")}))
         }
         Ok(_) => response::Json(json!({"response" : 200, "msg": "success"})),
     }
 }
 
+const ADMIN_TOKEN_HEADER: &str = "x-admin-token";
+
+fn is_authorized(admin_token: &Option<String>, headers: &HeaderMap) -> bool {
+    let Some(admin_token) = admin_token else {
+        return false;
+    };
+    headers
+        .get(ADMIN_TOKEN_HEADER)
+        .and_then(|val| val.to_str().ok())
+        .is_some_and(|token| token == admin_token)
+}
+
+fn unauthorized() -> response::Json<Value> {
+    error!("Rejected admin request, missing or invalid admin token");
+    response::Json(json!({"response" : 401, "msg": "unauthorized"}))
+}
+
+fn parse_flag(payload: &HashMap<String, String>, key: &str) -> Option<bool> {
+    payload
+        .get(key)
+        .and_then(|val| str::parse::<bool>(val).ok())
+}
+
+fn send_admin_command(sender: Sender<Event>, command: AdminCommand) -> response::Json<Value> {
+    match sender.send(Event::Admin(command)) {
+        Err(err) => {
+            error!("{err:?}");
+            response::Json(json!({"response" : 400, "msg": format!("{err}")}))
+        }
+        Ok(_) => response::Json(json!({"response" : 200, "msg": "success"})),
+    }
+}
+
+async fn post_halt(
+    sender: Sender<Event>,
+    admin_token: Arc<Option<String>>,
+    headers: HeaderMap,
+    response::Json(payload): response::Json<HashMap<String, String>>,
+) -> response::Json<Value> {
+    info!("Received halt from webhook, payload: {payload:?}");
+    if !is_authorized(&admin_token, &headers) {
+        return unauthorized();
+    }
+
+    let command = AdminCommand::Halt {
+        reason: payload
+            .get("reason")
+            .cloned()
+            .unwrap_or("admin request".to_string()),
+        cancel_orders: parse_flag(&payload, "cancel_orders"),
+        liquidate: parse_flag(&payload, "liquidate"),
+    };
+    send_admin_command(sender, command)
+}
+
+async fn post_resume(
+    sender: Sender<Event>,
+    admin_token: Arc<Option<String>>,
+    headers: HeaderMap,
+) -> response::Json<Value> {
+    info!("Received resume from webhook");
+    if !is_authorized(&admin_token, &headers) {
+        return unauthorized();
+    }
+    send_admin_command(sender, AdminCommand::Resume)
+}
+
+async fn post_adopt(
+    sender: Sender<Event>,
+    admin_token: Arc<Option<String>>,
+    headers: HeaderMap,
+    response::Json(payload): response::Json<HashMap<String, String>>,
+) -> response::Json<Value> {
+    info!("Received adopt from webhook, payload: {payload:?}");
+    if !is_authorized(&admin_token, &headers) {
+        return unauthorized();
+    }
+
+    let command = AdminCommand::Adopt {
+        symbol: payload.get("symbol").cloned(),
+    };
+    send_admin_command(sender, command)
+}
+
+async fn get_execution_report(
+    reports: Arc<ExecutionReports>,
+    extract::Query(params): extract::Query<HashMap<String, String>>,
+) -> response::Json<Value> {
+    info!("Received execution report request, params: {params:?}");
+
+    let days = match params.get("days").map(|val| str::parse::<i64>(val)) {
+        Some(Err(err)) => {
+            error!("Failed to parse value: days");
+            return response::Json(json!({"response" : 400, "msg": format!("{:?}", err)}));
+        }
+        Some(Ok(days)) => Some(days),
+        None => None,
+    };
+    let filter = ReportFilter::new(
+        params.get("strategy").cloned(),
+        params.get("symbol").cloned(),
+        days,
+    );
+    match reports.fetch(&filter).await {
+        Err(err) => {
+            error!("{err:?}");
+            response::Json(json!({"response" : 500, "msg": format!("{err}")}))
+        }
+        Ok(report) => response::Json(json!({"response" : 200, "report": report})),
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct WebHook {
     shutdown_signal: CancellationToken,
+    reports: Arc<ExecutionReports>,
+    admin_token: Arc<Option<String>>,
 }
 
 impl WebHook {
-    pub async fn new(shutdown_signal: CancellationToken) -> Self {
-        WebHook { shutdown_signal }
+    pub async fn new(
+        shutdown_signal: CancellationToken,
+        reports: ExecutionReports,
+        admin_token: Option<String>,
+    ) -> Self {
+        WebHook {
+            shutdown_signal,
+            reports: Arc::new(reports),
+            admin_token: Arc::new(admin_token),
+        }
     }
 
     pub async fn run(&mut self, sender: Sender<Event>) -> Result<()> {
+        let halt_sender = sender.clone();
+        let resume_sender = sender.clone();
+        let adopt_sender = sender.clone();
+        let reports = Arc::clone(&self.reports);
+        let halt_token = Arc::clone(&self.admin_token);
+        let resume_token = Arc::clone(&self.admin_token);
+        let adopt_token = Arc::clone(&self.admin_token);
         let app = Router::new()
             .route(
                 "/v1/mktsignal",
                 routing::post(move |body| post_event(sender, body)),
             )
+            .route(
+                "/v1/admin/halt",
+                routing::post(move |headers, body| {
+                    post_halt(halt_sender, halt_token, headers, body)
+                }),
+            )
+            .route(
+                "/v1/admin/resume",
+                routing::post(move |headers| post_resume(resume_sender, resume_token, headers)),
+            )
+            .route(
+                "/v1/admin/adopt",
+                routing::post(move |headers, body| {
+                    post_adopt(adopt_sender, adopt_token, headers, body)
+                }),
+            )
+            .route(
+                "/v1/reports/execution",
+                routing::get(move |query| get_execution_report(reports, query)),
+            )
             .layer(CorsLayer::permissive());
 
         let server =
             axum::Server::bind(&"0.0.0.0:4010".parse().unwrap()).serve(app.into_make_service());
 
         let cancel_request = self.shutdown_signal.clone();
-        server.with_graceful_shutdown(async {
-            cancel_request.cancelled().await;
+        tokio::spawn(async move {
+            if let Err(err) = server
+                .with_graceful_shutdown(async {
+                    cancel_request.cancelled().await;
+                })
+                .await
+            {
+                error!("Webhook server stopped, error={}", err);
+            }
         });
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admin_requests_need_matching_token() {
+        let mut headers = HeaderMap::new();
+        assert!(!is_authorized(&Some("secret".to_string()), &headers));
+        headers.insert(ADMIN_TOKEN_HEADER, "wrong".parse().unwrap());
+        assert!(!is_authorized(&Some("secret".to_string()), &headers));
+        headers.insert(ADMIN_TOKEN_HEADER, "secret".parse().unwrap());
+        assert!(is_authorized(&Some("secret".to_string()), &headers));
+        assert!(!is_authorized(&None, &headers));
+    }
+}
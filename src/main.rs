@@ -3,6 +3,8 @@ use apca::data::v2::stream::Bar;
 use apca::data::v2::stream::Quote;
 use apca::data::v2::stream::Trade;
 use clap::Parser;
+use clap::Subcommand;
+use std::collections::HashMap;
 use std::env;
 use tokio::signal;
 use tokio::sync::broadcast::error::RecvError;
@@ -19,10 +21,16 @@ mod platform;
 mod settings;
 mod utils;
 
+use events::AdminCommand;
 use events::EventPublisher;
 use events::MktSignal;
+use events::PortAction;
 use logging::CloudLogging;
+use platform::Credentials;
+use platform::ExecutionReports;
+use platform::ExecutionStats;
 use platform::Platform;
+use platform::ReportFilter;
 use settings::Config;
 use settings::Settings;
 
@@ -33,6 +41,7 @@ pub enum Event {
     Bar(Bar),
     OrderUpdate(OrderUpdate),
     MktSignal(MktSignal),
+    Admin(AdminCommand),
 }
 
 #[derive(Parser, Debug)]
@@ -40,11 +49,86 @@ pub enum Event {
 struct Args {
     #[arg(short, long)]
     settings: String,
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-fn graceful_shutdown(is_graceful_shutdown: &mut bool, shutdown_signal: &CancellationToken) {
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print slippage, time to fill and fill rate per strategy and symbol
+    ExecutionReport {
+        #[arg(long)]
+        strategy: Option<String>,
+        #[arg(long)]
+        symbol: Option<String>,
+        /// Only include orders submitted in the last number of days
+        #[arg(long)]
+        days: Option<i64>,
+    },
+}
+
+fn format_bps(value: Option<f64>) -> String {
+    value.map_or("-".to_string(), |value| format!("{value:.2}"))
+}
+
+fn print_execution_stats(report: &[ExecutionStats]) {
+    println!(
+        "{:<12} {:<8} {:<10} {:>6} {:>6} {:>6} {:>10} {:>10} {:>10} {:>10} {:>8}",
+        "strategy",
+        "symbol",
+        "action",
+        "orders",
+        "filled",
+        "rate",
+        "signal",
+        "arrival",
+        "limit",
+        "stop",
+        "secs"
+    );
+    for stats in report {
+        println!(
+            "{:<12} {:<8} {:<10} {:>6} {:>6} {:>6.2} {:>10} {:>10} {:>10} {:>10} {:>8}",
+            stats.strategy,
+            stats.symbol,
+            stats.action,
+            stats.orders,
+            stats.filled,
+            stats.fill_rate,
+            format_bps(stats.signal_slippage_bps),
+            format_bps(stats.arrival_slippage_bps),
+            format_bps(stats.limit_offset_bps),
+            format_bps(stats.stop_offset_bps),
+            format_bps(stats.avg_secs_to_fill)
+        );
+    }
+}
+
+async fn execution_report(settings: &Settings, filter: ReportFilter) {
+    let report = match ExecutionReports::new(settings).await {
+        Ok(reports) => reports.fetch(&filter).await,
+        Err(err) => Err(err),
+    };
+    match report {
+        Ok(report) => {
+            print_execution_stats(&report);
+            std::process::exit(0);
+        }
+        Err(err) => {
+            println!("Execution report failed: {err}");
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn graceful_shutdown(
+    platform: &Platform,
+    is_graceful_shutdown: &mut bool,
+    shutdown_signal: &CancellationToken,
+) {
     *is_graceful_shutdown = true;
     info!("Graceful shutdown initiated");
+    platform.shutdown().await;
     shutdown_signal.cancel();
 }
 
@@ -58,6 +142,14 @@ async fn main() {
         }
         Ok(val) => val,
     };
+    if let Some(Command::ExecutionReport {
+        strategy,
+        symbol,
+        days,
+    }) = cmdline_args.command
+    {
+        execution_report(&settings, ReportFilter::new(strategy, symbol, days)).await;
+    }
 
     let shutdown_signal = CancellationToken::new();
     let _logger = CloudLogging::new(
@@ -68,40 +160,42 @@ async fn main() {
     )
     .await
     .unwrap();
-    let is_live = match settings.account_type.as_str() {
-        "live" => true,
-        "paper" => false,
-        val => {
-            error!(
-                "Couldn't determine cmdline type [{val:?}] account [{}]",
-                settings.account_type
-            );
-            std::process::exit(1);
-        }
-    };
+    if !matches!(settings.account_type.as_str(), "live" | "paper" | "shadow") {
+        error!(
+            "Couldn't determine cmdline type account [{}]",
+            settings.account_type
+        );
+        std::process::exit(1);
+    }
 
     info!("**************** Let the trading begin! ****************\n");
 
-    let key = env::var("API_KEY").expect("Failed to read the 'key' environment variable.");
-    let secret = env::var("API_SECRET").expect("Failed to read the 'secret' environment variable.");
-
-    info!("Found key: {key}, secret: {secret}");
+    let mut credentials = HashMap::new();
+    for (name, account) in settings.get_accounts() {
+        let key = env::var(&account.key_env).unwrap_or_else(|_| {
+            panic!(
+                "Failed to read the '{}' environment variable.",
+                account.key_env
+            )
+        });
+        let secret = env::var(&account.secret_env).unwrap_or_else(|_| {
+            panic!(
+                "Failed to read the '{}' environment variable.",
+                account.secret_env
+            )
+        });
+        info!("Found account: {name}, live: {}", account.is_live);
+        credentials.insert(name, Credentials { key, secret });
+    }
 
-    let mut platform = match Platform::new(
-        settings.clone(),
-        &key,
-        &secret,
-        is_live,
-        shutdown_signal.clone(),
-    )
-    .await
-    {
-        Ok(platform) => platform,
-        Err(err) => {
-            error!("Failed to startup platform, error={}", err);
-            std::process::exit(1);
-        }
-    };
+    let mut platform =
+        match Platform::new(settings.clone(), &credentials, shutdown_signal.clone()).await {
+            Ok(platform) => platform,
+            Err(err) => {
+                error!("Failed to startup platform, error={}", err);
+                std::process::exit(1);
+            }
+        };
     let mut publisher = match EventPublisher::new(shutdown_signal.clone(), settings).await {
         Ok(publisher) => publisher,
         Err(err) => {
@@ -134,10 +228,28 @@ async fn main() {
         tokio::select! {
             event = publisher_events.recv() => {
                 match event {
-                    Ok(Event::MktSignal(event)) => {
-                        info!("Recieved an event {event:?}, creating new position");
-                        if let Err(err) = platform.create_position(&event).await {
-                            warn!("Signal dropped {event:?}, error: {err}");
+                    Ok(Event::MktSignal(event)) => match event.action {
+                        PortAction::Halt => {
+                            if let Some(command) = event.admin_command() {
+                                if let Err(err) = platform.admin_command(&command).await {
+                                    warn!("Admin signal dropped {event:?}, error: {err}");
+                                }
+                            }
+                        }
+                        PortAction::Resume => {
+                            warn!("Resume signal dropped {event:?}, resume is only accepted on /v1/admin/resume");
+                        }
+                        _ => {
+                            info!("Recieved an event {event:?}, creating new position");
+                            if let Err(err) = platform.create_position(&event).await {
+                                warn!("Signal dropped {event:?}, error: {err}");
+                            }
+                        }
+                    },
+                    Ok(Event::Admin(command)) => {
+                        info!("Recieved an admin command {command:?}");
+                        if let Err(err) = platform.admin_command(&command).await {
+                            warn!("Admin command dropped {command:?}, error: {err}");
                         }
                     },
                     Ok(_) => (),
@@ -158,10 +270,10 @@ async fn main() {
                 }
             }
             _ = sigterm.recv() => {
-                graceful_shutdown(&mut is_graceful_shutdown, &shutdown_signal);
+                graceful_shutdown(&platform, &mut is_graceful_shutdown, &shutdown_signal).await;
             }
             _ = signal::ctrl_c() => {
-                graceful_shutdown(&mut is_graceful_shutdown, &shutdown_signal);
+                graceful_shutdown(&platform, &mut is_graceful_shutdown, &shutdown_signal).await;
             }
             _ = sleep(Duration::from_secs(120)) => {
                 info!("Printing status updates");
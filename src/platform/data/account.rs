@@ -41,6 +41,18 @@ impl AccountDetails {
         self.account.equity.clone()
     }
 
+    pub async fn last_equity(&self) -> Num {
+        self.account.last_equity.clone()
+    }
+
+    pub async fn buying_power(&self) -> Num {
+        self.account.buying_power.clone()
+    }
+
+    pub async fn daytrade_count(&self) -> u64 {
+        self.account.daytrade_count
+    }
+
     pub async fn update_account(&mut self) -> Result<()> {
         let account_details = self.connectors.get_account_details().await?;
         self.account = account_details;
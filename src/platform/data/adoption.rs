@@ -0,0 +1,260 @@
+use anyhow::bail;
+use anyhow::Ok;
+use anyhow::Result;
+use apca::api::v2::order;
+use apca::api::v2::position;
+use apca::api::v2::position::Position;
+use chrono::DateTime;
+use chrono::Utc;
+use num_decimal::Num;
+use std::str::FromStr;
+use tracing::info;
+use tracing::warn;
+
+use super::get_key;
+use super::locker::TransactionType;
+use super::Transaction;
+use super::TransactionStatus;
+use super::Transactions;
+use crate::events::Direction;
+use crate::settings::AdoptionConfig;
+
+// Adopted without a broker fill to date the entry, holding periods cannot be measured
+pub const ADOPTED_UNKNOWN_ENTRY: &str = "adopted_unknown_entry";
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum AnchorPrice {
+    #[default]
+    Entry,
+    Current,
+}
+
+impl FromStr for AnchorPrice {
+    type Err = String;
+
+    fn from_str(val: &str) -> Result<Self, Self::Err> {
+        match val.to_lowercase().as_str() {
+            "entry" => std::result::Result::Ok(AnchorPrice::Entry),
+            "current" => std::result::Result::Ok(AnchorPrice::Current),
+            _ => Err(format!("Failed to parse anchor price, unknown: {}", val)),
+        }
+    }
+}
+
+fn find_anchor_price(anchor: AnchorPrice, entry_price: &Num, current_price: Option<&Num>) -> Num {
+    match (anchor, current_price) {
+        (AnchorPrice::Current, Some(price)) => price.clone(),
+        _ => entry_price.clone(),
+    }
+}
+
+fn find_latest_fill(
+    orders: &[order::Order],
+    symbol: &str,
+    side: order::Side,
+) -> Option<DateTime<Utc>> {
+    orders
+        .iter()
+        .filter(|order| order.symbol == symbol && order.side == side)
+        .filter_map(|order| order.filled_at)
+        .max()
+}
+
+impl Transactions {
+    pub async fn find_untracked_positions(&mut self) -> Result<Vec<Position>> {
+        let positions = self.mktpositions.get_broker_positions().await?;
+        Ok(positions
+            .into_iter()
+            .filter(|position| !self.has_open_transaction(&position.symbol))
+            .collect())
+    }
+
+    // Latest fill on the side which opened the position, orders older than the broker listing are lost
+    async fn find_entry_fill_time(
+        &self,
+        symbol: &str,
+        direction: Direction,
+    ) -> Option<DateTime<Utc>> {
+        let side = match direction {
+            Direction::Long => order::Side::Buy,
+            Direction::Short => order::Side::Sell,
+        };
+        let orders = match self.mktorders.get_broker_orders().await {
+            std::result::Result::Ok(orders) => orders,
+            Err(err) => {
+                warn!(
+                    "Failed to fetch broker orders for symbol: {}, error={}",
+                    symbol, err
+                );
+                return None;
+            }
+        };
+        find_latest_fill(&orders, symbol, side)
+    }
+
+    pub async fn adopt_position(
+        &mut self,
+        position: &Position,
+        config: &AdoptionConfig,
+    ) -> Result<()> {
+        let symbol = &position.symbol;
+        let strategy = &config.strategy;
+        let anchor = AnchorPrice::from_str(&config.anchor).unwrap_or_else(|err| {
+            warn!("{}, anchoring at entry price", err);
+            AnchorPrice::Entry
+        });
+        let anchor_price = find_anchor_price(
+            anchor,
+            &position.average_entry_price,
+            position.current_price.as_ref(),
+        );
+        if anchor_price.is_zero() {
+            bail!("Unable to adopt symbol: {}, no anchor price", symbol)
+        }
+        let direction = match position.side {
+            position::Side::Long => Direction::Long,
+            position::Side::Short => Direction::Short,
+        };
+        let quantity = if position.quantity.is_negative() {
+            -position.quantity.clone()
+        } else {
+            position.quantity.clone()
+        };
+
+        let (entry_time, reason) = match self.find_entry_fill_time(symbol, direction).await {
+            Some(fill_time) => (fill_time, "adopted"),
+            None => {
+                warn!(
+                    "No broker fill found for symbol: {}, adopting with unknown entry time",
+                    symbol
+                );
+                (Utc::now(), ADOPTED_UNKNOWN_ENTRY)
+            }
+        };
+
+        let locker_id = self
+            .locker
+            .create_new_stop(
+                symbol,
+                strategy,
+                anchor_price.clone(),
+                TransactionType::Position,
+                direction,
+            )
+            .await;
+        self.locker.activate(locker_id).await;
+
+        let mut transaction = Transaction {
+            strategy: strategy.to_string(),
+            symbol: symbol.to_string(),
+            locker: locker_id,
+            entry_time,
+            entry_price: position.average_entry_price.clone(),
+            quantity,
+            cost_basis: position.cost_basis.clone(),
+            pnl: position
+                .unrealized_gain_total
+                .clone()
+                .unwrap_or(Num::from(0)),
+            direction,
+            status: TransactionStatus::Confirmed,
+            reason: Some(reason.to_string()),
+            ..Default::default()
+        };
+        transaction.persist_db(self.db.clone()).await?;
+        transaction.record_event(None, reason, &self.db).await;
+        self.mktpositions.add_position(
+            strategy,
+            symbol,
+            direction,
+            &transaction.quantity,
+            &transaction.entry_price,
+        );
+        self.transactions
+            .insert(get_key(strategy, symbol), transaction);
+        info!(
+            "Strategy[{}] adopted position symbol[{}], quantity[{}], locker anchored at {}",
+            strategy, symbol, position.quantity, anchor_price
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use apca::api::v2::asset;
+    use chrono::Duration;
+    use uuid::Uuid;
+
+    fn build_order(
+        symbol: &str,
+        side: order::Side,
+        filled_at: Option<DateTime<Utc>>,
+    ) -> order::Order {
+        order::Order {
+            id: order::Id(Uuid::new_v4()),
+            client_order_id: Uuid::new_v4().to_string(),
+            status: order::Status::Filled,
+            created_at: Utc::now(),
+            updated_at: None,
+            submitted_at: None,
+            filled_at,
+            expired_at: None,
+            canceled_at: None,
+            asset_class: asset::Class::UsEquity,
+            asset_id: asset::Id(Uuid::nil()),
+            symbol: symbol.to_string(),
+            amount: order::Amount::quantity(10),
+            filled_quantity: Num::from(10),
+            type_: order::Type::Market,
+            class: order::Class::Simple,
+            side,
+            time_in_force: order::TimeInForce::Day,
+            limit_price: None,
+            stop_price: None,
+            trail_price: None,
+            trail_percent: None,
+            average_fill_price: None,
+            extended_hours: false,
+            legs: Vec::default(),
+        }
+    }
+
+    #[test]
+    fn test_anchor_price() {
+        let entry = Num::from(100);
+        let current = Num::from(110);
+        assert_eq!(
+            find_anchor_price(AnchorPrice::Entry, &entry, Some(&current)),
+            entry
+        );
+        assert_eq!(
+            find_anchor_price(AnchorPrice::Current, &entry, Some(&current)),
+            current
+        );
+        assert_eq!(find_anchor_price(AnchorPrice::Current, &entry, None), entry);
+    }
+
+    #[test]
+    fn test_entry_time_from_latest_opening_fill() {
+        let now = Utc::now();
+        let earlier = now - Duration::hours(2);
+        let orders = vec![
+            build_order("AAPL", order::Side::Buy, Some(earlier)),
+            build_order("AAPL", order::Side::Buy, Some(now)),
+            build_order("AAPL", order::Side::Sell, Some(now + Duration::hours(1))),
+            build_order("MSFT", order::Side::Buy, Some(now + Duration::hours(1))),
+            build_order("AAPL", order::Side::Buy, None),
+        ];
+        assert_eq!(
+            find_latest_fill(&orders, "AAPL", order::Side::Buy),
+            Some(now)
+        );
+        assert_eq!(
+            find_latest_fill(&orders, "AAPL", order::Side::Sell),
+            Some(now + Duration::hours(1))
+        );
+        assert_eq!(find_latest_fill(&orders, "TSLA", order::Side::Buy), None);
+    }
+}
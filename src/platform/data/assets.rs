@@ -47,6 +47,12 @@ impl Assets {
         self.assets[symbol].exchange
     }
 
+    pub fn is_fractionable(&self, symbol: &str) -> bool {
+        self.assets
+            .get(symbol)
+            .is_some_and(|asset| asset.fractionable)
+    }
+
     pub fn check_if_assest_is_tradable(&self, symbol: &str, direction: Direction) -> bool {
         info!("Number of assets stored in cache: {}", self.assets.len());
         if let Some(asset) = self.assets.get(symbol) {
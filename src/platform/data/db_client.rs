@@ -1,11 +1,15 @@
 use anyhow::bail;
 use anyhow::Ok;
 use anyhow::Result;
+use sqlx::postgres::PgConnection;
 use sqlx::postgres::PgPoolOptions;
+use sqlx::Connection;
+use sqlx::Executor;
 use sqlx::Pool;
 use sqlx::Postgres;
 use std::env;
 use std::sync::Arc;
+use tracing::info;
 use uuid::Uuid;
 
 use super::Settings;
@@ -92,10 +96,55 @@ impl DBClient {
             "postgresql://{}:{}@{}:{}/{}?sslmode=disable",
             db_cfg.user, dbpass, db_cfg.host, db_cfg.port, db_cfg.name
         );
+        let schema = match settings.account_type.as_str() {
+            "shadow" => {
+                let shadow = settings
+                    .shadow
+                    .as_ref()
+                    .map(|shadow| shadow.schema.clone())
+                    .filter(|schema| !schema.is_empty())
+                    .unwrap_or("shadow".to_string());
+                match &db_cfg.schema {
+                    Some(schema) => Some(format!("{}_{}", shadow, schema)),
+                    None => Some(shadow),
+                }
+            }
+            _ => db_cfg.schema.clone(),
+        };
+        if let Some(schema) = &schema {
+            info!("Database using schema: {}", schema);
+            let mut conn = match PgConnection::connect(&database_url).await {
+                std::result::Result::Ok(conn) => conn,
+                std::result::Result::Err(err) => {
+                    bail!(
+                        "Failed to connect to db with url: {} error={}",
+                        database_url,
+                        err
+                    );
+                }
+            };
+            if let Err(err) = conn
+                .execute(format!("CREATE SCHEMA IF NOT EXISTS {}", schema).as_str())
+                .await
+            {
+                bail!("Failed to create db schema: {}, error={}", schema, err);
+            }
+            let _ = conn.close().await;
+        }
         let pool = match PgPoolOptions::new()
             .min_connections(2)
             .max_connections(5)
             .test_before_acquire(false)
+            .after_connect(move |conn, _meta| {
+                let schema = schema.clone();
+                Box::pin(async move {
+                    if let Some(schema) = schema {
+                        conn.execute(format!("SET search_path TO {}", schema).as_str())
+                            .await?;
+                    }
+                    std::result::Result::Ok(())
+                })
+            })
             .connect(&database_url)
             .await
         {
@@ -109,6 +158,10 @@ impl DBClient {
             }
         };
 
+        if let Err(err) = sqlx::migrate!().run(&pool).await {
+            bail!("Failed to migrate db schema, error={}", err);
+        }
+
         Ok(Arc::new(DBClient {
             pool,
             query_builder: SqlQueryBuilder {},
@@ -135,8 +188,10 @@ impl DBClient {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::settings::AccountConfig;
     use crate::settings::DatabaseConfig;
     use anyhow::bail;
+    use std::collections::HashMap;
     use uuid::Uuid;
 
     #[test]
@@ -278,6 +333,7 @@ mod tests {
             host: "0.0.0.0".to_string(),
             user: "test".to_string(),
             password: Some("test".to_string()),
+            schema: None,
         };
         let settings = Settings {
             database: db_config,
@@ -318,4 +374,50 @@ mod tests {
             .fetch_one(&db_client.pool)
             .await;
     }
+
+    // tests require local db setup name=test user=test pass=test
+    #[tokio::test]
+    async fn test_accounts_start_on_fresh_schemas() {
+        let account = |name: &str| AccountConfig {
+            schema: Some(format!("{}_{}", name, Uuid::new_v4().simple())),
+            ..Default::default()
+        };
+        let settings = Settings {
+            database: DatabaseConfig {
+                name: "test".to_string(),
+                port: 5432,
+                host: "0.0.0.0".to_string(),
+                user: "test".to_string(),
+                password: Some("test".to_string()),
+                schema: None,
+            },
+            accounts: Some(HashMap::from([
+                ("personal".to_string(), account("personal")),
+                ("ira".to_string(), account("ira")),
+            ])),
+            ..Default::default()
+        };
+
+        for (name, account) in settings.get_accounts() {
+            let schema = account.schema.clone().unwrap();
+            let account_settings = settings.for_account(&name, &account);
+            let db_client = DBClient::new(&account_settings).await.unwrap();
+            for table in ["transaction", "mktorder", "locker", "kill_switch"] {
+                let count: i64 = sqlx::query_scalar(
+                    "SELECT COUNT(*) FROM information_schema.tables WHERE table_schema = $1 AND table_name = $2",
+                )
+                .bind(&schema)
+                .bind(table)
+                .fetch_one(&db_client.pool)
+                .await
+                .unwrap();
+                assert_eq!(count, 1, "table {} missing in schema {}", table, schema);
+            }
+            DBClient::new(&account_settings).await.unwrap();
+
+            let _ = sqlx::query(&format!("DROP SCHEMA {} CASCADE", schema))
+                .execute(&db_client.pool)
+                .await;
+        }
+    }
 }
@@ -0,0 +1,307 @@
+use anyhow::bail;
+use anyhow::Ok;
+use anyhow::Result;
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Utc;
+use num_decimal::Num;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use super::db_client::DBClient;
+use super::mktorder::MktOrder;
+use crate::events::Side;
+use crate::Settings;
+
+#[derive(Debug, Clone, Default)]
+pub struct ReportFilter {
+    pub strategy: Option<String>,
+    pub symbol: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+}
+
+impl ReportFilter {
+    pub fn new(strategy: Option<String>, symbol: Option<String>, days: Option<i64>) -> Self {
+        ReportFilter {
+            strategy,
+            symbol,
+            since: days.map(|days| Utc::now() - Duration::days(days)),
+        }
+    }
+}
+
+// Slippage and offsets are in bps, positive values cost the strategy money
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExecutionStats {
+    pub strategy: String,
+    pub symbol: String,
+    pub action: String,
+    pub orders: usize,
+    pub filled: usize,
+    pub fill_rate: f64,
+    pub signal_slippage_bps: Option<f64>,
+    pub arrival_slippage_bps: Option<f64>,
+    pub limit_offset_bps: Option<f64>,
+    pub stop_offset_bps: Option<f64>,
+    pub avg_secs_to_fill: Option<f64>,
+}
+
+#[derive(Debug, Default)]
+struct Average {
+    total: f64,
+    weight: f64,
+}
+
+impl Average {
+    fn add(&mut self, value: Option<f64>, weight: f64) {
+        if let Some(value) = value {
+            self.total += value * weight;
+            self.weight += weight;
+        }
+    }
+
+    fn get(&self) -> Option<f64> {
+        (self.weight > 0.0).then(|| (self.total / self.weight * 100.0).round() / 100.0)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Accumulator {
+    orders: usize,
+    filled: usize,
+    signal_slippage: Average,
+    arrival_slippage: Average,
+    limit_offset: Average,
+    stop_offset: Average,
+    secs_to_fill: Average,
+}
+
+fn get_bps(price: &Num, benchmark: &Num, side: Side) -> Option<f64> {
+    if price.is_zero() || !benchmark.is_positive() {
+        return None;
+    }
+    let cost = match side {
+        Side::Buy => price.clone() - benchmark.clone(),
+        Side::Sell => benchmark.clone() - price.clone(),
+    };
+    (cost / benchmark.clone() * Num::from(10_000)).to_f64()
+}
+
+fn get_leg(order: &MktOrder) -> String {
+    if order.client_order_id.is_empty() {
+        return order.local_id.to_string();
+    }
+    match order.client_order_id.rsplit_once("-r") {
+        Some((leg, reprice))
+            if !reprice.is_empty() && reprice.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            leg.to_string()
+        }
+        _ => order.client_order_id.clone(),
+    }
+}
+
+fn collapse_legs(orders: &[MktOrder]) -> Vec<MktOrder> {
+    let mut legs: BTreeMap<String, Vec<&MktOrder>> = BTreeMap::new();
+    for order in orders {
+        legs.entry(get_leg(order)).or_default().push(order);
+    }
+    legs.into_values()
+        .map(|mut chain| {
+            chain.sort_by_key(|order| order.entry_time);
+            let (notional, filled) = chain.iter().fold(
+                (Num::default(), Num::default()),
+                |(notional, filled), order| {
+                    (
+                        notional + order.fill_price.clone() * order.filled_quantity.clone(),
+                        filled + order.filled_quantity.clone(),
+                    )
+                },
+            );
+            let mut leg = chain[0].clone();
+            leg.fill_price = match filled.is_zero() {
+                true => Num::default(),
+                false => notional / filled.clone(),
+            };
+            leg.fill_time = chain
+                .iter()
+                .filter(|order| order.filled_quantity.is_positive())
+                .map(|order| order.fill_time)
+                .max()
+                .unwrap_or_default();
+            leg.filled_quantity = filled;
+            leg
+        })
+        .collect()
+}
+
+pub fn aggregate(orders: &[MktOrder]) -> Vec<ExecutionStats> {
+    let submitted: Vec<MktOrder> = orders
+        .iter()
+        .filter(|order| order.entry_time != DateTime::<Utc>::default())
+        .cloned()
+        .collect();
+    let mut groups: BTreeMap<(String, String, String), Accumulator> = BTreeMap::new();
+    for order in &collapse_legs(&submitted) {
+        let key = (
+            order.strategy.clone(),
+            order.symbol.clone(),
+            order.action.to_string(),
+        );
+        let group = groups.entry(key).or_default();
+        group.orders += 1;
+        group.limit_offset.add(
+            get_bps(&order.entry_price, &order.signal_price, order.side),
+            1.0,
+        );
+        group.stop_offset.add(
+            get_bps(&order.stop_price, &order.signal_price, order.side),
+            1.0,
+        );
+        let Some(quantity) = order
+            .filled_quantity
+            .to_f64()
+            .filter(|quantity| *quantity > 0.0)
+        else {
+            continue;
+        };
+        group.filled += 1;
+        group.signal_slippage.add(
+            get_bps(&order.fill_price, &order.signal_price, order.side),
+            quantity,
+        );
+        group.arrival_slippage.add(
+            get_bps(&order.fill_price, &order.arrival_price, order.side),
+            quantity,
+        );
+        if order.fill_time > order.entry_time {
+            let millis = (order.fill_time - order.entry_time).num_milliseconds();
+            group.secs_to_fill.add(Some(millis as f64 / 1000.0), 1.0);
+        }
+    }
+    groups
+        .into_iter()
+        .map(|((strategy, symbol, action), group)| ExecutionStats {
+            strategy,
+            symbol,
+            action,
+            orders: group.orders,
+            filled: group.filled,
+            fill_rate: (group.filled as f64 / group.orders as f64 * 100.0).round() / 100.0,
+            signal_slippage_bps: group.signal_slippage.get(),
+            arrival_slippage_bps: group.arrival_slippage.get(),
+            limit_offset_bps: group.limit_offset.get(),
+            stop_offset_bps: group.stop_offset.get(),
+            avg_secs_to_fill: group.secs_to_fill.get(),
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub struct ExecutionReports {
+    dbs: Vec<Arc<DBClient>>,
+}
+
+impl ExecutionReports {
+    pub async fn new(settings: &Settings) -> Result<Self> {
+        let mut schemas = HashSet::new();
+        let mut dbs = Vec::new();
+        for (name, account) in settings.get_accounts() {
+            let settings = settings.for_account(&name, &account);
+            if schemas.insert(settings.database.schema.clone()) {
+                dbs.push(DBClient::new(&settings).await?);
+            }
+        }
+        Ok(ExecutionReports { dbs })
+    }
+
+    pub async fn fetch(&self, filter: &ReportFilter) -> Result<Vec<ExecutionStats>> {
+        let stmt = "SELECT * FROM mktorder \
+            WHERE ($1::TEXT IS NULL OR strategy = $1) \
+            AND ($2::TEXT IS NULL OR symbol = $2) \
+            AND ($3::TIMESTAMPTZ IS NULL OR entry_time >= $3)";
+        let mut orders = Vec::new();
+        for db in &self.dbs {
+            match sqlx::query_as::<_, MktOrder>(stmt)
+                .bind(filter.strategy.clone())
+                .bind(filter.symbol.clone())
+                .bind(filter.since)
+                .fetch_all(&db.pool)
+                .await
+            {
+                sqlx::Result::Ok(rows) => orders.extend(rows),
+                Err(err) => bail!("Failed to fetch orders for execution report, error={}", err),
+            }
+        }
+        Ok(aggregate(&orders))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::data::mktorder::OrderAction;
+    use uuid::Uuid;
+
+    fn build_order(side: Side, signal: i64, arrival: i64, fill: i64, quantity: i64) -> MktOrder {
+        let entry_time = Utc::now();
+        MktOrder {
+            local_id: Uuid::new_v4(),
+            strategy: "auto01".to_string(),
+            symbol: "AAPL".to_string(),
+            side,
+            action: OrderAction::Create,
+            entry_price: Num::from(signal + 1),
+            signal_price: Num::from(signal),
+            arrival_price: Num::from(arrival),
+            fill_price: Num::from(fill),
+            entry_time,
+            fill_time: entry_time + Duration::seconds(4),
+            quantity: Num::from(10),
+            filled_quantity: Num::from(quantity),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_execution_stats_are_volume_weighted() {
+        let orders = vec![
+            build_order(Side::Buy, 100, 100, 101, 10),
+            build_order(Side::Buy, 100, 101, 100, 30),
+            build_order(Side::Buy, 100, 100, 0, 0),
+            MktOrder {
+                strategy: "auto01".to_string(),
+                symbol: "AAPL".to_string(),
+                action: OrderAction::Create,
+                ..Default::default()
+            },
+        ];
+        let stats = aggregate(&orders);
+        assert_eq!(stats.len(), 1);
+        let stats = &stats[0];
+        assert_eq!((stats.orders, stats.filled), (3, 2));
+        assert_eq!(stats.fill_rate, 0.67);
+        assert_eq!(stats.signal_slippage_bps, Some(25.0));
+        assert_eq!(stats.arrival_slippage_bps, Some(-49.26));
+        assert_eq!(stats.limit_offset_bps, Some(100.0));
+        assert_eq!(stats.stop_offset_bps, None);
+        assert_eq!(stats.avg_secs_to_fill, Some(4.0));
+
+        let sell = aggregate(&[build_order(Side::Sell, 100, 100, 99, 10)]);
+        assert_eq!(sell[0].signal_slippage_bps, Some(100.0));
+    }
+
+    #[test]
+    fn test_replacement_chain_counts_as_one_leg() {
+        let mut original = build_order(Side::Sell, 100, 100, 99, 4);
+        original.client_order_id = "abc-liquidate-0".to_string();
+        let mut replacement = build_order(Side::Sell, 100, 100, 98, 6);
+        replacement.client_order_id = "abc-liquidate-0-r1".to_string();
+        replacement.entry_time = original.entry_time + Duration::seconds(2);
+        let stats = aggregate(&[original, replacement]);
+        assert_eq!((stats[0].orders, stats[0].filled), (1, 1));
+        assert_eq!(stats[0].signal_slippage_bps, Some(160.0));
+    }
+}
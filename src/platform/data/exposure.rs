@@ -0,0 +1,325 @@
+use anyhow::bail;
+use anyhow::Ok;
+use anyhow::Result;
+use num_decimal::Num;
+use std::cmp::min;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::str::FromStr;
+use tracing::info;
+use tracing::warn;
+
+use super::mktposition::MktPosition;
+use crate::events::Direction;
+use crate::settings::ExposureConfig;
+use crate::to_num;
+use crate::Settings;
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum BreachPolicy {
+    #[default]
+    Reject,
+    Resize,
+}
+
+impl FromStr for BreachPolicy {
+    type Err = String;
+
+    fn from_str(val: &str) -> Result<Self, Self::Err> {
+        match val.to_lowercase().as_str() {
+            "reject" => std::result::Result::Ok(BreachPolicy::Reject),
+            "resize" => std::result::Result::Ok(BreachPolicy::Resize),
+            _ => Err(format!("Failed to parse breach policy, unknown: {}", val)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExposureDecision {
+    Accept,
+    Resize(Num, String),
+    Reject(String),
+}
+
+impl fmt::Display for ExposureDecision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExposureDecision::Accept => write!(f, "accept"),
+            ExposureDecision::Resize(size, reason) => {
+                write!(f, "resize to {}, {}", size.round_with(3), reason)
+            }
+            ExposureDecision::Reject(reason) => write!(f, "reject, {}", reason),
+        }
+    }
+}
+
+pub struct ExposureLimits {
+    config: Option<ExposureConfig>,
+    sectors: HashMap<String, String>,
+}
+
+impl ExposureLimits {
+    pub fn new(settings: &Settings) -> Result<Self> {
+        let config = settings.exposure.clone();
+        let sectors = match config.as_ref().and_then(|cfg| cfg.sector_file.as_ref()) {
+            Some(path) => Self::read_sector_file(path)?,
+            None => HashMap::default(),
+        };
+        Ok(ExposureLimits { config, sectors })
+    }
+
+    fn read_sector_file(path: &str) -> Result<HashMap<String, String>> {
+        let mut file = match File::open(path) {
+            std::result::Result::Ok(file) => file,
+            Err(err) => bail!("Failed to open sector file: {}, error={}", path, err),
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let sectors: HashMap<String, String> = serde_json::from_str(&contents)?;
+        info!(
+            "Loaded {} symbols from sector file: {}",
+            sectors.len(),
+            path
+        );
+        Ok(sectors)
+    }
+
+    fn notional(position: &MktPosition) -> Num {
+        let notional = position.avg_price.clone() * position.quantity.clone();
+        if notional.is_negative() {
+            -notional
+        } else {
+            notional
+        }
+    }
+
+    fn total(values: impl Iterator<Item = Num>) -> Num {
+        values.fold(to_num!(0.0), |total, value| total + value)
+    }
+
+    fn signed(notional: Num, direction: Direction) -> Num {
+        match direction {
+            Direction::Long => notional,
+            Direction::Short => -notional,
+        }
+    }
+
+    fn headroom(limit: Num, used: Num) -> Num {
+        let headroom = limit - used;
+        if headroom.is_negative() {
+            to_num!(0.0)
+        } else {
+            headroom
+        }
+    }
+
+    // Positions are expected to include the unfilled remainder of pending entries
+    #[allow(clippy::too_many_arguments)]
+    pub fn check(
+        &self,
+        symbol: &str,
+        direction: Direction,
+        size: &Num,
+        price: &Num,
+        equity: &Num,
+        positions: &[MktPosition],
+        fractional: bool,
+    ) -> ExposureDecision {
+        let Some(config) = &self.config else {
+            return ExposureDecision::Accept;
+        };
+        let notional = size.clone() * price.clone();
+        let gross = Self::total(positions.iter().map(Self::notional));
+        let net = Self::total(
+            positions
+                .iter()
+                .map(|position| Self::signed(Self::notional(position), position.direction)),
+        );
+
+        let mut limits: Vec<(Num, String)> = Vec::new();
+        if let Some(max_gross_pc) = config.max_gross_pc {
+            let limit = equity.clone() * to_num!(max_gross_pc / 100.0);
+            limits.push((
+                Self::headroom(limit, gross.clone()),
+                format!("gross exposure limit {}%", max_gross_pc),
+            ));
+        }
+        if let Some(max_net_pc) = config.max_net_pc {
+            let limit = equity.clone() * to_num!(max_net_pc / 100.0);
+            let used = match direction {
+                Direction::Long => net.clone(),
+                Direction::Short => -net.clone(),
+            };
+            limits.push((
+                Self::headroom(limit, used),
+                format!("net exposure limit {}%", max_net_pc),
+            ));
+        }
+        if let Some(max_symbol_notional) = config.max_symbol_notional {
+            let used = Self::total(
+                positions
+                    .iter()
+                    .filter(|position| position.symbol == symbol)
+                    .map(Self::notional),
+            );
+            limits.push((
+                Self::headroom(to_num!(max_symbol_notional), used),
+                format!("symbol notional limit {}", max_symbol_notional),
+            ));
+        }
+        if let (Some(max_sector_pc), Some(sector)) =
+            (config.max_sector_pc, self.sectors.get(symbol))
+        {
+            let limit = equity.clone() * to_num!(max_sector_pc / 100.0);
+            let used = Self::total(
+                positions
+                    .iter()
+                    .filter(|position| self.sectors.get(&position.symbol) == Some(sector))
+                    .map(Self::notional),
+            );
+            limits.push((
+                Self::headroom(limit, used),
+                format!("sector {} exposure limit {}%", sector, max_sector_pc),
+            ));
+        }
+
+        let Some((allowed, reason)) = limits
+            .into_iter()
+            .filter(|(headroom, _)| *headroom < notional)
+            .min_by(|lhs, rhs| lhs.0.cmp(&rhs.0))
+        else {
+            return ExposureDecision::Accept;
+        };
+
+        let policy = BreachPolicy::from_str(&config.on_breach).unwrap_or_else(|err| {
+            warn!("{}, rejecting order", err);
+            BreachPolicy::Reject
+        });
+        let reason = format!(
+            "order notional {} breaches {}, headroom {}",
+            notional.round_with(2),
+            reason,
+            allowed.round_with(2)
+        );
+        let allowed_size = allowed / price.clone();
+        let resized = match fractional {
+            true => min(size.clone(), allowed_size),
+            false => min(size.clone(), allowed_size.trunc()),
+        };
+        match policy {
+            BreachPolicy::Resize if resized.is_positive() => {
+                ExposureDecision::Resize(resized, reason)
+            }
+            _ => ExposureDecision::Reject(reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::ExposureConfig;
+
+    fn build_limits(on_breach: &str) -> ExposureLimits {
+        let config = ExposureConfig {
+            max_gross_pc: Some(100.0),
+            max_net_pc: None,
+            max_symbol_notional: Some(5000.0),
+            max_sector_pc: Some(20.0),
+            sector_file: None,
+            on_breach: on_breach.to_string(),
+        };
+        let sectors = HashMap::from([
+            ("AAPL".to_string(), "tech".to_string()),
+            ("MSFT".to_string(), "tech".to_string()),
+        ]);
+        ExposureLimits {
+            config: Some(config),
+            sectors,
+        }
+    }
+
+    fn build_position(symbol: &str, price: f64, quantity: i64) -> MktPosition {
+        MktPosition {
+            symbol: symbol.to_string(),
+            avg_price: to_num!(price),
+            quantity: Num::from(quantity),
+            direction: Direction::Long,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_accept_within_limits() {
+        let limits = build_limits("reject");
+        let decision = limits.check(
+            "XOM",
+            Direction::Long,
+            &Num::from(10),
+            &to_num!(100.0),
+            &to_num!(10000.0),
+            &[build_position("AAPL", 100.0, 10)],
+            false,
+        );
+        assert_eq!(decision, ExposureDecision::Accept);
+    }
+
+    #[test]
+    fn test_resize_on_sector_limit() {
+        let limits = build_limits("resize");
+        let decision = limits.check(
+            "MSFT",
+            Direction::Long,
+            &Num::from(10),
+            &to_num!(100.0),
+            &to_num!(10000.0),
+            &[build_position("AAPL", 100.0, 15)],
+            false,
+        );
+        match decision {
+            ExposureDecision::Resize(size, _) => assert_eq!(size, Num::from(5)),
+            _ => panic!("Expected resize, found {}", decision),
+        }
+    }
+
+    #[test]
+    fn test_reject_on_sector_limit() {
+        let limits = build_limits("reject");
+        let decision = limits.check(
+            "MSFT",
+            Direction::Long,
+            &Num::from(10),
+            &to_num!(100.0),
+            &to_num!(10000.0),
+            &[build_position("AAPL", 100.0, 20)],
+            false,
+        );
+        assert!(matches!(decision, ExposureDecision::Reject(_)));
+    }
+
+    #[test]
+    fn test_resize_keeps_fractions_when_fractional() {
+        let limits = build_limits("resize");
+        let resize = |fractional| {
+            limits.check(
+                "MSFT",
+                Direction::Long,
+                &Num::from(10),
+                &to_num!(80.0),
+                &to_num!(10000.0),
+                &[build_position("AAPL", 100.0, 15)],
+                fractional,
+            )
+        };
+        match resize(false) {
+            ExposureDecision::Resize(size, _) => assert_eq!(size, Num::from(6)),
+            decision => panic!("Expected resize, found {}", decision),
+        }
+        match resize(true) {
+            ExposureDecision::Resize(size, _) => assert_eq!(size, to_num!(625, 100)),
+            decision => panic!("Expected resize, found {}", decision),
+        }
+    }
+}
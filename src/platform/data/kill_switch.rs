@@ -0,0 +1,170 @@
+use anyhow::bail;
+use anyhow::Ok;
+use anyhow::Result;
+use chrono::DateTime;
+use chrono::Utc;
+use sqlx::postgres::PgArguments;
+use sqlx::postgres::PgRow;
+use sqlx::query::Query;
+use sqlx::FromRow;
+use sqlx::Postgres;
+use sqlx::Row;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::info;
+use tracing::warn;
+use uuid::Uuid;
+
+use super::db_client::DBClient;
+use crate::settings::KillSwitchConfig;
+use crate::Settings;
+
+#[derive(Debug, Clone, Default)]
+pub struct HaltState {
+    pub local_id: Uuid,
+    pub halted: bool,
+    pub reason: String,
+    pub cancel_orders: bool,
+    pub liquidate: bool,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl fmt::Display for HaltState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Halt halted[{}], reason[{}], cancel_orders[{}], liquidate[{}], since[{}]",
+            self.halted, self.reason, self.cancel_orders, self.liquidate, self.updated_at
+        )
+    }
+}
+
+impl FromRow<'_, PgRow> for HaltState {
+    fn from_row(row: &PgRow) -> sqlx::Result<Self> {
+        sqlx::Result::Ok(Self {
+            local_id: row.try_get("local_id")?,
+            halted: row.try_get("halted")?,
+            reason: row.try_get("reason")?,
+            cancel_orders: row.try_get("cancel_orders")?,
+            liquidate: row.try_get("liquidate")?,
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
+}
+
+impl HaltState {
+    fn build_query<'a>(&'a self, stmt: &'a str) -> Query<'a, Postgres, PgArguments> {
+        sqlx::query(stmt)
+            .bind(self.halted)
+            .bind(self.reason.clone())
+            .bind(self.cancel_orders)
+            .bind(self.liquidate)
+            .bind(self.updated_at)
+            .bind(self.local_id)
+    }
+
+    async fn persist_db(&mut self, db: &Arc<DBClient>) -> Result<()> {
+        let columns = vec![
+            "halted",
+            "reason",
+            "cancel_orders",
+            "liquidate",
+            "updated_at",
+            "local_id",
+        ];
+
+        let stmt = db.get_sql_stmt("kill_switch", &self.local_id, columns, db);
+        if Uuid::is_nil(&self.local_id) {
+            self.local_id = Uuid::new_v4();
+        }
+
+        if let Err(err) = self.build_query(&stmt).execute(&db.pool).await {
+            bail!("Kill switch failed to publish to db, error={}", err)
+        }
+        Ok(())
+    }
+}
+
+pub struct KillSwitch {
+    db: Arc<DBClient>,
+    config: KillSwitchConfig,
+    state: HaltState,
+}
+
+impl KillSwitch {
+    pub fn new(settings: &Settings, db: &Arc<DBClient>) -> Self {
+        KillSwitch {
+            db: Arc::clone(db),
+            config: settings.kill_switch.clone().unwrap_or_default(),
+            state: HaltState::default(),
+        }
+    }
+
+    pub async fn startup(&mut self) -> Result<()> {
+        let stmt = self
+            .db
+            .query_builder
+            .prepare_fetch_statement("kill_switch", &Vec::default());
+        let rows = match sqlx::query_as::<_, HaltState>(&stmt)
+            .fetch_all(&self.db.pool)
+            .await
+        {
+            sqlx::Result::Ok(val) => val,
+            Err(err) => bail!("Failed to fetch kill switch state from db, error={}", err),
+        };
+        if let Some(state) = rows.into_iter().max_by_key(|state| state.updated_at) {
+            if state.halted {
+                warn!("Restoring halted trading state from db, {}", state);
+            }
+            self.state = state;
+        }
+        Ok(())
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.state.halted
+    }
+
+    pub fn get_state(&self) -> &HaltState {
+        &self.state
+    }
+
+    pub fn default_cancel_orders(&self) -> bool {
+        self.config.cancel_orders
+    }
+
+    pub fn default_liquidate(&self) -> bool {
+        self.config.liquidate
+    }
+
+    pub fn is_flag_file_set(&self) -> bool {
+        match &self.config.flag_file {
+            Some(path) => Path::new(path).exists(),
+            None => false,
+        }
+    }
+
+    pub async fn halt(&mut self, reason: &str, cancel_orders: bool, liquidate: bool) -> Result<()> {
+        self.state.halted = true;
+        self.state.reason = reason.to_string();
+        self.state.cancel_orders = cancel_orders;
+        self.state.liquidate = liquidate;
+        self.state.updated_at = Utc::now();
+        warn!("Trading halted, {}", self.state);
+        self.state.persist_db(&self.db).await
+    }
+
+    pub async fn resume(&mut self) -> Result<()> {
+        if self.is_flag_file_set() {
+            bail!(
+                "Unable to resume trading, kill switch flag file still present: {:?}",
+                self.config.flag_file
+            )
+        }
+        self.state.halted = false;
+        self.state.updated_at = Utc::now();
+        info!("Trading resumed, {}", self.state);
+        self.state.persist_db(&self.db).await
+    }
+}
@@ -26,6 +26,7 @@ use super::DBClient;
 use super::MktData;
 use super::Settings;
 use crate::events::Direction;
+use crate::platform::mktdata::PriceSource;
 use crate::platform::mktdata::Snapshot;
 use crate::to_num;
 
@@ -131,6 +132,30 @@ impl Stop {
         }
     }
 
+    fn tighten(&mut self, percent: f64) -> bool {
+        let (last_price, stop_price, direction) = match self {
+            Stop::Atr(atr) => (&atr.last_price, &mut atr.stop_price, atr.direction),
+            Stop::Smart(trailing) => (
+                &trailing.current_price,
+                &mut trailing.stop_price,
+                trailing.direction,
+            ),
+        };
+        let offset = last_price.clone() * to_num!(percent / 100.0);
+        let tightened = match direction {
+            Direction::Long => last_price.clone() - offset,
+            Direction::Short => last_price.clone() + offset,
+        };
+        let is_tighter = match direction {
+            Direction::Long => tightened > *stop_price,
+            Direction::Short => tightened < *stop_price,
+        };
+        if is_tighter {
+            *stop_price = tightened;
+        }
+        is_tighter
+    }
+
     fn zone(&self) -> i16 {
         match self {
             Stop::Atr(atr) => atr.zone,
@@ -436,6 +461,43 @@ impl Locker {
         }
     }
 
+    pub async fn flush(&mut self) -> Result<()> {
+        for stop in self
+            .stops
+            .values_mut()
+            .filter(|stop| stop.status.ne(&LockerStatus::Finished))
+        {
+            stop.persist_to_db(&self.db).await?
+        }
+        Ok(())
+    }
+
+    pub fn get_tracked(&self) -> Vec<(Uuid, String)> {
+        self.stops
+            .values()
+            .filter(|stop| stop.status.ne(&LockerStatus::Finished))
+            .map(|stop| (stop.local_id, stop.symbol.clone()))
+            .collect()
+    }
+
+    pub fn get_stop_price(&self, locker_id: &Uuid) -> Option<Num> {
+        self.stops
+            .get(locker_id)
+            .filter(|stop| {
+                stop.status == LockerStatus::Active
+                    && stop.transact_type == TransactionType::Position
+            })
+            .map(|stop| stop.stop.stop_price())
+            .filter(|price| !price.is_zero())
+    }
+
+    pub fn get_initial_stop_price(&self, locker_id: &Uuid) -> Option<Num> {
+        self.stops
+            .get(locker_id)
+            .map(|stop| stop.stop.stop_price())
+            .filter(|price| !price.is_zero())
+    }
+
     pub async fn activate(&mut self, locker_id: Uuid) {
         if let Some(stop) = self.stops.get_mut(&locker_id) {
             stop.status = LockerStatus::Active;
@@ -443,6 +505,35 @@ impl Locker {
         }
     }
 
+    fn get_price_source(&self, strategy: &str) -> PriceSource {
+        self.settings
+            .strategies
+            .get(strategy)
+            .and_then(|cfg| cfg.price_source)
+            .unwrap_or_default()
+    }
+
+    pub async fn tighten_all(&mut self, percent: f64) {
+        for stop in self.stops.values_mut() {
+            if stop.status != LockerStatus::Active
+                || stop.transact_type != TransactionType::Position
+            {
+                continue;
+            }
+            if stop.stop.tighten(percent) {
+                info!(
+                    "Strategy[{}] symbol[{}] stop tightened to {}",
+                    stop.strategy,
+                    stop.symbol,
+                    stop.stop.stop_price().round_with(2)
+                );
+                if let Err(err) = stop.persist_to_db(&self.db).await {
+                    error!("Failed to persist tightened stop to db, error={}", err);
+                }
+            }
+        }
+    }
+
     pub fn print_stop(&mut self, locker_id: &Uuid) -> String {
         match self.stops.get_mut(locker_id) {
             Some(stop) => format!("{}", stop),
@@ -462,13 +553,16 @@ impl Locker {
 
         async fn check_should_close(
             snapshot: &Snapshot,
+            price_source: PriceSource,
             smart: &mut SmartStop,
             mktdata: &Arc<Mutex<MktData>>,
         ) -> Result<Num> {
             if smart.status.ne(&LockerStatus::Active) {
                 bail!("Not active");
             }
-            let last_price = snapshot.mid_price.clone();
+            let Some(last_price) = snapshot.get_price(price_source, smart.direction) else {
+                bail!("No {} price available", price_source);
+            };
             let stop_price = smart
                 .stop
                 .price_update(
@@ -496,9 +590,11 @@ impl Locker {
             Ok(stop_price)
         }
 
+        let strategy = self.stops[locker_id].strategy.clone();
+        let price_source = self.get_price_source(&strategy);
         if let Some(smart) = self.stops.get_mut(locker_id) {
             if let anyhow::Result::Ok(stop_price) =
-                check_should_close(snapshot, smart, &self.mktdata).await
+                check_should_close(snapshot, price_source, smart, &self.mktdata).await
             {
                 if smart.transact_type == TransactionType::Position {
                     let _ = smart.persist_to_db(&self.db).await;
@@ -15,9 +15,11 @@ use std::collections::HashMap;
 use std::fmt;
 use std::str::FromStr;
 use std::sync::Arc;
+use tracing::info;
 use uuid::Uuid;
 
 use super::db_client::DBClient;
+use super::Transaction;
 use crate::events::Direction;
 use crate::events::Side;
 use crate::platform::web_clients::Connectors;
@@ -28,8 +30,27 @@ pub enum OrderStatus {
     #[default]
     Waiting,
     New,
+    PartiallyFilled,
     Filled,
+    DoneForDay,
     Cancelled,
+    Rejected,
+    Expired,
+    Replaced,
+    Held,
+}
+
+impl OrderStatus {
+    pub fn is_open(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::Waiting
+                | OrderStatus::New
+                | OrderStatus::PartiallyFilled
+                | OrderStatus::DoneForDay
+                | OrderStatus::Held
+        )
+    }
 }
 
 impl fmt::Display for OrderStatus {
@@ -45,8 +66,14 @@ impl FromStr for OrderStatus {
         match val {
             "Waiting" => std::result::Result::Ok(OrderStatus::Waiting),
             "New" => std::result::Result::Ok(OrderStatus::New),
+            "PartiallyFilled" => std::result::Result::Ok(OrderStatus::PartiallyFilled),
             "Filled" => std::result::Result::Ok(OrderStatus::Filled),
+            "DoneForDay" => std::result::Result::Ok(OrderStatus::DoneForDay),
             "Cancelled" => std::result::Result::Ok(OrderStatus::Cancelled),
+            "Rejected" => std::result::Result::Ok(OrderStatus::Rejected),
+            "Expired" => std::result::Result::Ok(OrderStatus::Expired),
+            "Replaced" => std::result::Result::Ok(OrderStatus::Replaced),
+            "Held" => std::result::Result::Ok(OrderStatus::Held),
             _ => Err(format!("Failed to parse order status, unknown: {}", val)),
         }
     }
@@ -57,6 +84,8 @@ pub enum OrderAction {
     Create,
     #[default]
     Liquidate,
+    Protect,
+    TakeProfit,
 }
 
 impl fmt::Display for OrderAction {
@@ -72,24 +101,37 @@ impl FromStr for OrderAction {
         match val {
             "Create" => std::result::Result::Ok(OrderAction::Create),
             "Liquidate" => std::result::Result::Ok(OrderAction::Liquidate),
+            "Protect" => std::result::Result::Ok(OrderAction::Protect),
+            "TakeProfit" => std::result::Result::Ok(OrderAction::TakeProfit),
             _ => Err(format!("Failed to parse order action, unknown: {}", val)),
         }
     }
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct Benchmarks {
+    pub signal_price: Num,
+    pub arrival_price: Num,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct MktOrder {
     pub local_id: Uuid,
+    pub client_order_id: String,
     pub strategy: String,
     pub symbol: String,
     pub side: Side,
     pub direction: Direction,
     pub action: OrderAction,
     pub entry_price: Num,
+    pub stop_price: Num,
+    pub signal_price: Num,
+    pub arrival_price: Num,
     pub fill_price: Num,
     pub entry_time: DateTime<Utc>,
     pub fill_time: DateTime<Utc>,
     pub quantity: Num,
+    pub filled_quantity: Num,
     pub status: OrderStatus,
 }
 
@@ -102,18 +144,30 @@ impl FromRow<'_, PgRow> for MktOrder {
             }
         }
 
+        fn sqlx_to_quantity(row: &PgRow, value: &str) -> sqlx::Result<Num> {
+            let val = row.try_get::<f64, _>(value)?;
+            std::result::Result::Ok(to_num!((val * 1e9).round() as i64, 1_000_000_000))
+        }
+
         sqlx::Result::Ok(Self {
             local_id: row.try_get("local_id")?,
+            client_order_id: row
+                .try_get::<Option<String>, _>("client_order_id")?
+                .unwrap_or_default(),
             strategy: row.try_get("strategy")?,
             symbol: row.try_get("symbol")?,
             side: Side::from_str(row.try_get("side")?).unwrap(),
             direction: Direction::from_str(row.try_get("direction")?).unwrap(),
             action: OrderAction::from_str(row.try_get("action")?).unwrap(),
             entry_price: sqlx_to_num(row, "entry_price")?,
+            stop_price: sqlx_to_num(row, "stop_price")?,
+            signal_price: sqlx_to_num(row, "signal_price")?,
+            arrival_price: sqlx_to_num(row, "arrival_price")?,
             fill_price: sqlx_to_num(row, "fill_price")?,
             entry_time: row.try_get("entry_time")?,
             fill_time: row.try_get("fill_time")?,
-            quantity: Num::from(row.try_get::<i64, &str>("quantity")?),
+            quantity: sqlx_to_quantity(row, "quantity")?,
+            filled_quantity: sqlx_to_quantity(row, "filled_quantity")?,
             status: OrderStatus::from_str(row.try_get("status")?).unwrap(),
         })
     }
@@ -152,11 +206,16 @@ impl MktOrder {
             .bind(self.side.to_string())
             .bind(self.direction.to_string())
             .bind(self.entry_price.round_with(3).to_f64())
+            .bind(self.stop_price.round_with(3).to_f64())
+            .bind(self.signal_price.round_with(3).to_f64())
+            .bind(self.arrival_price.round_with(3).to_f64())
             .bind(self.fill_price.round_with(3).to_f64())
             .bind(self.entry_time)
             .bind(self.fill_time)
-            .bind(self.quantity.to_i64())
+            .bind(self.quantity.to_f64())
+            .bind(self.filled_quantity.to_f64())
             .bind(self.status.to_string())
+            .bind(self.client_order_id.to_string())
             .bind(self.local_id)
     }
 
@@ -168,11 +227,16 @@ impl MktOrder {
             "side",
             "direction",
             "entry_price",
+            "stop_price",
+            "signal_price",
+            "arrival_price",
             "fill_price",
             "entry_time",
             "fill_time",
             "quantity",
+            "filled_quantity",
             "status",
+            "client_order_id",
             "local_id",
         ];
 
@@ -197,11 +261,32 @@ impl MktOrder {
         Ok(())
     }
 
+    async fn assign_order_id(&mut self, order_id: Uuid, db: &Arc<DBClient>) -> Result<()> {
+        let columns = vec!["local_id", "client_order_id"];
+        let stmt = db
+            .query_builder
+            .prepare_update_statement("mktorder", &columns);
+        if let Err(err) = sqlx::query(&stmt)
+            .bind(order_id)
+            .bind(self.client_order_id.to_string())
+            .execute(&db.pool)
+            .await
+        {
+            bail!("Order failed to assign broker id in db, error={}", err)
+        }
+        self.local_id = order_id;
+        Ok(())
+    }
+
     async fn update_inner(&mut self, order: order::Order, db: Arc<DBClient>) -> Result<MktOrder> {
         if let Some(price) = order.limit_price {
             self.entry_price = price
         }
 
+        if let Some(price) = order.stop_price {
+            self.stop_price = price
+        }
+
         if let Some(price) = order.average_fill_price {
             self.fill_price = price;
         }
@@ -214,15 +299,24 @@ impl MktOrder {
             self.fill_time = time;
         }
 
+        self.client_order_id = order.client_order_id;
+
         if let order::Amount::Quantity { quantity } = order.amount {
             self.quantity = quantity;
         }
+        self.filled_quantity = order.filled_quantity;
 
         self.status = match order.status {
-            order::Status::Accepted => OrderStatus::Waiting,
+            order::Status::Accepted | order::Status::PendingNew => OrderStatus::Waiting,
             order::Status::New => OrderStatus::New,
+            order::Status::PartiallyFilled => OrderStatus::PartiallyFilled,
             order::Status::Filled => OrderStatus::Filled,
+            order::Status::DoneForDay => OrderStatus::DoneForDay,
             order::Status::Canceled => OrderStatus::Cancelled,
+            order::Status::Rejected => OrderStatus::Rejected,
+            order::Status::Expired => OrderStatus::Expired,
+            order::Status::Replaced => OrderStatus::Replaced,
+            order::Status::Held => OrderStatus::Held,
             _ => self.status,
         };
 
@@ -239,7 +333,7 @@ impl fmt::Display for MktOrder {
             self.strategy,
             self.symbol,
             self.entry_price.round_with(3).to_f64().unwrap(),
-            self.quantity.round_with(3).to_i64().unwrap(),
+            self.quantity.round_with(3).to_f64().unwrap(),
             self.action,
             self.status
         )
@@ -249,6 +343,7 @@ impl fmt::Display for MktOrder {
 pub struct MktOrders {
     connectors: Arc<Connectors>,
     mktorders: HashMap<Uuid, MktOrder>,
+    pending: HashMap<String, MktOrder>,
     db: Arc<DBClient>,
 }
 
@@ -257,6 +352,7 @@ impl MktOrders {
         MktOrders {
             connectors: Arc::clone(connectors),
             mktorders: HashMap::default(),
+            pending: HashMap::default(),
             db: Arc::clone(db),
         }
     }
@@ -290,6 +386,119 @@ impl MktOrders {
         Ok(mktorder)
     }
 
+    async fn load_prepared_from_db(&self, transaction_id: &Uuid) -> Result<Vec<MktOrder>> {
+        let prefix = transaction_id.simple().to_string();
+        let stmt = "SELECT * FROM mktorder WHERE client_order_id LIKE $1";
+        match sqlx::query_as::<_, MktOrder>(stmt)
+            .bind(format!("{}-%", prefix))
+            .fetch_all(&self.db.pool)
+            .await
+        {
+            sqlx::Result::Ok(mktorders) => Ok(mktorders),
+            Err(err) => bail!("Failed to fetch prepared orders from db, error={}", err),
+        }
+    }
+
+    // Rows prepared before a restart are resolved against the broker by their client id, those
+    // never received are kept as discarded so the leg numbering carries on past them
+    pub async fn recover_prepared(
+        &mut self,
+        transaction_id: &Uuid,
+        tracked: &[Uuid],
+    ) -> Result<Vec<MktOrder>> {
+        let mut recovered = Vec::new();
+        for mut mktorder in self.load_prepared_from_db(transaction_id).await? {
+            if tracked.contains(&mktorder.local_id) {
+                continue;
+            }
+            let client_order_id = mktorder.client_order_id.clone();
+            match self
+                .connectors
+                .get_order_by_client_id(&client_order_id)
+                .await?
+            {
+                Some(order) => {
+                    let order_id = order.id.0;
+                    if mktorder.local_id != order_id {
+                        mktorder.assign_order_id(order_id, &self.db).await?;
+                    }
+                    let mktorder = mktorder.update_inner(order, self.db.clone()).await?;
+                    info!(
+                        "Recovered order: {} for symbol: {} by client id: {}",
+                        order_id, mktorder.symbol, client_order_id
+                    );
+                    self.mktorders.insert(order_id, mktorder.clone());
+                    recovered.push(mktorder);
+                }
+                None => {
+                    if mktorder.status != OrderStatus::Rejected {
+                        info!(
+                            "Prepared order with client id: {} never reached the broker, discarding",
+                            client_order_id
+                        );
+                        mktorder.status = OrderStatus::Rejected;
+                        mktorder.persist_db(self.db.clone(), None).await?;
+                    }
+                    self.pending.insert(client_order_id, mktorder);
+                }
+            }
+        }
+        Ok(recovered)
+    }
+
+    pub fn get_client_order_id(&self, transaction_id: &Uuid, action: OrderAction) -> String {
+        let prefix = transaction_id.simple().to_string();
+        let leg = self
+            .mktorders
+            .values()
+            .chain(self.pending.values())
+            .filter(|mktorder| mktorder.client_order_id.starts_with(&prefix))
+            .count();
+        format!("{}-{}-{}", prefix, action.to_string().to_lowercase(), leg)
+    }
+
+    pub async fn prepare_order(
+        &mut self,
+        client_order_id: &str,
+        transaction: &Transaction,
+        action: OrderAction,
+        benchmarks: Benchmarks,
+    ) -> Result<()> {
+        let direction = transaction.direction;
+        let side = match (action, direction) {
+            (OrderAction::Create, Direction::Long) => Side::Buy,
+            (OrderAction::Create, Direction::Short) => Side::Sell,
+            (_, Direction::Long) => Side::Sell,
+            (_, Direction::Short) => Side::Buy,
+        };
+        let mut mktorder = MktOrder {
+            local_id: Uuid::nil(),
+            client_order_id: client_order_id.to_string(),
+            strategy: transaction.strategy.to_string(),
+            symbol: transaction.symbol.to_string(),
+            side,
+            direction,
+            action,
+            signal_price: benchmarks.signal_price,
+            arrival_price: benchmarks.arrival_price,
+            ..Default::default()
+        };
+        mktorder
+            .persist_db(self.db.clone(), Some(Uuid::new_v4()))
+            .await?;
+        self.pending.insert(client_order_id.to_string(), mktorder);
+        Ok(())
+    }
+
+    // Pending orders are kept once discarded so the leg numbering never hands out their id again
+    pub async fn discard_order(&mut self, client_order_id: &str) -> Result<()> {
+        if let Some(mktorder) = self.pending.get_mut(client_order_id) {
+            mktorder.status = OrderStatus::Rejected;
+            mktorder.persist_db(self.db.clone(), None).await?;
+        }
+        Ok(())
+    }
+
     pub async fn add_order(
         &mut self,
         order_id: Uuid,
@@ -299,17 +508,25 @@ impl MktOrders {
         direction: Direction,
         action: OrderAction,
     ) -> Result<MktOrder> {
-        let mut mktorder = MktOrder::new(
-            order_id,
-            action,
-            strategy,
-            symbol,
-            side,
-            direction,
-            Some(&self.db),
-        )
-        .await?;
         let order = self.connectors.get_order(order_id).await?;
+        let mut mktorder = match self.pending.remove(&order.client_order_id) {
+            Some(mut mktorder) => {
+                mktorder.assign_order_id(order_id, &self.db).await?;
+                mktorder
+            }
+            None => {
+                MktOrder::new(
+                    order_id,
+                    action,
+                    strategy,
+                    symbol,
+                    side,
+                    direction,
+                    Some(&self.db),
+                )
+                .await?
+            }
+        };
         let _ = mktorder.update_inner(order, self.db.clone()).await?;
 
         self.mktorders.insert(order_id, mktorder.clone());
@@ -333,6 +550,32 @@ impl MktOrders {
         self.mktorders.get(order_id)
     }
 
+    pub async fn find_replacement(&self, order_id: &Uuid) -> Result<Uuid> {
+        let relations = self.connectors.get_order_relations(*order_id).await?;
+        match relations.replaced_by {
+            Some(new_order_id) => Ok(new_order_id.0),
+            None => bail!("Replacement for order_id: {} not found", order_id),
+        }
+    }
+
+    pub async fn find_replaced(&self, order: &order::Order) -> Result<Uuid> {
+        let relations = self.connectors.get_order_relations(order.id.0).await?;
+        match relations.replaces {
+            Some(old_order_id) if self.mktorders.contains_key(&old_order_id.0) => {
+                Ok(old_order_id.0)
+            }
+            _ => bail!(
+                "Replaced order for new order_id: {} symbol: {} not found",
+                order.id.0,
+                order.symbol
+            ),
+        }
+    }
+
+    pub async fn get_broker_orders(&self) -> Result<Vec<order::Order>> {
+        self.connectors.get_orders().await
+    }
+
     pub async fn update_orders(&mut self) -> Result<&HashMap<Uuid, MktOrder>> {
         let orders = self.connectors.get_orders().await?;
         for order in &orders {
@@ -343,3 +586,72 @@ impl MktOrders {
         Ok(&self.mktorders)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::DatabaseConfig;
+    use crate::Settings;
+
+    // tests require local db setup name=test user=test pass=test
+    #[tokio::test]
+    async fn test_fractional_quantity_round_trip() {
+        let schema = format!("fractional_{}", Uuid::new_v4().simple());
+        let settings = Settings {
+            database: DatabaseConfig {
+                name: "test".to_string(),
+                port: 5432,
+                host: "0.0.0.0".to_string(),
+                user: "test".to_string(),
+                password: Some("test".to_string()),
+                schema: Some(schema.clone()),
+            },
+            ..Default::default()
+        };
+        let db = DBClient::new(&settings).await.unwrap();
+
+        let order_id = Uuid::new_v4();
+        let mut mktorder = MktOrder {
+            strategy: "auto01".to_string(),
+            symbol: "AAPL".to_string(),
+            quantity: to_num!(25, 100),
+            filled_quantity: to_num!(125, 1000),
+            ..Default::default()
+        };
+        mktorder
+            .persist_db(db.clone(), Some(order_id))
+            .await
+            .unwrap();
+        let stmt = db
+            .query_builder
+            .prepare_fetch_statement("mktorder", &vec!["local_id"]);
+        let fetched = sqlx::query_as::<_, MktOrder>(&stmt)
+            .bind(order_id)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(fetched.quantity, to_num!(25, 100));
+        assert_eq!(fetched.filled_quantity, to_num!(125, 1000));
+
+        let mut transaction = Transaction {
+            strategy: "auto01".to_string(),
+            symbol: "AAPL".to_string(),
+            quantity: to_num!(25, 100),
+            ..Default::default()
+        };
+        transaction.persist_db(db.clone()).await.unwrap();
+        let stmt = db
+            .query_builder
+            .prepare_fetch_statement("transaction", &vec!["local_id"]);
+        let fetched = sqlx::query_as::<_, Transaction>(&stmt)
+            .bind(transaction.local_id)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(fetched.quantity, to_num!(25, 100));
+
+        let _ = sqlx::query(&format!("DROP SCHEMA {} CASCADE", schema))
+            .execute(&db.pool)
+            .await;
+    }
+}
@@ -1,3 +1,4 @@
+use anyhow::bail;
 use anyhow::Result;
 use apca::api::v2::asset::Exchange;
 use apca::api::v2::position::Position;
@@ -9,13 +10,13 @@ use uuid::Uuid;
 
 use super::super::web_clients::Connectors;
 use crate::events::Direction;
-use crate::to_num;
 
 #[derive(Debug, Clone, Default)]
 pub struct MktPosition {
     pub local_id: Uuid,
     pub symbol: String,
     pub strategy: String,
+    pub entry_price: Num,
     pub avg_price: Num,
     pub quantity: Num,
     pub cost_basis: Num,
@@ -39,16 +40,16 @@ impl MktPosition {
             Some(price) => price.clone(),
             None => entry_price,
         };
-        self.quantity = position.quantity.clone();
-        self.cost_basis = position.cost_basis.clone();
-        self.pnl = self.get_pnl(&position);
+        self.cost_basis = self.entry_price.clone() * self.quantity.clone();
+        self.pnl = self.get_pnl();
         self
     }
 
-    fn get_pnl(&self, position: &Position) -> Num {
-        match &position.unrealized_gain_total {
-            Some(val) => val.clone(),
-            None => to_num!(0.0),
+    fn get_pnl(&self) -> Num {
+        let pnl = (self.avg_price.clone() - self.entry_price.clone()) * self.quantity.clone();
+        match self.direction {
+            Direction::Long => pnl,
+            Direction::Short => -pnl,
         }
     }
 }
@@ -69,7 +70,7 @@ impl fmt::Display for MktPosition {
 
 pub struct MktPositions {
     connectors: Arc<Connectors>,
-    positions: HashMap<String, MktPosition>,
+    positions: HashMap<(String, String), MktPosition>,
 }
 
 impl MktPositions {
@@ -80,28 +81,60 @@ impl MktPositions {
         }
     }
 
-    pub fn add_position(&mut self, strategy: &str, symbol: &str, direction: Direction) {
-        let position = MktPosition::new(strategy, symbol, direction);
-        self.positions.insert(symbol.to_string(), position);
+    pub fn add_position(
+        &mut self,
+        strategy: &str,
+        symbol: &str,
+        direction: Direction,
+        quantity: &Num,
+        entry_price: &Num,
+    ) {
+        let position = self
+            .positions
+            .entry((strategy.to_string(), symbol.to_string()))
+            .or_insert_with(|| MktPosition::new(strategy, symbol, direction));
+        position.direction = direction;
+        position.quantity = quantity.clone();
+        position.entry_price = entry_price.clone();
+    }
+
+    pub fn remove_position(&mut self, strategy: &str, symbol: &str) {
+        self.positions
+            .remove(&(strategy.to_string(), symbol.to_string()));
     }
 
     pub async fn update_position(
         &mut self,
+        strategy: &str,
         symbol: &str,
         exchange: Exchange,
     ) -> Result<MktPosition> {
         let position = self.connectors.get_position(symbol, exchange).await?;
-        if let Some(mktposition) = self.positions.get_mut(symbol) {
-            Ok(mktposition.update_inner(position).clone())
-        } else {
-            panic!("MktPosition key not found in collection")
+        match self
+            .positions
+            .get_mut(&(strategy.to_string(), symbol.to_string()))
+        {
+            Some(mktposition) => Ok(mktposition.update_inner(position).clone()),
+            None => bail!(
+                "MktPosition for strategy: {} symbol: {} not found",
+                strategy,
+                symbol
+            ),
         }
     }
 
+    pub async fn get_broker_positions(&self) -> Result<Vec<Position>> {
+        self.connectors.get_positions().await
+    }
+
     pub async fn update_positions(&mut self) -> Result<Vec<MktPosition>> {
         let positions = self.connectors.get_positions().await?;
         for position in &positions {
-            if let Some(mktposition) = self.positions.get_mut(&position.symbol) {
+            for mktposition in self
+                .positions
+                .values_mut()
+                .filter(|mktposition| mktposition.symbol == position.symbol)
+            {
                 mktposition.update_inner(position.clone());
             }
         }
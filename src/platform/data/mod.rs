@@ -1,6 +1,7 @@
 use anyhow::bail;
 use anyhow::Ok;
 use anyhow::Result;
+use apca::api::v2::order;
 use chrono::DateTime;
 use chrono::Utc;
 use num_decimal::Num;
@@ -21,11 +22,20 @@ use tracing::warn;
 use uuid::Uuid;
 
 pub mod account;
+pub mod adoption;
 pub mod assets;
-mod db_client;
+pub mod db_client;
+pub mod execution;
+pub mod exposure;
+pub mod kill_switch;
 mod locker;
 pub mod mktorder;
 pub mod mktposition;
+pub mod pre_trade;
+pub mod protective_stop;
+pub mod reconciler;
+pub mod risk_limits;
+mod transaction_event;
 
 use super::mktdata::MktData;
 use super::mktdata::Snapshot;
@@ -37,12 +47,14 @@ use assets::Assets;
 use db_client::DBClient;
 use locker::Locker;
 use locker::TransactionType;
+use mktorder::Benchmarks;
 use mktorder::MktOrder;
 use mktorder::MktOrders;
 use mktorder::OrderAction;
 use mktorder::OrderStatus;
 use mktposition::MktPosition;
 use mktposition::MktPositions;
+use transaction_event::TransactionEvent;
 
 use crate::Settings;
 
@@ -50,11 +62,42 @@ use crate::Settings;
 pub enum TransactionStatus {
     #[default]
     Waiting,
+    PartiallyFilled,
     Confirmed,
+    Closing,
     Cancelled,
     Complete,
 }
 
+impl TransactionStatus {
+    pub fn can_transition(&self, next: TransactionStatus) -> bool {
+        use TransactionStatus::*;
+        matches!(
+            (self, next),
+            (Waiting, PartiallyFilled | Confirmed | Cancelled)
+                | (PartiallyFilled, Confirmed | Closing | Complete | Cancelled)
+                | (Confirmed, Closing | Complete | Cancelled)
+                | (Closing, Confirmed | Complete | Cancelled)
+        )
+    }
+
+    pub fn is_open(&self) -> bool {
+        !matches!(
+            self,
+            TransactionStatus::Cancelled | TransactionStatus::Complete
+        )
+    }
+
+    pub fn has_position(&self) -> bool {
+        matches!(
+            self,
+            TransactionStatus::PartiallyFilled
+                | TransactionStatus::Confirmed
+                | TransactionStatus::Closing
+        )
+    }
+}
+
 impl fmt::Display for TransactionStatus {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:?}", self)
@@ -67,7 +110,9 @@ impl FromStr for TransactionStatus {
     fn from_str(val: &str) -> Result<Self, Self::Err> {
         match val {
             "Waiting" => std::result::Result::Ok(TransactionStatus::Waiting),
+            "PartiallyFilled" => std::result::Result::Ok(TransactionStatus::PartiallyFilled),
             "Confirmed" => std::result::Result::Ok(TransactionStatus::Confirmed),
+            "Closing" => std::result::Result::Ok(TransactionStatus::Closing),
             "Cancelled" => std::result::Result::Ok(TransactionStatus::Cancelled),
             "Complete" => std::result::Result::Ok(TransactionStatus::Complete),
             _ => Err(format!(
@@ -95,6 +140,8 @@ pub struct Transaction {
     pub cost_basis: Num,
     pub direction: Direction,
     pub status: TransactionStatus,
+    pub reason: Option<String>,
+    pub exit_reason: Option<String>,
 }
 
 impl FromRow<'_, PgRow> for Transaction {
@@ -106,9 +153,14 @@ impl FromRow<'_, PgRow> for Transaction {
             }
         }
 
+        fn sqlx_to_quantity(row: &PgRow, value: &str) -> sqlx::Result<Num> {
+            let val = row.try_get::<f64, _>(value)?;
+            std::result::Result::Ok(to_num!((val * 1e9).round() as i64, 1_000_000_000))
+        }
+
         fn get_orders_from_row(order_str: &str) -> Result<Vec<Uuid>> {
             if order_str.is_empty() {
-                bail!("Failed to pull orders from DB")
+                return Ok(Vec::default());
             }
             Ok(order_str
                 .split(',')
@@ -126,12 +178,14 @@ impl FromRow<'_, PgRow> for Transaction {
             exit_time: row.try_get("exit_time")?,
             entry_price: sqlx_to_num(row, "entry_price")?,
             exit_price: sqlx_to_num(row, "exit_price")?,
-            quantity: Num::from(row.try_get::<i64, &str>("quantity")?),
+            quantity: sqlx_to_quantity(row, "quantity")?,
             pnl: sqlx_to_num(row, "pnl")?,
             roi: sqlx_to_num(row, "roi")?,
             cost_basis: sqlx_to_num(row, "cost_basis")?,
             direction: Direction::from_str(row.try_get("direction")?).unwrap(),
             status: TransactionStatus::from_str(row.try_get("status")?).unwrap(),
+            reason: row.try_get("reason")?,
+            exit_reason: row.try_get("exit_reason")?,
         })
     }
 }
@@ -153,36 +207,99 @@ impl Transaction {
             ..Default::default()
         };
         transaction.persist_db(db.clone()).await?;
+        transaction.record_event(None, "created", db).await;
         Ok(transaction)
     }
 
+    async fn record_event(
+        &self,
+        from_status: Option<TransactionStatus>,
+        cause: &str,
+        db: &Arc<DBClient>,
+    ) {
+        let event = TransactionEvent::new(self.local_id, from_status, self.status, cause);
+        if let Err(err) = event.persist_db(db).await {
+            warn!("{}", err);
+        }
+    }
+
+    async fn transition(
+        &mut self,
+        next: TransactionStatus,
+        cause: &str,
+        db: &Arc<DBClient>,
+    ) -> Result<()> {
+        if self.status == next {
+            return Ok(());
+        }
+        if !self.status.can_transition(next) {
+            bail!(
+                "Invalid transition for strategy: {} symbol: {} from {} to {}, cause: {}",
+                self.strategy,
+                self.symbol,
+                self.status,
+                next,
+                cause
+            )
+        }
+        let from_status = self.status;
+        self.status = next;
+        self.record_event(Some(from_status), cause, db).await;
+        info!(
+            "Strategy[{}] symbol[{}] transaction {} -> {}, cause: {}",
+            self.strategy, self.symbol, from_status, next, cause
+        );
+        Ok(())
+    }
+
     fn calculate_roi(&self) -> Num {
         self.pnl.clone() / self.cost_basis.clone() * to_num!(100.00)
     }
 
+    fn calculate_pnl(&self, price: &Num) -> Num {
+        let pnl = (price.clone() - self.entry_price.clone()) * self.quantity.clone();
+        match self.direction {
+            Direction::Long => pnl,
+            Direction::Short => -pnl,
+        }
+    }
+
     async fn update_from_position(&mut self, position: &MktPosition, db: &Arc<DBClient>) {
         self.cost_basis = position.cost_basis.clone();
         self.pnl = position.pnl.clone();
-        self.roi = self.calculate_roi();
+        if !self.cost_basis.is_zero() {
+            self.roi = self.calculate_roi();
+        }
         let _ = self.persist_db(db.clone()).await;
     }
 
     async fn update_from_order(&mut self, order: &MktOrder, db: &Arc<DBClient>) {
         match order.action {
             OrderAction::Create => {
-                if order.status.eq(&OrderStatus::Filled) {
+                if !order.filled_quantity.is_zero() {
                     self.entry_time = order.fill_time;
                     self.entry_price = order.fill_price.clone();
-                    self.quantity = order.quantity.clone();
-                    if self.status.eq(&TransactionStatus::Waiting) {
-                        self.status = TransactionStatus::Confirmed;
+                    self.quantity = order.filled_quantity.clone();
+                    let next = match order.status.is_open() {
+                        true => (TransactionStatus::PartiallyFilled, "entry_partial_fill"),
+                        false => (TransactionStatus::Confirmed, "entry_fill"),
+                    };
+                    if matches!(
+                        self.status,
+                        TransactionStatus::Waiting | TransactionStatus::PartiallyFilled
+                    ) {
+                        if let Err(err) = self.transition(next.0, next.1, db).await {
+                            warn!("{}", err);
+                        }
                     }
                 }
             }
-            OrderAction::Liquidate => {
+            OrderAction::Liquidate | OrderAction::Protect | OrderAction::TakeProfit => {
                 if order.status.eq(&OrderStatus::Filled) {
                     self.exit_time = order.fill_time;
-                    self.exit_price = order.fill_price.clone();
+                    if self.exit_price.is_zero() {
+                        self.exit_price = order.fill_price.clone();
+                    }
                 }
             }
         };
@@ -200,27 +317,47 @@ impl Transaction {
         let _ = self.persist_db(db.clone()).await;
     }
 
-    async fn cancel(&mut self, order: &MktOrder, db: &Arc<DBClient>) {
-        self.status = TransactionStatus::Cancelled;
+    pub fn is_open(&self) -> bool {
+        self.status.is_open()
+    }
+
+    async fn cancel(&mut self, order: &MktOrder, reason: &str, db: &Arc<DBClient>) -> Result<()> {
+        self.transition(TransactionStatus::Cancelled, reason, db)
+            .await?;
+        self.reason = Some(reason.to_string());
         self.update_from_order(order, db).await;
+        Ok(())
     }
 
-    async fn zombie(&mut self, db: &Arc<DBClient>) {
-        self.status = TransactionStatus::Cancelled;
-        let _ = self.persist_db(db.clone()).await;
+    async fn zombie(&mut self, db: &Arc<DBClient>) -> Result<()> {
+        self.transition(TransactionStatus::Cancelled, "zombie", db)
+            .await?;
+        self.persist_db(db.clone()).await
     }
 
     async fn complete(
         &mut self,
         order: &MktOrder,
-        position: Option<&MktPosition>,
+        exit_price: Num,
         db: &Arc<DBClient>,
-    ) {
-        self.status = TransactionStatus::Complete;
-        self.update_from_order(order, db).await;
-        if let Some(position) = position {
-            self.update_from_position(position, db).await;
+    ) -> Result<()> {
+        let cause = self.exit_reason.clone().unwrap_or("liquidated".to_string());
+        self.transition(TransactionStatus::Complete, &cause, db)
+            .await?;
+        let exit_price = match exit_price.is_zero() {
+            true => order.fill_price.clone(),
+            false => exit_price,
+        };
+        if !exit_price.is_zero() {
+            self.exit_price = exit_price.clone();
+            self.cost_basis = self.entry_price.clone() * self.quantity.clone();
+            self.pnl = self.calculate_pnl(&exit_price);
+            if !self.cost_basis.is_zero() {
+                self.roi = self.calculate_roi();
+            }
         }
+        self.update_from_order(order, db).await;
+        Ok(())
     }
 
     fn build_query<'a>(
@@ -237,12 +374,14 @@ impl Transaction {
             .bind(self.exit_time)
             .bind(self.entry_price.round_with(3).to_f64())
             .bind(self.exit_price.round_with(3).to_f64())
-            .bind(self.quantity.to_i64())
+            .bind(self.quantity.to_f64())
             .bind(self.pnl.round_with(3).to_f64())
             .bind(self.roi.round_with(3).to_f64())
             .bind(self.cost_basis.round_with(3).to_f64())
             .bind(self.direction.to_string())
             .bind(self.status.to_string())
+            .bind(self.reason.clone())
+            .bind(self.exit_reason.clone())
             .bind(self.local_id)
     }
 
@@ -262,6 +401,8 @@ impl Transaction {
             "cost_basis",
             "direction",
             "status",
+            "reason",
+            "exit_reason",
             "local_id",
         ];
 
@@ -300,7 +441,7 @@ impl Transaction {
 }
 
 pub struct Transactions {
-    transactions: HashMap<String, Transaction>,
+    transactions: HashMap<(String, String), Transaction>,
     locker: Locker,
     db: Arc<DBClient>,
     mktorders: MktOrders,
@@ -311,10 +452,11 @@ pub struct Transactions {
 impl Transactions {
     pub async fn new(
         settings: &Settings,
+        db: &Arc<DBClient>,
         connectors: &Arc<Connectors>,
         mktdata: &Arc<Mutex<MktData>>,
     ) -> Result<Self> {
-        let db = DBClient::new(settings).await?;
+        let db = Arc::clone(db);
         let locker = Locker::new(settings, db.clone(), mktdata);
 
         let transactions = HashMap::new();
@@ -364,7 +506,12 @@ impl Transactions {
 
         let transactions = fetch_with_status(
             columns.clone(),
-            vec![TransactionStatus::Waiting, TransactionStatus::Confirmed],
+            vec![
+                TransactionStatus::Waiting,
+                TransactionStatus::PartiallyFilled,
+                TransactionStatus::Confirmed,
+                TransactionStatus::Closing,
+            ],
             &self.db,
         )
         .await;
@@ -372,67 +519,98 @@ impl Transactions {
         let mut orders = 0;
         let mut positions = 0;
         for mut transaction in transactions {
+            for mktorder in self
+                .mktorders
+                .recover_prepared(&transaction.local_id, &transaction.orders)
+                .await?
+            {
+                transaction.update_from_order(&mktorder, &self.db).await;
+            }
             match transaction.status {
-                TransactionStatus::Waiting => {
-                    let mktorder = &self
-                        .mktorders
-                        .load_from_db(&[transaction.orders[0]])
-                        .await?[0];
+                TransactionStatus::Waiting | TransactionStatus::PartiallyFilled => {
+                    let mktorders = self.mktorders.load_from_db(&transaction.orders).await?;
+                    let Some(mktorder) = get_entry_order(&mktorders) else {
+                        if let Err(err) = transaction.zombie(&self.db).await {
+                            warn!("{}", err);
+                        }
+                        self.locker.complete(transaction.locker).await;
+                        continue;
+                    };
                     match mktorder.status {
-                        OrderStatus::Cancelled => {
-                            transaction.cancel(mktorder, &self.db).await;
+                        OrderStatus::Cancelled | OrderStatus::Rejected | OrderStatus::Expired
+                            if mktorder.filled_quantity.is_zero() =>
+                        {
+                            let reason = mktorder.status.to_string().to_lowercase();
+                            if let Err(err) = transaction.cancel(mktorder, &reason, &self.db).await
+                            {
+                                warn!("{}", err);
+                            }
                             self.locker.complete(transaction.locker).await;
                             continue;
                         }
-                        OrderStatus::Filled => {
+                        OrderStatus::Filled
+                        | OrderStatus::PartiallyFilled
+                        | OrderStatus::Cancelled
+                        | OrderStatus::Rejected
+                        | OrderStatus::Expired => {
                             transaction.update_from_order(mktorder, &self.db).await;
                             self.mktpositions.add_position(
                                 &transaction.strategy,
                                 &transaction.symbol,
                                 transaction.direction,
+                                &transaction.quantity,
+                                &transaction.entry_price,
                             )
                         }
                         _ => (),
                     }
                     orders += 1;
                 }
-                TransactionStatus::Confirmed => {
-                    let symbol = &transaction.symbol;
+                TransactionStatus::Confirmed | TransactionStatus::Closing => {
                     self.mktpositions.add_position(
                         &transaction.strategy,
-                        symbol,
+                        &transaction.symbol,
                         transaction.direction,
+                        &transaction.quantity,
+                        &transaction.entry_price,
                     );
-                    let order_ids = &transaction.orders[1..];
-                    let orders = self.mktorders.load_from_db(order_ids).await?;
-                    if !order_ids.is_empty() && orders.is_empty() {
-                        transaction.zombie(&self.db).await;
+                    let orders = self.mktorders.load_from_db(&transaction.orders).await?;
+                    if !transaction.orders.is_empty() && orders.is_empty() {
+                        if let Err(err) = transaction.zombie(&self.db).await {
+                            warn!("{}", err);
+                        }
                         continue;
                     }
 
-                    let filled_quantity: i64 = orders
+                    // Replacement entries sit in the same list, only exit orders close the position
+                    let exits: Vec<&MktOrder> = orders
                         .iter()
-                        .map(|order| {
-                            if order.status.eq(&OrderStatus::Filled) {
-                                order.quantity.to_i64().unwrap()
-                            } else {
-                                0_i64
-                            }
-                        })
-                        .sum();
-                    if transaction.quantity == Num::from(filled_quantity) {
-                        transaction
-                            .complete(orders.last().unwrap(), None, &self.db)
-                            .await;
+                        .filter(|order| !matches!(order.action, OrderAction::Create))
+                        .collect();
+                    let filled_quantity = exits.iter().fold(Num::default(), |total, order| {
+                        total + order.filled_quantity.clone()
+                    });
+                    if !exits.is_empty() && transaction.quantity == filled_quantity {
+                        let exit_price = get_exit_price(exits.clone());
+                        if let Err(err) = transaction
+                            .complete(exits[exits.len() - 1], exit_price, &self.db)
+                            .await
+                        {
+                            warn!("{}", err);
+                        }
                         self.locker.complete(transaction.locker).await;
+                        self.mktpositions
+                            .remove_position(&transaction.strategy, &transaction.symbol);
                         continue;
                     }
                     positions += 1;
                 }
                 _ => (),
             }
-            self.transactions
-                .insert(transaction.symbol.clone(), transaction);
+            self.transactions.insert(
+                (transaction.strategy.clone(), transaction.symbol.clone()),
+                transaction,
+            );
         }
         info!(
             "Loaded {} positions and {} orders from db",
@@ -458,12 +636,12 @@ impl Transactions {
 
         for transaction in &mut self.transactions.values_mut() {
             match transaction.status {
-                TransactionStatus::Cancelled | TransactionStatus::Waiting => {
-                    if let Some(order) = self
-                        .mktorders
-                        .get_order(transaction.orders.first().unwrap())
+                TransactionStatus::Cancelled | TransactionStatus::Complete => (),
+                TransactionStatus::Waiting => {
+                    if let Some(order) = get_open_entry_order(&self.mktorders, transaction)
+                        .and_then(|order_id| self.mktorders.get_order(&order_id))
                     {
-                        if order.status == OrderStatus::New {
+                        if order.status.is_open() {
                             let stop = self.locker.print_stop(&transaction.locker);
                             info!("{} {}", order, stop);
                         }
@@ -473,7 +651,11 @@ impl Transactions {
                     let symbol = transaction.symbol.as_str();
                     if let anyhow::Result::Ok(position) = self
                         .mktpositions
-                        .update_position(symbol, self.assets.get_exchange(symbol))
+                        .update_position(
+                            &transaction.strategy,
+                            symbol,
+                            self.assets.get_exchange(symbol),
+                        )
                         .await
                     {
                         let stop = self.locker.print_stop(&transaction.locker);
@@ -492,15 +674,11 @@ impl Transactions {
     pub fn count_capacity(&self, strategy: &str) -> usize {
         self.transactions
             .values()
-            .filter(|transaction| {
-                transaction.strategy == strategy
-                    && (transaction.status == TransactionStatus::Waiting
-                        || transaction.status == TransactionStatus::Confirmed)
-            })
+            .filter(|transaction| transaction.strategy == strategy && transaction.is_open())
             .count()
     }
 
-    async fn update_order(&mut self, order_id: Uuid) -> Result<MktOrder> {
+    pub async fn update_order(&mut self, order_id: Uuid) -> Result<MktOrder> {
         self.mktorders.update_order(&order_id).await
     }
 
@@ -511,7 +689,7 @@ impl Transactions {
         entry_price: Num,
         direction: Direction,
     ) -> Result<()> {
-        if let Some(transaction) = self.transactions.get_mut(symbol) {
+        if let Some(transaction) = self.transactions.get_mut(&get_key(strategy, symbol)) {
             info!(
                 "Strategy[{}] locker tracking {} at entry_price: {}",
                 strategy, symbol, entry_price
@@ -530,32 +708,39 @@ impl Transactions {
             transaction.persist_db(self.db.clone()).await?
         } else {
             warn!(
-                "Unable to activate locker, transaction not found for symbol: {}",
-                symbol
+                "Unable to activate locker, transaction not found for strategy: {} symbol: {}",
+                strategy, symbol
             );
         };
         Ok(())
     }
 
-    pub async fn activate_stop(&mut self, symbol: &str) {
-        if let Some(transaction) = self.transactions.get_mut(symbol) {
+    pub async fn activate_stop(&mut self, strategy: &str, symbol: &str) {
+        if let Some(transaction) = self.transactions.get(&get_key(strategy, symbol)) {
             self.locker.activate(transaction.locker).await;
-            info!("Locker tracking symbol: {} activated", symbol);
+            info!(
+                "Strategy[{}] locker tracking symbol: {} activated",
+                strategy, symbol
+            );
         } else {
             warn!(
-                "Unable to update locker, transaction not found for symbol: {}",
-                symbol
+                "Unable to update locker, transaction not found for strategy: {} symbol: {}",
+                strategy, symbol
             );
         }
     }
 
-    pub async fn stop_complete(&mut self, symbol: &str) {
-        if let Some(transaction) = self.transactions.get(symbol) {
+    pub async fn tighten_stops(&mut self, percent: f64) {
+        self.locker.tighten_all(percent).await
+    }
+
+    pub async fn stop_complete(&mut self, strategy: &str, symbol: &str) {
+        if let Some(transaction) = self.transactions.get(&get_key(strategy, symbol)) {
             self.locker.complete(transaction.locker).await;
         } else {
             warn!(
-                "Unable to close locker, transaction not found for symbol: {}",
-                symbol
+                "Unable to close locker, transaction not found for strategy: {} symbol: {}",
+                strategy, symbol
             );
         };
     }
@@ -569,7 +754,8 @@ impl Transactions {
     ) -> Result<()> {
         let transaction =
             Transaction::new(symbol, strategy, direction, entry_price, &self.db).await?;
-        self.transactions.insert(symbol.to_string(), transaction);
+        self.transactions
+            .insert(get_key(strategy, symbol), transaction);
         info!(
             "Strategy[{}] symbol[{}] added a waiting transaction",
             strategy, symbol
@@ -577,23 +763,122 @@ impl Transactions {
         Ok(())
     }
 
+    pub async fn abandon_waiting(
+        &mut self,
+        strategy: &str,
+        symbol: &str,
+        reason: &str,
+    ) -> Result<()> {
+        let Some(transaction) = self.transactions.get_mut(&get_key(strategy, symbol)) else {
+            return Ok(());
+        };
+        if transaction.status != TransactionStatus::Waiting {
+            return Ok(());
+        }
+        transaction
+            .transition(TransactionStatus::Cancelled, reason, &self.db)
+            .await?;
+        transaction.reason = Some(reason.to_string());
+        transaction.persist_db(self.db.clone()).await?;
+        self.locker.complete(transaction.locker).await;
+        Ok(())
+    }
+
+    pub async fn set_reason(&mut self, strategy: &str, symbol: &str, reason: &str) -> Result<()> {
+        if let Some(transaction) = self.transactions.get_mut(&get_key(strategy, symbol)) {
+            transaction.reason = Some(reason.to_string());
+            transaction.persist_db(self.db.clone()).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn start_closing(
+        &mut self,
+        strategy: &str,
+        symbol: &str,
+        reason: &str,
+    ) -> Result<()> {
+        if let Some(transaction) = self.transactions.get_mut(&get_key(strategy, symbol)) {
+            transaction
+                .transition(TransactionStatus::Closing, reason, &self.db)
+                .await?;
+            transaction.exit_reason = Some(reason.to_string());
+            transaction.persist_db(self.db.clone()).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn reopen(&mut self, strategy: &str, symbol: &str, cause: &str) -> Result<()> {
+        if let Some(transaction) = self.transactions.get_mut(&get_key(strategy, symbol)) {
+            if transaction.status != TransactionStatus::Closing {
+                return Ok(());
+            }
+            transaction
+                .transition(TransactionStatus::Confirmed, cause, &self.db)
+                .await?;
+            transaction.exit_reason = None;
+            transaction.persist_db(self.db.clone()).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn get_positions(&mut self) -> Result<Vec<MktPosition>> {
+        self.mktpositions.update_positions().await
+    }
+
     pub fn get_assets(&self) -> &Assets {
         &self.assets
     }
 
-    pub fn get_transaction(&self, symbol: &str) -> Option<&Transaction> {
-        self.transactions.get(symbol)
+    pub fn get_transaction(&self, strategy: &str, symbol: &str) -> Option<&Transaction> {
+        self.transactions.get(&get_key(strategy, symbol))
+    }
+
+    pub fn get_transactions_with_status(&self, status: TransactionStatus) -> Vec<Transaction> {
+        self.transactions
+            .values()
+            .filter(|transaction| transaction.status == status)
+            .cloned()
+            .collect()
+    }
+
+    pub fn get_held_transactions(&self) -> Vec<Transaction> {
+        self.transactions
+            .values()
+            .filter(|transaction| {
+                matches!(
+                    transaction.status,
+                    TransactionStatus::PartiallyFilled | TransactionStatus::Confirmed
+                )
+            })
+            .cloned()
+            .collect()
+    }
+
+    pub fn get_open_transactions(&self, symbol: &str) -> Vec<Transaction> {
+        self.transactions
+            .values()
+            .filter(|transaction| transaction.symbol == symbol && transaction.is_open())
+            .cloned()
+            .collect()
+    }
+
+    pub fn has_open_transaction(&self, symbol: &str) -> bool {
+        self.transactions
+            .values()
+            .any(|transaction| transaction.symbol == symbol && transaction.is_open())
     }
 
     pub async fn confirm_transaction(&mut self, order_id: Uuid) -> Result<()> {
         let order = self.update_order(order_id).await?;
-        let symbol = order.symbol.clone();
-        if let Some(transaction) = self.transactions.get_mut(&symbol) {
+        let key = get_key(&order.strategy, &order.symbol);
+        if let Some(transaction) = self.transactions.get_mut(&key) {
             transaction.update_from_order(&order, &self.db).await;
             info!(
-                "Strategy[{}] symbol[{}], position confirmed",
-                transaction.strategy, transaction.symbol
+                "Strategy[{}] symbol[{}], position confirmed with quantity: {} of {}",
+                transaction.strategy, transaction.symbol, order.filled_quantity, order.quantity
             );
+            self.locker.activate(transaction.locker).await;
             self.locker
                 .start_tracking_position(transaction.locker)
                 .await?;
@@ -601,85 +886,269 @@ impl Transactions {
                 &transaction.strategy,
                 &transaction.symbol,
                 transaction.direction,
+                &transaction.quantity,
+                &transaction.entry_price,
             );
         }
         Ok(())
     }
 
+    pub async fn replace_order(&mut self, old_order_id: Uuid, new_order_id: Uuid) -> Result<()> {
+        let old_order = self.update_order(old_order_id).await?;
+        if self.mktorders.get_order(&new_order_id).is_some() {
+            debug!("Replacement order {} already tracked", new_order_id);
+            return Ok(());
+        }
+        info!(
+            "Order {} for symbol: {} replaced by {}",
+            old_order_id, old_order.symbol, new_order_id
+        );
+        self.add_order(
+            &old_order.strategy,
+            &old_order.symbol,
+            new_order_id,
+            old_order.side,
+            old_order.direction,
+            old_order.action,
+        )
+        .await
+    }
+
+    pub async fn find_replacement_order(&self, order_id: &Uuid) -> Result<Uuid> {
+        self.mktorders.find_replacement(order_id).await
+    }
+
+    pub async fn find_replaced_order(&self, order: &order::Order) -> Result<Uuid> {
+        self.mktorders.find_replaced(order).await
+    }
+
+    pub fn get_open_entry_order(&self, strategy: &str, symbol: &str) -> Option<Uuid> {
+        let transaction = self.transactions.get(&get_key(strategy, symbol))?;
+        get_open_entry_order(&self.mktorders, transaction)
+    }
+
+    pub fn get_pending_entries(&self) -> Vec<MktPosition> {
+        self.transactions
+            .values()
+            .filter(|transaction| {
+                matches!(
+                    transaction.status,
+                    TransactionStatus::Waiting | TransactionStatus::PartiallyFilled
+                )
+            })
+            .filter_map(|transaction| {
+                let order_id = get_open_entry_order(&self.mktorders, transaction)?;
+                let order = self.mktorders.get_order(&order_id)?;
+                let mut position =
+                    MktPosition::new(&order.strategy, &order.symbol, order.direction);
+                position.avg_price = if order.entry_price.is_zero() {
+                    transaction.entry_price.clone()
+                } else {
+                    order.entry_price.clone()
+                };
+                position.quantity = order.quantity.clone() - order.filled_quantity.clone();
+                Some(position)
+            })
+            .filter(|position| position.quantity.is_positive())
+            .collect()
+    }
+
+    pub fn get_protective_order(&self, strategy: &str, symbol: &str) -> Option<Uuid> {
+        let transaction = self.transactions.get(&get_key(strategy, symbol))?;
+        transaction
+            .orders
+            .iter()
+            .filter_map(|order_id| self.mktorders.get_order(order_id))
+            .filter(|order| matches!(order.action, OrderAction::Protect) && order.status.is_open())
+            .max_by_key(|order| order.entry_time)
+            .map(|order| order.local_id)
+    }
+
+    pub fn get_exit_orders(&self, strategy: &str, symbol: &str) -> Vec<Uuid> {
+        let Some(transaction) = self.transactions.get(&get_key(strategy, symbol)) else {
+            return Vec::new();
+        };
+        transaction
+            .orders
+            .iter()
+            .filter_map(|order_id| self.mktorders.get_order(order_id))
+            .filter(|order| {
+                matches!(order.action, OrderAction::Protect | OrderAction::TakeProfit)
+                    && order.status.is_open()
+            })
+            .map(|order| order.local_id)
+            .collect()
+    }
+
+    fn get_transaction_orders(&self, transaction: &Transaction) -> Vec<&MktOrder> {
+        transaction
+            .orders
+            .iter()
+            .filter_map(|order_id| self.mktorders.get_order(order_id))
+            .collect()
+    }
+
+    pub fn get_unfilled_exit_quantity(&self, strategy: &str, symbol: &str) -> Num {
+        let Some(transaction) = self.transactions.get(&get_key(strategy, symbol)) else {
+            return Num::default();
+        };
+        self.get_transaction_orders(transaction)
+            .into_iter()
+            .filter(|order| !matches!(order.action, OrderAction::Create))
+            .fold(transaction.quantity.clone(), |remaining, order| {
+                remaining - order.filled_quantity.clone()
+            })
+    }
+
+    pub fn get_stop_price(&self, transaction: &Transaction) -> Option<Num> {
+        self.locker.get_stop_price(&transaction.locker)
+    }
+
+    pub fn get_initial_stop(&self, strategy: &str, symbol: &str) -> Option<Num> {
+        let transaction = self.transactions.get(&get_key(strategy, symbol))?;
+        self.locker.get_initial_stop_price(&transaction.locker)
+    }
+
     pub async fn close_transaction(&mut self, order_id: Uuid) -> Result<()> {
         let order = self.mktorders.update_order(&order_id).await?;
-        let symbol = &order.symbol;
-        if let Some(transaction) = self.transactions.get_mut(symbol) {
+        let key = get_key(&order.strategy, &order.symbol);
+        let exit_price = match self.transactions.get(&key) {
+            Some(transaction) => get_exit_price(self.get_transaction_orders(transaction)),
+            None => Num::default(),
+        };
+        if let Some(transaction) = self.transactions.get_mut(&key) {
             let orders = &transaction.orders;
             assert!(orders
                 .iter()
                 .any(|order_local_id| order.local_id.eq(order_local_id)));
-            info!("Closing transaction, before getting position from broker");
-            let position = self
-                .mktpositions
-                .update_position(symbol, self.assets.get_exchange(symbol))
-                .await?;
-            transaction
-                .complete(&order, Some(&position), &self.db)
-                .await
+            info!(
+                "Closing transaction for strategy: {} symbol: {}",
+                order.strategy, order.symbol
+            );
+            transaction.complete(&order, exit_price, &self.db).await?;
+            self.mktpositions
+                .remove_position(&order.strategy, &order.symbol);
         } else {
             bail!(
-                "Unable to close transaction, not found for symbol: {}",
-                symbol
+                "Unable to close transaction, not found for strategy: {} symbol: {}",
+                order.strategy,
+                order.symbol
             );
         }
         Ok(())
     }
 
-    pub async fn cancel_transaction(&mut self, order_id: Uuid) -> Result<()> {
+    pub async fn cancel_transaction(
+        &mut self,
+        order_id: Uuid,
+        reason: &str,
+    ) -> Result<TransactionStatus> {
         let order = self.update_order(order_id).await?;
         let symbol = order.symbol.clone();
-        info!("Transaction cancelled for symbol: {}", symbol);
-        if let Some(transaction) = self.transactions.get_mut(&symbol) {
-            transaction.cancel(&order, &self.db).await;
+        let key = get_key(&order.strategy, &symbol);
+        if !order.filled_quantity.is_zero() {
+            info!(
+                "Entry order for symbol: {} {}, keeping filled quantity: {}",
+                symbol, reason, order.filled_quantity
+            );
+            if let Some(transaction) = self.transactions.get_mut(&key) {
+                transaction.update_from_order(&order, &self.db).await;
+            }
+            return Ok(TransactionStatus::Confirmed);
+        }
+        info!(
+            "Transaction cancelled for strategy: {} symbol: {}, reason: {}",
+            order.strategy, symbol, reason
+        );
+        if let Some(transaction) = self.transactions.get_mut(&key) {
+            transaction.cancel(&order, reason, &self.db).await?;
             self.locker.complete(transaction.locker).await;
         } else {
             bail!(
-                "Unable to cancel transaction, not found for symbol: {}",
+                "Unable to cancel transaction, not found for strategy: {} symbol: {}",
+                order.strategy,
                 symbol
             );
         }
-        Ok(())
+        Ok(TransactionStatus::Cancelled)
     }
 
     pub async fn get_order(&self, order_id: &Uuid) -> Option<&MktOrder> {
         self.mktorders.get_order(order_id)
     }
 
+    pub async fn prepare_order(
+        &mut self,
+        strategy: &str,
+        symbol: &str,
+        action: OrderAction,
+        benchmarks: Benchmarks,
+    ) -> Result<String> {
+        let Some(transaction) = self.transactions.get(&get_key(strategy, symbol)) else {
+            bail!(
+                "Could not find transaction to prepare order with strategy: {} symbol: {}",
+                strategy,
+                symbol
+            )
+        };
+        let client_order_id = self
+            .mktorders
+            .get_client_order_id(&transaction.local_id, action);
+        self.mktorders
+            .prepare_order(&client_order_id, transaction, action, benchmarks)
+            .await?;
+        Ok(client_order_id)
+    }
+
+    pub async fn discard_order(&mut self, client_order_id: &str) {
+        if let Err(err) = self.mktorders.discard_order(client_order_id).await {
+            warn!(
+                "Failed to discard order: {}, error={}",
+                client_order_id, err
+            );
+        }
+    }
+
     pub async fn add_order(
         &mut self,
+        strategy: &str,
         symbol: &str,
         order_id: Uuid,
         side: Side,
         direction: Direction,
         action: OrderAction,
     ) -> Result<()> {
-        if let Some(transaction) = self.transactions.get_mut(symbol) {
+        if let Some(transaction) = self.transactions.get_mut(&get_key(strategy, symbol)) {
             let _ = self
                 .mktorders
-                .add_order(
-                    order_id,
-                    symbol,
-                    &transaction.strategy,
-                    side,
-                    direction,
-                    action,
-                )
+                .add_order(order_id, symbol, strategy, side, direction, action)
                 .await?;
             transaction.orders.push(order_id);
             transaction.persist_db(self.db.clone()).await?;
         } else {
             bail!(
-                "Could not find transaction for new order with symbol: {}",
+                "Could not find transaction for new order with strategy: {} symbol: {}",
+                strategy,
                 symbol
             )
         }
-        info!("New order added for symbol: {}", symbol);
+        info!(
+            "New order added for strategy: {} symbol: {}",
+            strategy, symbol
+        );
+        Ok(())
+    }
+
+    pub async fn flush(&mut self) -> Result<()> {
+        for transaction in self
+            .transactions
+            .values_mut()
+            .filter(|transaction| transaction.is_open())
+        {
+            transaction.persist_db(self.db.clone()).await?;
+        }
+        self.locker.flush().await?;
+        info!("Flushed transactions and locker state to db");
         Ok(())
     }
 
@@ -707,9 +1176,10 @@ impl Transactions {
                     }
                     anyhow::Result::Err(err) => {
                         warn!("Locker check failed, err={err}");
-                        let _ = self
-                            .cancel_transaction(*transaction.orders.first().unwrap())
-                            .await;
+                        if let Some(order_id) = get_open_entry_order(&self.mktorders, &transaction)
+                        {
+                            let _ = self.cancel_transaction(order_id, "locker").await;
+                        }
                     }
                 }
             }
@@ -717,3 +1187,77 @@ impl Transactions {
         to_close
     }
 }
+
+fn get_key(strategy: &str, symbol: &str) -> (String, String) {
+    (strategy.to_string(), symbol.to_string())
+}
+
+fn get_open_entry_order(mktorders: &MktOrders, transaction: &Transaction) -> Option<Uuid> {
+    transaction
+        .orders
+        .iter()
+        .filter_map(|order_id| mktorders.get_order(order_id))
+        .find(|order| matches!(order.action, OrderAction::Create) && order.status.is_open())
+        .map(|order| order.local_id)
+}
+
+// The latest entry which was not replaced, it may have closed while the app was down
+fn get_entry_order(orders: &[MktOrder]) -> Option<&MktOrder> {
+    orders.iter().rev().find(|order| {
+        matches!(order.action, OrderAction::Create) && order.status != OrderStatus::Replaced
+    })
+}
+
+fn get_exit_price(orders: Vec<&MktOrder>) -> Num {
+    let (notional, quantity) = orders
+        .into_iter()
+        .filter(|order| !matches!(order.action, OrderAction::Create))
+        .fold(
+            (Num::default(), Num::default()),
+            |(notional, quantity), order| {
+                (
+                    notional + order.fill_price.clone() * order.filled_quantity.clone(),
+                    quantity + order.filled_quantity.clone(),
+                )
+            },
+        );
+    if quantity.is_zero() {
+        return quantity;
+    }
+    notional / quantity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transaction_status_transitions() {
+        use TransactionStatus::*;
+        assert!(Waiting.can_transition(PartiallyFilled));
+        assert!(PartiallyFilled.can_transition(Confirmed));
+        assert!(Confirmed.can_transition(Closing));
+        assert!(Closing.can_transition(Confirmed));
+        assert!(Closing.can_transition(Complete));
+        assert!(!Waiting.can_transition(Complete));
+        assert!(!Cancelled.can_transition(Waiting));
+        assert!(!Complete.can_transition(Confirmed));
+        assert!(!Cancelled.is_open());
+        assert!(Closing.has_position());
+    }
+
+    #[test]
+    fn test_exit_price_is_volume_weighted() {
+        let build = |action, price: i64, quantity: i64| MktOrder {
+            action,
+            fill_price: Num::from(price),
+            filled_quantity: Num::from(quantity),
+            ..Default::default()
+        };
+        let entry = build(OrderAction::Create, 90, 10);
+        let limit = build(OrderAction::Liquidate, 100, 4);
+        let market = build(OrderAction::Liquidate, 95, 6);
+        assert_eq!(get_exit_price(vec![&entry, &limit, &market]), Num::from(97));
+        assert!(get_exit_price(vec![&entry]).is_zero());
+    }
+}
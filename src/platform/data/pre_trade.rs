@@ -0,0 +1,116 @@
+use anyhow::bail;
+use anyhow::Ok;
+use anyhow::Result;
+use num_decimal::Num;
+use tracing::warn;
+
+use super::account::AccountDetails;
+use crate::settings::PreTradeConfig;
+use crate::to_num;
+use crate::Settings;
+
+const PDT_EQUITY_THRESHOLD: f64 = 25000.0;
+const PDT_MAX_DAY_TRADES: u64 = 3;
+
+pub struct PreTradeChecks {
+    config: PreTradeConfig,
+}
+
+impl PreTradeChecks {
+    pub fn new(settings: &Settings) -> Self {
+        PreTradeChecks {
+            config: settings.pre_trade.clone().unwrap_or_default(),
+        }
+    }
+
+    pub async fn check_buying_power(&self, account: &AccountDetails, notional: &Num) -> Result<()> {
+        let buying_power = account.buying_power().await;
+        let buffer = to_num!(1.0 - self.config.buying_power_buffer_pc / 100.0);
+        let available = buying_power.clone() * buffer;
+        if *notional > available {
+            bail!(
+                "Order notional {} exceeds buying power {} with {}% buffer",
+                notional.round_with(2),
+                buying_power.round_with(2),
+                self.config.buying_power_buffer_pc
+            )
+        }
+        Ok(())
+    }
+
+    pub async fn check_day_trades(&self, account: &AccountDetails) -> Result<()> {
+        let daytrade_count = account.daytrade_count().await;
+        if let Some(block) = self.find_day_trade_block(&account.equity().await, daytrade_count) {
+            bail!("{}", block)
+        }
+        if self.config.overnight_only {
+            warn!(
+                "Overnight only policy, a same-day stop exit still counts as a day trade, day trade count {}",
+                daytrade_count
+            );
+        }
+        Ok(())
+    }
+
+    fn is_pdt_restricted(&self, equity: &Num) -> bool {
+        let threshold = self
+            .config
+            .pdt_equity_threshold
+            .unwrap_or(PDT_EQUITY_THRESHOLD);
+        *equity < to_num!(threshold)
+    }
+
+    fn find_day_trade_block(&self, equity: &Num, daytrade_count: u64) -> Option<String> {
+        if !self.is_pdt_restricted(equity) {
+            return None;
+        }
+        let max_day_trades = self.config.max_day_trades.unwrap_or(PDT_MAX_DAY_TRADES);
+        if daytrade_count >= max_day_trades {
+            return Some(format!(
+                "day trade count {} at limit {} for account under PDT threshold",
+                daytrade_count, max_day_trades
+            ));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_checks(max_day_trades: Option<u64>) -> PreTradeChecks {
+        let settings = Settings {
+            pre_trade: Some(PreTradeConfig {
+                buying_power_buffer_pc: 5.0,
+                pdt_equity_threshold: None,
+                max_day_trades,
+                overnight_only: false,
+            }),
+            ..Default::default()
+        };
+        PreTradeChecks::new(&settings)
+    }
+
+    #[test]
+    fn test_pdt_threshold() {
+        let checks = build_checks(None);
+        assert!(checks.is_pdt_restricted(&Num::from(24999)));
+        assert!(!checks.is_pdt_restricted(&Num::from(25000)));
+    }
+
+    #[test]
+    fn test_day_trade_count_blocks_entry() {
+        let checks = build_checks(None);
+        let equity = Num::from(10000);
+        assert!(checks.find_day_trade_block(&equity, 0).is_none());
+        assert!(checks.find_day_trade_block(&equity, 2).is_none());
+        let block = checks.find_day_trade_block(&equity, 3).unwrap();
+        assert!(block.starts_with("day trade count 3 at limit 3"));
+        assert!(checks.find_day_trade_block(&Num::from(30000), 5).is_none());
+
+        let checks = build_checks(Some(1));
+        assert!(checks.find_day_trade_block(&equity, 0).is_none());
+        assert!(checks.find_day_trade_block(&equity, 1).is_some());
+    }
+}
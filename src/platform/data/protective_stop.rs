@@ -0,0 +1,206 @@
+use chrono::DateTime;
+use chrono::Utc;
+use num_decimal::Num;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::events::Direction;
+use crate::settings::ProtectiveStopConfig;
+use crate::Settings;
+
+#[derive(Debug, Clone)]
+struct Backstop {
+    stop_price: Num,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum StopSync {
+    Place,
+    Replace(Uuid),
+    Keep,
+}
+
+pub struct ProtectiveStops {
+    config: ProtectiveStopConfig,
+    backstops: HashMap<Uuid, Backstop>,
+}
+
+impl ProtectiveStops {
+    pub fn new(settings: &Settings) -> Option<Self> {
+        settings
+            .protective_stop
+            .clone()
+            .map(|config| ProtectiveStops {
+                config,
+                backstops: HashMap::new(),
+            })
+    }
+
+    fn bps(value: f64) -> Num {
+        Num::new((value * 100.0) as i64, 1_000_000)
+    }
+
+    // The broker stop rests behind the locker stop so the locker remains the primary trigger
+    pub fn get_backstop_price(&self, stop_price: &Num, direction: Direction) -> Num {
+        let buffer = stop_price.clone() * Self::bps(self.config.buffer_bps);
+        let price = match direction {
+            Direction::Long => stop_price.clone() - buffer,
+            Direction::Short => stop_price.clone() + buffer,
+        };
+        price.round_with(2)
+    }
+
+    fn is_throttled(&self, backstop: &Backstop, now: DateTime<Utc>) -> bool {
+        (now - backstop.updated_at).num_seconds() < self.config.throttle_secs as i64
+    }
+
+    pub fn should_place(&self, transaction_id: &Uuid, now: DateTime<Utc>) -> bool {
+        self.backstops
+            .get(transaction_id)
+            .is_none_or(|backstop| !self.is_throttled(backstop, now))
+    }
+
+    pub fn should_replace(
+        &self,
+        transaction_id: &Uuid,
+        stop_price: &Num,
+        now: DateTime<Utc>,
+    ) -> bool {
+        let Some(backstop) = self.backstops.get(transaction_id) else {
+            return true;
+        };
+        if self.is_throttled(backstop, now) {
+            return false;
+        }
+        let change = if *stop_price > backstop.stop_price {
+            stop_price.clone() - backstop.stop_price.clone()
+        } else {
+            backstop.stop_price.clone() - stop_price.clone()
+        };
+        change > Num::default()
+            && change >= backstop.stop_price.clone() * Self::bps(self.config.min_change_bps)
+    }
+
+    pub fn find_sync(
+        &self,
+        transaction_id: &Uuid,
+        order_id: Option<Uuid>,
+        stop_price: &Num,
+        now: DateTime<Utc>,
+    ) -> StopSync {
+        match order_id {
+            None if self.should_place(transaction_id, now) => StopSync::Place,
+            Some(order_id) if self.should_replace(transaction_id, stop_price, now) => {
+                StopSync::Replace(order_id)
+            }
+            _ => StopSync::Keep,
+        }
+    }
+
+    pub fn record(&mut self, transaction_id: Uuid, stop_price: &Num, now: DateTime<Utc>) {
+        self.backstops.insert(
+            transaction_id,
+            Backstop {
+                stop_price: stop_price.clone(),
+                updated_at: now,
+            },
+        );
+    }
+
+    pub fn retain(&mut self, transaction_ids: &[Uuid]) {
+        self.backstops
+            .retain(|transaction_id, _| transaction_ids.contains(transaction_id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::to_num;
+
+    #[test]
+    fn test_protective_stop_throttle_and_threshold() {
+        let settings = Settings {
+            protective_stop: Some(ProtectiveStopConfig {
+                buffer_bps: 50.0,
+                min_change_bps: 20.0,
+                throttle_secs: 30,
+            }),
+            ..Default::default()
+        };
+        let mut stops = ProtectiveStops::new(&settings).unwrap();
+        assert_eq!(
+            stops.get_backstop_price(&Num::from(100), Direction::Long),
+            to_num!(99.5)
+        );
+        assert_eq!(
+            stops.get_backstop_price(&Num::from(100), Direction::Short),
+            to_num!(100.5)
+        );
+
+        let transaction_id = Uuid::new_v4();
+        let now = Utc::now();
+        assert!(stops.should_place(&transaction_id, now));
+        stops.record(transaction_id, &Num::from(100), now);
+        assert!(!stops.should_place(&transaction_id, now));
+
+        let later = now + chrono::Duration::seconds(31);
+        assert!(!stops.should_replace(&transaction_id, &Num::from(101), now));
+        assert!(!stops.should_replace(&transaction_id, &to_num!(1001, 10), later));
+        assert!(stops.should_replace(&transaction_id, &to_num!(1002, 10), later));
+        assert!(stops.should_replace(&transaction_id, &to_num!(997, 10), later));
+
+        stops.retain(&[]);
+        assert!(stops.should_place(&transaction_id, now));
+    }
+
+    #[test]
+    fn test_protective_stop_sync_decision() {
+        let settings = Settings {
+            protective_stop: Some(ProtectiveStopConfig {
+                buffer_bps: 50.0,
+                min_change_bps: 20.0,
+                throttle_secs: 30,
+            }),
+            ..Default::default()
+        };
+        let mut stops = ProtectiveStops::new(&settings).unwrap();
+        let transaction_id = Uuid::new_v4();
+        let order_id = Uuid::new_v4();
+        let now = Utc::now();
+        let later = now + chrono::Duration::seconds(31);
+        let price = Num::from(100);
+
+        assert_eq!(
+            stops.find_sync(&transaction_id, None, &price, now),
+            StopSync::Place
+        );
+        assert_eq!(
+            stops.find_sync(&transaction_id, Some(order_id), &price, now),
+            StopSync::Replace(order_id)
+        );
+
+        stops.record(transaction_id, &price, now);
+        assert_eq!(
+            stops.find_sync(&transaction_id, None, &price, now),
+            StopSync::Keep
+        );
+        assert_eq!(
+            stops.find_sync(&transaction_id, None, &price, later),
+            StopSync::Place
+        );
+        assert_eq!(
+            stops.find_sync(&transaction_id, Some(order_id), &Num::from(101), now),
+            StopSync::Keep
+        );
+        assert_eq!(
+            stops.find_sync(&transaction_id, Some(order_id), &to_num!(1001, 10), later),
+            StopSync::Keep
+        );
+        assert_eq!(
+            stops.find_sync(&transaction_id, Some(order_id), &Num::from(101), later),
+            StopSync::Replace(order_id)
+        );
+    }
+}
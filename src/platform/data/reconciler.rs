@@ -0,0 +1,404 @@
+use anyhow::bail;
+use anyhow::Ok;
+use anyhow::Result;
+use apca::api::v2::order;
+use apca::api::v2::position;
+use chrono::Duration;
+use chrono::Utc;
+use num_decimal::Num;
+use std::cmp::min;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use tracing::info;
+use uuid::Uuid;
+
+use super::mktorder::OrderAction;
+use super::Transaction;
+use super::TransactionStatus;
+use super::Transactions;
+use crate::events::Direction;
+use crate::settings::ReconcilerConfig;
+
+pub const DEFAULT_INTERVAL_SECS: u64 = 60;
+const DEFAULT_GRACE_SECS: i64 = 30;
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum ReconcileAction {
+    #[default]
+    Alert,
+    Repair,
+    Flatten,
+}
+
+impl FromStr for ReconcileAction {
+    type Err = String;
+
+    fn from_str(val: &str) -> Result<Self, Self::Err> {
+        match val.to_lowercase().as_str() {
+            "alert" => std::result::Result::Ok(ReconcileAction::Alert),
+            "repair" => std::result::Result::Ok(ReconcileAction::Repair),
+            "flatten" => std::result::Result::Ok(ReconcileAction::Flatten),
+            _ => Err(format!(
+                "Failed to parse reconcile action, unknown: {}",
+                val
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Discrepancy {
+    OrphanPosition {
+        symbol: String,
+        quantity: Num,
+    },
+    MissingFill {
+        symbol: String,
+        order_id: Uuid,
+        action: OrderAction,
+    },
+    QuantityMismatch {
+        symbol: String,
+        local: Num,
+        broker: Num,
+    },
+    OrphanLocker {
+        symbol: String,
+        locker_id: Uuid,
+    },
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Discrepancy::OrphanPosition { symbol, quantity } => write!(
+                f,
+                "Orphan broker position symbol[{}], quantity[{}]",
+                symbol, quantity
+            ),
+            Discrepancy::MissingFill {
+                symbol,
+                order_id,
+                action,
+            } => write!(
+                f,
+                "Missing fill symbol[{}], order[{}], action[{}]",
+                symbol, order_id, action
+            ),
+            Discrepancy::QuantityMismatch {
+                symbol,
+                local,
+                broker,
+            } => write!(
+                f,
+                "Quantity mismatch symbol[{}], local[{}], broker[{}]",
+                symbol, local, broker
+            ),
+            Discrepancy::OrphanLocker { symbol, locker_id } => write!(
+                f,
+                "Locker without transaction symbol[{}], locker[{}]",
+                symbol, locker_id
+            ),
+        }
+    }
+}
+
+fn signed(quantity: Num, direction: Direction) -> Num {
+    match direction {
+        Direction::Long => quantity,
+        Direction::Short => -quantity,
+    }
+}
+
+fn find_quantity_mismatches(
+    local_positions: &HashMap<String, Num>,
+    broker_positions: &HashMap<String, Num>,
+    pending: &HashSet<String>,
+) -> Vec<Discrepancy> {
+    let mut discrepancies = Vec::new();
+    for (symbol, local) in local_positions {
+        if pending.contains(symbol) {
+            continue;
+        }
+        let broker = broker_positions.get(symbol).cloned().unwrap_or_default();
+        if broker != *local {
+            discrepancies.push(Discrepancy::QuantityMismatch {
+                symbol: symbol.clone(),
+                local: local.clone(),
+                broker,
+            });
+        }
+    }
+    discrepancies
+}
+
+fn share_quantity(quantities: &[Num], broker: &Num) -> Vec<Num> {
+    let mut remaining = if broker.is_negative() {
+        -broker.clone()
+    } else {
+        broker.clone()
+    };
+    quantities
+        .iter()
+        .map(|quantity| {
+            let quantity = min(quantity.clone(), remaining.clone());
+            remaining -= quantity.clone();
+            quantity
+        })
+        .collect()
+}
+
+impl Transactions {
+    pub async fn reconcile(&mut self, config: &ReconcilerConfig) -> Result<Vec<Discrepancy>> {
+        let grace = Duration::seconds(config.grace_secs.unwrap_or(DEFAULT_GRACE_SECS));
+        let broker_positions: HashMap<String, Num> = self
+            .mktpositions
+            .get_broker_positions()
+            .await?
+            .into_iter()
+            .map(|position| {
+                let direction = match position.side {
+                    position::Side::Long => Direction::Long,
+                    position::Side::Short => Direction::Short,
+                };
+                let quantity = if position.quantity.is_negative() {
+                    -position.quantity
+                } else {
+                    position.quantity
+                };
+                (position.symbol, signed(quantity, direction))
+            })
+            .collect();
+        let broker_orders: HashMap<Uuid, order::Order> = self
+            .mktorders
+            .get_broker_orders()
+            .await?
+            .into_iter()
+            .map(|order| (order.id.0, order))
+            .collect();
+
+        let mut discrepancies = Vec::new();
+        let mut lockers = HashSet::new();
+        let mut pending = HashSet::new();
+        let mut local_positions: HashMap<String, Num> = HashMap::new();
+        for transaction in self
+            .transactions
+            .values()
+            .filter(|transaction| transaction.is_open())
+        {
+            lockers.insert(transaction.locker);
+            for order_id in &transaction.orders {
+                let Some(mktorder) = self.mktorders.get_order(order_id) else {
+                    continue;
+                };
+                if !mktorder.status.is_open() {
+                    continue;
+                }
+                // Resting exit legs do not make the position quantity uncertain
+                if !matches!(
+                    mktorder.action,
+                    OrderAction::Protect | OrderAction::TakeProfit
+                ) {
+                    pending.insert(transaction.symbol.clone());
+                }
+                let Some(order) = broker_orders.get(order_id) else {
+                    continue;
+                };
+                let is_stale = order
+                    .filled_at
+                    .is_some_and(|filled_at| filled_at + grace < Utc::now());
+                if order.status == order::Status::Filled && is_stale {
+                    discrepancies.push(Discrepancy::MissingFill {
+                        symbol: transaction.symbol.clone(),
+                        order_id: *order_id,
+                        action: mktorder.action,
+                    });
+                }
+            }
+            if transaction.status.has_position() {
+                let local = local_positions
+                    .entry(transaction.symbol.clone())
+                    .or_default();
+                *local =
+                    local.clone() + signed(transaction.quantity.clone(), transaction.direction);
+            }
+        }
+
+        discrepancies.extend(find_quantity_mismatches(
+            &local_positions,
+            &broker_positions,
+            &pending,
+        ));
+
+        for (symbol, quantity) in &broker_positions {
+            if !self.has_open_transaction(symbol) {
+                discrepancies.push(Discrepancy::OrphanPosition {
+                    symbol: symbol.clone(),
+                    quantity: quantity.clone(),
+                });
+            }
+        }
+
+        for (locker_id, symbol) in self.locker.get_tracked() {
+            if !lockers.contains(&locker_id) {
+                discrepancies.push(Discrepancy::OrphanLocker { symbol, locker_id });
+            }
+        }
+        Ok(discrepancies)
+    }
+
+    pub async fn repair_quantity(&mut self, symbol: &str, broker: &Num) -> Result<()> {
+        let mut transactions: Vec<&mut Transaction> = self
+            .transactions
+            .values_mut()
+            .filter(|transaction| transaction.symbol == symbol && transaction.status.has_position())
+            .collect();
+        if broker.is_zero() {
+            info!(
+                "Position for symbol: {} closed outside the app, completing {} transactions",
+                symbol,
+                transactions.len()
+            );
+            for transaction in transactions {
+                transaction
+                    .transition(TransactionStatus::Complete, "reconcile", &self.db)
+                    .await?;
+                transaction.exit_time = Utc::now();
+                transaction.exit_reason = Some("reconcile".to_string());
+                self.locker.complete(transaction.locker).await;
+                self.mktpositions
+                    .remove_position(&transaction.strategy, symbol);
+                transaction.persist_db(self.db.clone()).await?;
+            }
+            return Ok(());
+        }
+        let [transaction] = transactions.as_mut_slice() else {
+            bail!(
+                "Unable to attribute broker quantity {} for symbol: {} across {} strategies",
+                broker,
+                symbol,
+                transactions.len()
+            )
+        };
+        let quantity = if broker.is_negative() {
+            -broker.clone()
+        } else {
+            broker.clone()
+        };
+        info!(
+            "Strategy[{}] updating quantity for symbol: {} from {} to broker quantity {}",
+            transaction.strategy, symbol, transaction.quantity, quantity
+        );
+        transaction.quantity = quantity;
+        self.mktpositions.add_position(
+            &transaction.strategy,
+            symbol,
+            transaction.direction,
+            &transaction.quantity,
+            &transaction.entry_price,
+        );
+        transaction.persist_db(self.db.clone()).await
+    }
+
+    pub async fn cap_quantity(&mut self, symbol: &str, broker: &Num) -> Result<()> {
+        let mut transactions: Vec<&mut Transaction> = self
+            .transactions
+            .values_mut()
+            .filter(|transaction| transaction.symbol == symbol && transaction.status.has_position())
+            .collect();
+        transactions.sort_by_key(|transaction| transaction.entry_time);
+        let quantities: Vec<Num> = transactions
+            .iter()
+            .map(|transaction| transaction.quantity.clone())
+            .collect();
+        let capped = share_quantity(&quantities, broker);
+        for (transaction, quantity) in transactions.into_iter().zip(capped) {
+            if quantity == transaction.quantity {
+                continue;
+            }
+            info!(
+                "Strategy[{}] capping quantity for symbol: {} from {} to {} of broker quantity {}",
+                transaction.strategy, symbol, transaction.quantity, quantity, broker
+            );
+            if quantity.is_zero() {
+                transaction
+                    .transition(TransactionStatus::Complete, "reconcile", &self.db)
+                    .await?;
+                transaction.exit_time = Utc::now();
+                transaction.exit_reason = Some("reconcile".to_string());
+                self.locker.complete(transaction.locker).await;
+                self.mktpositions
+                    .remove_position(&transaction.strategy, symbol);
+            } else {
+                transaction.quantity = quantity;
+                self.mktpositions.add_position(
+                    &transaction.strategy,
+                    symbol,
+                    transaction.direction,
+                    &transaction.quantity,
+                    &transaction.entry_price,
+                );
+            }
+            transaction.persist_db(self.db.clone()).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn repair_locker(&mut self, locker_id: Uuid) {
+        self.locker.complete(locker_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantity_mismatch_skips_pending_symbols() {
+        let local_positions = HashMap::from([
+            ("AAPL".to_string(), Num::from(10)),
+            ("MSFT".to_string(), Num::from(-5)),
+            ("TSLA".to_string(), Num::from(3)),
+        ]);
+        let broker_positions = HashMap::from([
+            ("AAPL".to_string(), Num::from(10)),
+            ("MSFT".to_string(), Num::from(-4)),
+        ]);
+        let pending = HashSet::from(["TSLA".to_string()]);
+        let discrepancies = find_quantity_mismatches(&local_positions, &broker_positions, &pending);
+        let [Discrepancy::QuantityMismatch {
+            symbol,
+            local,
+            broker,
+        }] = discrepancies.as_slice()
+        else {
+            panic!(
+                "Expected a single quantity mismatch, got {:?}",
+                discrepancies
+            )
+        };
+        assert_eq!(symbol, "MSFT");
+        assert_eq!(*local, Num::from(-5));
+        assert_eq!(*broker, Num::from(-4));
+
+        let discrepancies =
+            find_quantity_mismatches(&local_positions, &broker_positions, &HashSet::new());
+        assert_eq!(discrepancies.len(), 2);
+    }
+
+    #[test]
+    fn test_share_broker_quantity_oldest_first() {
+        let quantities = vec![Num::from(4), Num::from(6), Num::from(5)];
+        assert_eq!(
+            share_quantity(&quantities, &Num::from(8)),
+            vec![Num::from(4), Num::from(4), Num::from(0)]
+        );
+        assert_eq!(
+            share_quantity(&quantities, &Num::from(-8)),
+            vec![Num::from(4), Num::from(4), Num::from(0)]
+        );
+        assert_eq!(share_quantity(&quantities, &Num::from(20)), quantities);
+    }
+}
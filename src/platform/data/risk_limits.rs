@@ -0,0 +1,391 @@
+use anyhow::bail;
+use anyhow::Ok;
+use anyhow::Result;
+use chrono::DateTime;
+use chrono::NaiveDate;
+use chrono::Utc;
+use num_decimal::Num;
+use sqlx::postgres::PgArguments;
+use sqlx::postgres::PgRow;
+use sqlx::query::Query;
+use sqlx::FromRow;
+use sqlx::Postgres;
+use sqlx::Row;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::info;
+use tracing::warn;
+use uuid::Uuid;
+
+use super::db_client::DBClient;
+use crate::platform::market_session::MarketSession;
+use crate::settings::RiskLimitsConfig;
+use crate::to_num;
+use crate::Settings;
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum BreachAction {
+    #[default]
+    Block,
+    Tighten,
+    Flatten,
+}
+
+impl FromStr for BreachAction {
+    type Err = String;
+
+    fn from_str(val: &str) -> Result<Self, Self::Err> {
+        match val.to_lowercase().as_str() {
+            "block" => std::result::Result::Ok(BreachAction::Block),
+            "tighten" => std::result::Result::Ok(BreachAction::Tighten),
+            "flatten" => std::result::Result::Ok(BreachAction::Flatten),
+            _ => Err(format!("Failed to parse breach action, unknown: {}", val)),
+        }
+    }
+}
+
+impl fmt::Display for BreachAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EquityBaseline {
+    pub local_id: Uuid,
+    pub trading_day: NaiveDate,
+    pub start_of_day_equity: Num,
+    pub high_water_mark: Num,
+    pub breach: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl fmt::Display for EquityBaseline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Baseline day[{}], start of day equity[{}], high water mark[{}]",
+            self.trading_day,
+            self.start_of_day_equity.round_with(2),
+            self.high_water_mark.round_with(2)
+        )
+    }
+}
+
+impl FromRow<'_, PgRow> for EquityBaseline {
+    fn from_row(row: &PgRow) -> sqlx::Result<Self> {
+        fn sqlx_to_num(row: &PgRow, value: &str) -> sqlx::Result<Num> {
+            match row.try_get::<f64, _>(value) {
+                std::result::Result::Ok(val) => std::result::Result::Ok(to_num!(val)),
+                Err(err) => Err(err),
+            }
+        }
+
+        sqlx::Result::Ok(Self {
+            local_id: row.try_get("local_id")?,
+            trading_day: row.try_get("trading_day")?,
+            start_of_day_equity: sqlx_to_num(row, "start_of_day_equity")?,
+            high_water_mark: sqlx_to_num(row, "high_water_mark")?,
+            breach: row.try_get("breach")?,
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
+}
+
+impl EquityBaseline {
+    fn get_breach_on(&self, today: NaiveDate) -> Option<String> {
+        match self.trading_day == today {
+            true => self.breach.clone(),
+            false => None,
+        }
+    }
+
+    fn update(&mut self, today: NaiveDate, equity: &Num, last_equity: &Num) -> bool {
+        let mut changed = false;
+        if Uuid::is_nil(&self.local_id) || self.trading_day != today {
+            self.trading_day = today;
+            self.start_of_day_equity = if last_equity.is_zero() {
+                equity.clone()
+            } else {
+                last_equity.clone()
+            };
+            if self.start_of_day_equity > self.high_water_mark {
+                self.high_water_mark = self.start_of_day_equity.clone();
+            }
+            self.breach = None;
+            changed = true;
+            info!("New trading day, {}", self);
+        }
+        if *equity > self.high_water_mark {
+            self.high_water_mark = equity.clone();
+            changed = true;
+        }
+        changed
+    }
+
+    fn find_breach(&self, config: &RiskLimitsConfig, equity: &Num) -> Option<String> {
+        let start_of_day = &self.start_of_day_equity;
+        let daily_loss = start_of_day.clone() - equity.clone();
+        if let Some(max_loss) = config.max_daily_loss {
+            if daily_loss >= to_num!(max_loss) {
+                return Some(format!(
+                    "daily loss {} exceeds limit {}",
+                    daily_loss.round_with(2),
+                    max_loss
+                ));
+            }
+        }
+        if let Some(max_loss_pc) = config.max_daily_loss_pc {
+            if !start_of_day.is_zero() {
+                let loss_pc = daily_loss / start_of_day.clone() * to_num!(100.0);
+                if loss_pc >= to_num!(max_loss_pc) {
+                    return Some(format!(
+                        "daily loss {}% exceeds limit {}%",
+                        loss_pc.round_with(2),
+                        max_loss_pc
+                    ));
+                }
+            }
+        }
+        if let Some(max_drawdown_pc) = config.max_drawdown_pc {
+            let high_water_mark = &self.high_water_mark;
+            if !high_water_mark.is_zero() {
+                let drawdown_pc = (high_water_mark.clone() - equity.clone())
+                    / high_water_mark.clone()
+                    * to_num!(100.0);
+                if drawdown_pc >= to_num!(max_drawdown_pc) {
+                    return Some(format!(
+                        "drawdown {}% exceeds limit {}%",
+                        drawdown_pc.round_with(2),
+                        max_drawdown_pc
+                    ));
+                }
+            }
+        }
+        None
+    }
+
+    fn build_query<'a>(&'a self, stmt: &'a str) -> Query<'a, Postgres, PgArguments> {
+        sqlx::query(stmt)
+            .bind(self.trading_day)
+            .bind(self.start_of_day_equity.round_with(3).to_f64())
+            .bind(self.high_water_mark.round_with(3).to_f64())
+            .bind(self.breach.clone())
+            .bind(self.updated_at)
+            .bind(self.local_id)
+    }
+
+    async fn persist_db(&mut self, db: &Arc<DBClient>) -> Result<()> {
+        let columns = vec![
+            "trading_day",
+            "start_of_day_equity",
+            "high_water_mark",
+            "breach",
+            "updated_at",
+            "local_id",
+        ];
+
+        let stmt = db.get_sql_stmt("equity_baseline", &self.local_id, columns, db);
+        if Uuid::is_nil(&self.local_id) {
+            self.local_id = Uuid::new_v4();
+        }
+
+        if let Err(err) = self.build_query(&stmt).execute(&db.pool).await {
+            bail!("Equity baseline failed to publish to db, error={}", err)
+        }
+        Ok(())
+    }
+}
+
+pub struct RiskLimits {
+    db: Arc<DBClient>,
+    config: Option<RiskLimitsConfig>,
+    baseline: EquityBaseline,
+    breach: Option<String>,
+}
+
+impl RiskLimits {
+    pub fn new(settings: &Settings, db: &Arc<DBClient>) -> Self {
+        RiskLimits {
+            db: Arc::clone(db),
+            config: settings.risk_limits.clone(),
+            baseline: EquityBaseline::default(),
+            breach: None,
+        }
+    }
+
+    pub async fn startup(&mut self) -> Result<()> {
+        let stmt = self
+            .db
+            .query_builder
+            .prepare_fetch_statement("equity_baseline", &Vec::default());
+        let rows = match sqlx::query_as::<_, EquityBaseline>(&stmt)
+            .fetch_all(&self.db.pool)
+            .await
+        {
+            sqlx::Result::Ok(val) => val,
+            Err(err) => bail!("Failed to fetch equity baseline from db, error={}", err),
+        };
+        if let Some(baseline) = rows.into_iter().max_by_key(|baseline| baseline.updated_at) {
+            info!("Restored equity baseline from db, {}", baseline);
+            self.baseline = baseline;
+        }
+        // A breach holds for the rest of its trading day, a restart does not re-enable entries
+        self.breach = self
+            .baseline
+            .get_breach_on(MarketSession::eastern_date(&Utc::now()));
+        if let Some(breach) = &self.breach {
+            warn!("Restored risk limit breach from db: {}", breach);
+        }
+        Ok(())
+    }
+
+    pub fn get_breach(&self) -> Option<&String> {
+        self.breach.as_ref()
+    }
+
+    pub fn breach_action(&self) -> BreachAction {
+        match &self.config {
+            Some(config) => BreachAction::from_str(&config.breach_action).unwrap_or_else(|err| {
+                warn!("{}, blocking entries only", err);
+                BreachAction::Block
+            }),
+            None => BreachAction::Block,
+        }
+    }
+
+    pub fn tighten_percent(&self) -> f64 {
+        self.config
+            .as_ref()
+            .and_then(|config| config.tighten_pc)
+            .unwrap_or(1.0)
+    }
+
+    async fn update_baseline(&mut self, equity: &Num, last_equity: &Num) -> Result<()> {
+        let today = MarketSession::eastern_date(&Utc::now());
+        if self.baseline.trading_day != today || Uuid::is_nil(&self.baseline.local_id) {
+            self.breach = None;
+        }
+        if self.baseline.update(today, equity, last_equity) {
+            self.baseline.updated_at = Utc::now();
+            self.baseline.persist_db(&self.db).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn update_equity(
+        &mut self,
+        equity: &Num,
+        last_equity: &Num,
+    ) -> Result<Option<String>> {
+        let Some(config) = self.config.clone() else {
+            return Ok(None);
+        };
+        self.update_baseline(equity, last_equity).await?;
+        if self.breach.is_some() {
+            return Ok(None);
+        }
+        self.breach = self.baseline.find_breach(&config, equity);
+        if let Some(breach) = &self.breach {
+            warn!("Risk limit breached: {}, {}", breach, self.baseline);
+            self.baseline.breach = Some(breach.clone());
+            self.baseline.updated_at = Utc::now();
+            self.baseline.persist_db(&self.db).await?;
+        }
+        Ok(self.breach.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_config() -> RiskLimitsConfig {
+        RiskLimitsConfig {
+            max_daily_loss: Some(500.0),
+            max_daily_loss_pc: Some(10.0),
+            max_drawdown_pc: Some(20.0),
+            breach_action: "block".to_string(),
+            tighten_pc: None,
+        }
+    }
+
+    fn build_baseline(start_of_day: i64, high_water_mark: i64) -> EquityBaseline {
+        EquityBaseline {
+            local_id: Uuid::new_v4(),
+            trading_day: NaiveDate::from_ymd_opt(2024, 7, 5).unwrap(),
+            start_of_day_equity: Num::from(start_of_day),
+            high_water_mark: Num::from(high_water_mark),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_breach_thresholds_are_inclusive() {
+        let config = build_config();
+        let baseline = build_baseline(10000, 10000);
+        assert!(baseline.find_breach(&config, &Num::from(9501)).is_none());
+        let breach = baseline.find_breach(&config, &Num::from(9500)).unwrap();
+        assert!(breach.starts_with("daily loss 500"));
+
+        let config = RiskLimitsConfig {
+            max_daily_loss: None,
+            ..build_config()
+        };
+        assert!(baseline.find_breach(&config, &Num::from(9001)).is_none());
+        let breach = baseline.find_breach(&config, &Num::from(9000)).unwrap();
+        assert!(breach.starts_with("daily loss 10%"));
+
+        let config = RiskLimitsConfig {
+            max_daily_loss: None,
+            max_daily_loss_pc: None,
+            ..build_config()
+        };
+        let baseline = build_baseline(9000, 12000);
+        assert!(baseline.find_breach(&config, &Num::from(9601)).is_none());
+        let breach = baseline.find_breach(&config, &Num::from(9600)).unwrap();
+        assert!(breach.starts_with("drawdown 20%"));
+    }
+
+    #[test]
+    fn test_baseline_rolls_to_new_day() {
+        let mut baseline = build_baseline(10000, 10500);
+        baseline.breach = Some("daily loss 500 exceeds limit 500".to_string());
+        let today = baseline.trading_day;
+        assert_eq!(baseline.get_breach_on(today), baseline.breach);
+
+        assert!(!baseline.update(today, &Num::from(9800), &Num::from(9900)));
+        assert_eq!(baseline.start_of_day_equity, Num::from(10000));
+        assert!(baseline.breach.is_some());
+
+        let tomorrow = today.succ_opt().unwrap();
+        assert!(baseline.get_breach_on(tomorrow).is_none());
+        assert!(baseline.update(tomorrow, &Num::from(9800), &Num::from(9900)));
+        assert_eq!(baseline.trading_day, tomorrow);
+        assert_eq!(baseline.start_of_day_equity, Num::from(9900));
+        assert_eq!(baseline.high_water_mark, Num::from(10500));
+        assert!(baseline.breach.is_none());
+
+        assert!(baseline.update(tomorrow, &Num::from(10600), &Num::from(9900)));
+        assert_eq!(baseline.high_water_mark, Num::from(10600));
+    }
+
+    #[test]
+    fn test_missing_baseline_starts_from_current_equity() {
+        let today = NaiveDate::from_ymd_opt(2024, 7, 5).unwrap();
+        let mut baseline = EquityBaseline::default();
+        assert!(baseline.get_breach_on(today).is_none());
+        assert!(baseline
+            .find_breach(&build_config(), &Num::from(9000))
+            .is_none());
+
+        assert!(baseline.update(today, &Num::from(9000), &Num::from(0)));
+        assert_eq!(baseline.trading_day, today);
+        assert_eq!(baseline.start_of_day_equity, Num::from(9000));
+        assert_eq!(baseline.high_water_mark, Num::from(9000));
+        assert!(baseline
+            .find_breach(&build_config(), &Num::from(9000))
+            .is_none());
+    }
+}
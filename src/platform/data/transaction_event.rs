@@ -0,0 +1,69 @@
+use anyhow::bail;
+use anyhow::Ok;
+use anyhow::Result;
+use chrono::DateTime;
+use chrono::Utc;
+use sqlx::postgres::PgArguments;
+use sqlx::query::Query;
+use sqlx::Postgres;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use super::db_client::DBClient;
+use super::TransactionStatus;
+
+#[derive(Debug, Clone)]
+pub struct TransactionEvent {
+    pub local_id: Uuid,
+    pub transaction: Uuid,
+    pub from_status: Option<TransactionStatus>,
+    pub to_status: TransactionStatus,
+    pub cause: String,
+    pub event_time: DateTime<Utc>,
+}
+
+impl TransactionEvent {
+    pub fn new(
+        transaction: Uuid,
+        from_status: Option<TransactionStatus>,
+        to_status: TransactionStatus,
+        cause: &str,
+    ) -> Self {
+        TransactionEvent {
+            local_id: Uuid::new_v4(),
+            transaction,
+            from_status,
+            to_status,
+            cause: cause.to_string(),
+            event_time: Utc::now(),
+        }
+    }
+
+    fn build_query<'a>(&'a self, stmt: &'a str) -> Query<'a, Postgres, PgArguments> {
+        sqlx::query(stmt)
+            .bind(self.transaction)
+            .bind(self.from_status.map(|status| status.to_string()))
+            .bind(self.to_status.to_string())
+            .bind(self.cause.clone())
+            .bind(self.event_time)
+            .bind(self.local_id)
+    }
+
+    pub async fn persist_db(&self, db: &Arc<DBClient>) -> Result<()> {
+        let columns = vec![
+            "transaction",
+            "from_status",
+            "to_status",
+            "cause",
+            "event_time",
+            "local_id",
+        ];
+        let stmt = db
+            .query_builder
+            .prepare_insert_statement("transaction_event", &columns);
+        if let Err(err) = self.build_query(&stmt).execute(&db.pool).await {
+            bail!("Transaction event failed to publish to db, error={}", err)
+        }
+        Ok(())
+    }
+}
@@ -1,9 +1,16 @@
 use anyhow::bail;
 use anyhow::Ok;
 use anyhow::Result;
+use apca::api::v2::order;
 use apca::api::v2::updates;
 use apca::data::v2::stream;
+use chrono::DateTime;
+use chrono::NaiveTime;
+use chrono::Utc;
 use num_decimal::Num;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::str::FromStr;
 use std::sync::Arc;
 use tokio::sync::broadcast::Receiver;
 use tokio::sync::Mutex;
@@ -16,15 +23,37 @@ use tracing::info;
 use tracing::warn;
 use uuid::Uuid;
 
+use super::super::events::AdminCommand;
 use super::super::events::MktSignal;
 use super::data::account::AccountDetails;
+use super::data::adoption::ADOPTED_UNKNOWN_ENTRY;
+use super::data::db_client::DBClient;
+use super::data::exposure::ExposureDecision;
+use super::data::exposure::ExposureLimits;
+use super::data::kill_switch::KillSwitch;
+use super::data::mktorder::Benchmarks;
 use super::data::mktorder::OrderAction;
+use super::data::mktorder::OrderStatus;
+use super::data::pre_trade::PreTradeChecks;
+use super::data::protective_stop::ProtectiveStops;
+use super::data::protective_stop::StopSync;
+use super::data::reconciler;
+use super::data::reconciler::Discrepancy;
+use super::data::reconciler::ReconcileAction;
+use super::data::risk_limits::BreachAction;
+use super::data::risk_limits::RiskLimits;
+use super::data::Transaction;
 use super::data::TransactionStatus;
 use super::data::Transactions;
+use super::market_session::ClosedPolicy;
+use super::market_session::MarketSession;
 use super::mktdata::MktData;
+use super::order_handler::ExitLegs;
 use super::order_handler::OrderHandler;
 use super::technical_signals::TechnnicalSignals;
+use super::web_clients::BrokerError;
 use super::web_clients::Connectors;
+use super::web_clients::ErrorClass;
 use super::Event;
 use super::Settings;
 use crate::events::Direction;
@@ -32,12 +61,50 @@ use crate::events::Side;
 use crate::settings::PositionSizing;
 use crate::to_num;
 
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum ShutdownPolicy {
+    #[default]
+    Leave,
+    CancelEntries,
+    Flatten,
+}
+
+impl FromStr for ShutdownPolicy {
+    type Err = String;
+
+    fn from_str(val: &str) -> Result<Self, Self::Err> {
+        match val.to_lowercase().as_str() {
+            "leave" => std::result::Result::Ok(ShutdownPolicy::Leave),
+            "cancel_entries" => std::result::Result::Ok(ShutdownPolicy::CancelEntries),
+            "flatten" => std::result::Result::Ok(ShutdownPolicy::Flatten),
+            _ => Err(format!("Failed to parse shutdown policy, unknown: {}", val)),
+        }
+    }
+}
+
+struct PendingLiquidation {
+    strategy: String,
+    symbol: String,
+    reason: String,
+}
+
 pub struct Engine {
     settings: Settings,
     account: AccountDetails,
     mktdata: Arc<Mutex<MktData>>,
     order_handler: OrderHandler,
     transactions: Transactions,
+    kill_switch: KillSwitch,
+    risk_limits: RiskLimits,
+    exposure: ExposureLimits,
+    pre_trade: PreTradeChecks,
+    market_session: MarketSession,
+    protective_stops: Option<ProtectiveStops>,
+    queued_signals: Vec<MktSignal>,
+    expired_entries: HashSet<Uuid>,
+    shutdown_policy: ShutdownPolicy,
+    awaiting_exit_cancels: HashMap<Uuid, PendingLiquidation>,
+    resumed_liquidations: Vec<Uuid>,
     connectors: Arc<Connectors>,
 }
 
@@ -49,17 +116,53 @@ impl Engine {
         is_live: bool,
         shutdown_signal: CancellationToken,
     ) -> Result<Arc<Mutex<Self>>> {
-        let connectors = Connectors::new(key, secret, is_live, shutdown_signal)?;
+        let shutdown_policy = match &settings.shutdown {
+            Some(config) => match ShutdownPolicy::from_str(&config.policy) {
+                std::result::Result::Ok(policy) => policy,
+                Err(err) => bail!("{}", err),
+            },
+            None => ShutdownPolicy::Leave,
+        };
+        let shadow = match settings.account_type.as_str() {
+            "shadow" => Some(settings.shadow.clone().unwrap_or_default()),
+            _ => None,
+        };
+        let connectors = Connectors::new(
+            key,
+            secret,
+            is_live,
+            shadow,
+            settings.rate_limit.clone(),
+            shutdown_signal,
+        )?;
         let account = AccountDetails::new(&connectors).await?;
-        let order_handler = OrderHandler::new(&connectors);
+        let order_handler = OrderHandler::new(&connectors, &settings)?;
         let mktdata = MktData::new(&connectors);
-        let transactions = Transactions::new(&settings, &connectors, &mktdata).await?;
+        let db = DBClient::new(&settings).await?;
+        let transactions = Transactions::new(&settings, &db, &connectors, &mktdata).await?;
+        let kill_switch = KillSwitch::new(&settings, &db);
+        let risk_limits = RiskLimits::new(&settings, &db);
+        let exposure = ExposureLimits::new(&settings)?;
+        let pre_trade = PreTradeChecks::new(&settings);
+        let market_session = MarketSession::new(&settings, &connectors);
+        let protective_stops = ProtectiveStops::new(&settings);
         Ok(Arc::new(Mutex::new(Engine {
             settings,
             account,
             mktdata,
             order_handler,
             transactions,
+            kill_switch,
+            risk_limits,
+            exposure,
+            pre_trade,
+            market_session,
+            protective_stops,
+            queued_signals: Vec::default(),
+            expired_entries: HashSet::default(),
+            shutdown_policy,
+            awaiting_exit_cancels: HashMap::default(),
+            resumed_liquidations: Vec::default(),
             connectors,
         })))
     }
@@ -67,12 +170,371 @@ impl Engine {
     pub async fn startup(&mut self) -> Result<()> {
         info!("Downloading orders and positions in engine startup");
         self.transactions.startup().await?;
+        self.kill_switch.startup().await?;
+        self.risk_limits.startup().await?;
+        self.market_session.startup().await?;
         self.connectors.startup().await?;
         Ok(())
     }
 
+    pub async fn admin_command(&mut self, command: &AdminCommand) -> Result<()> {
+        match command {
+            AdminCommand::Halt {
+                reason,
+                cancel_orders,
+                liquidate,
+            } => {
+                let cancel_orders =
+                    cancel_orders.unwrap_or(self.kill_switch.default_cancel_orders());
+                let liquidate = liquidate.unwrap_or(self.kill_switch.default_liquidate());
+                self.halt(reason, cancel_orders, liquidate).await
+            }
+            AdminCommand::Resume => self.kill_switch.resume().await,
+            AdminCommand::Adopt { symbol } => self.adopt_positions(symbol.as_deref()).await,
+        }
+    }
+
+    async fn adopt_positions(&mut self, symbol: Option<&str>) -> Result<()> {
+        let Some(config) = self.settings.adoption.clone() else {
+            bail!("Adoption requested but no adoption settings configured")
+        };
+        if !self.settings.strategies.contains_key(&config.strategy) {
+            bail!(
+                "Adoption strategy: {} not found in strategies",
+                config.strategy
+            )
+        }
+        let positions = self.transactions.find_untracked_positions().await?;
+        for position in positions
+            .iter()
+            .filter(|position| symbol.is_none_or(|symbol| position.symbol == symbol))
+        {
+            if let Err(err) = self.transactions.adopt_position(position, &config).await {
+                error!(
+                    "Failed to adopt position for symbol: {}, error={}",
+                    position.symbol, err
+                );
+                continue;
+            }
+            self.mktdata
+                .lock()
+                .await
+                .subscribe(&position.symbol)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn halt(&mut self, reason: &str, cancel_orders: bool, liquidate: bool) -> Result<()> {
+        self.kill_switch
+            .halt(reason, cancel_orders, liquidate)
+            .await?;
+        if cancel_orders {
+            self.cancel_working_orders().await?;
+        }
+        if liquidate {
+            self.liquidate_positions("halt").await;
+        }
+        Ok(())
+    }
+
+    async fn cancel_working_orders(&mut self) -> Result<()> {
+        let orders = self.connectors.get_orders().await?;
+        for order in orders.iter().filter(|order| {
+            matches!(
+                order.status,
+                order::Status::New
+                    | order::Status::Accepted
+                    | order::Status::PendingNew
+                    | order::Status::PartiallyFilled
+                    | order::Status::DoneForDay
+                    | order::Status::Held
+            )
+        }) {
+            info!(
+                "Kill switch cancelling working order: {} for symbol: {}",
+                order.id.0, order.symbol
+            );
+            if let Err(err) = self.connectors.cancel_order(&order.id).await {
+                error!(
+                    "Kill switch failed to cancel order: {}, error={}",
+                    order.id.0, err
+                );
+            }
+        }
+        Ok(())
+    }
+
+    async fn liquidate_positions(&mut self, reason: &str) -> Vec<Uuid> {
+        let transactions = self.transactions.get_held_transactions();
+        let mut order_ids = Vec::new();
+        for transaction in &transactions {
+            info!(
+                "Strategy[{}] liquidating position for symbol: {}, reason: {}",
+                transaction.strategy, transaction.symbol, reason
+            );
+            if let Some(order_id) = self.handle_liquidate(transaction, reason).await {
+                self.handle_closing_position(transaction, order_id, reason)
+                    .await;
+                order_ids.push(order_id);
+            }
+        }
+        order_ids
+    }
+
+    async fn cancel_entry_orders(&mut self) -> Vec<Uuid> {
+        let mut transactions = self
+            .transactions
+            .get_transactions_with_status(TransactionStatus::Waiting);
+        transactions.extend(
+            self.transactions
+                .get_transactions_with_status(TransactionStatus::PartiallyFilled),
+        );
+        let mut order_ids = Vec::new();
+        for transaction in &transactions {
+            let Some(order_id) = self
+                .transactions
+                .get_open_entry_order(&transaction.strategy, &transaction.symbol)
+            else {
+                continue;
+            };
+            info!(
+                "Strategy[{}] cancelling entry order for symbol: {}",
+                transaction.strategy, transaction.symbol
+            );
+            match self.order_handler.cancel_order(&order_id).await {
+                Err(err) => error!("Failed to cancel entry order, error={}", err),
+                _ => order_ids.push(order_id),
+            }
+        }
+        order_ids
+    }
+
+    pub async fn shutdown(&mut self) -> Vec<Uuid> {
+        let policy = self.shutdown_policy;
+        info!("Engine shutting down with policy: {:?}", policy);
+        match policy {
+            ShutdownPolicy::Leave => Vec::new(),
+            ShutdownPolicy::CancelEntries => self.cancel_entry_orders().await,
+            ShutdownPolicy::Flatten => {
+                let mut order_ids = self.cancel_entry_orders().await;
+                order_ids.extend(self.liquidate_positions("shutdown").await);
+                order_ids
+            }
+        }
+    }
+
+    // An escalated exit is replaced rather than closed, so the drain follows it to its replacement
+    pub async fn is_drained(&mut self, order_ids: &[Uuid]) -> bool {
+        self.check_exit_cancels().await;
+        if !self.awaiting_exit_cancels.is_empty() {
+            return false;
+        }
+        let mut pending = order_ids.to_vec();
+        pending.extend(self.resumed_liquidations.iter().cloned());
+        while let Some(order_id) = pending.pop() {
+            match self.transactions.update_order(order_id).await {
+                anyhow::Result::Ok(order) if order.status == OrderStatus::Replaced => {
+                    match self.transactions.find_replacement_order(&order_id).await {
+                        anyhow::Result::Ok(new_order_id) => {
+                            if let Err(err) = self
+                                .transactions
+                                .replace_order(order_id, new_order_id)
+                                .await
+                            {
+                                warn!(
+                                    "Failed to track replacement: {}, error={}",
+                                    new_order_id, err
+                                );
+                            }
+                            pending.push(new_order_id);
+                        }
+                        Err(err) => {
+                            warn!(
+                                "Failed to follow replaced order: {}, error={}",
+                                order_id, err
+                            );
+                            return false;
+                        }
+                    }
+                }
+                anyhow::Result::Ok(order) if order.status.is_open() => return false,
+                Err(err) => warn!("Failed to check order: {}, error={}", order_id, err),
+                _ => (),
+            }
+        }
+        true
+    }
+
+    pub async fn flush(&mut self) -> Result<()> {
+        self.transactions.flush().await
+    }
+
+    fn parse_holding_period(period: &str) -> Result<chrono::Duration> {
+        let Some(unit) = period.chars().last() else {
+            bail!("Empty holding period")
+        };
+        let value: i64 = match period[..period.len() - unit.len_utf8()].parse() {
+            std::result::Result::Ok(value) => value,
+            Err(err) => bail!("Failed to parse holding period: {}, error={}", period, err),
+        };
+        match unit {
+            'd' => Ok(chrono::Duration::days(value)),
+            'h' => Ok(chrono::Duration::hours(value)),
+            'm' => Ok(chrono::Duration::minutes(value)),
+            _ => bail!("Failed to parse holding period unit, unknown: {}", period),
+        }
+    }
+
+    fn find_exit_reason(&self, transaction: &Transaction) -> Option<&'static str> {
+        let config = self.settings.strategies.get(&transaction.strategy)?;
+        if let Some(flatten_at) = &config.flatten_at {
+            match NaiveTime::from_str(flatten_at) {
+                std::result::Result::Ok(cutoff)
+                    if self
+                        .market_session
+                        .is_flatten_due(cutoff, &transaction.entry_time) =>
+                {
+                    return Some("eod")
+                }
+                Err(err) => warn!(
+                    "Strategy[{}] invalid flatten_at, error={}",
+                    transaction.strategy, err
+                ),
+                _ => (),
+            }
+        }
+        if transaction.reason.as_deref() == Some(ADOPTED_UNKNOWN_ENTRY) {
+            return None;
+        }
+        if let Some(period) = &config.max_holding_period {
+            match Self::parse_holding_period(period) {
+                std::result::Result::Ok(period)
+                    if Utc::now() - transaction.entry_time >= period =>
+                {
+                    return Some("time_stop")
+                }
+                Err(err) => warn!("Strategy[{}] {}", transaction.strategy, err),
+                _ => (),
+            }
+        }
+        None
+    }
+
+    pub async fn check_exit_schedule(&mut self) {
+        if !self.market_session.is_locker_active() {
+            return;
+        }
+        let transactions = self.transactions.get_held_transactions();
+        for transaction in &transactions {
+            let Some(reason) = self.find_exit_reason(transaction) else {
+                continue;
+            };
+            info!(
+                "Strategy[{}] symbol[{}] scheduled exit, reason: {}",
+                transaction.strategy, transaction.symbol, reason
+            );
+            if let Some(order_id) = self.handle_liquidate(transaction, reason).await {
+                self.handle_closing_position(transaction, order_id, reason)
+                    .await
+            }
+        }
+    }
+
+    fn is_entry_expired(&self, strategy: &str, submitted_at: &DateTime<Utc>) -> bool {
+        let Some(timeout) = self
+            .settings
+            .strategies
+            .get(strategy)
+            .and_then(|config| config.entry_timeout.as_ref())
+        else {
+            return false;
+        };
+        let close = self.market_session.get_close_after(submitted_at);
+        match Self::is_timeout_reached(timeout, submitted_at, close, Utc::now()) {
+            std::result::Result::Ok(expired) => expired,
+            Err(err) => {
+                warn!("Strategy[{}] invalid entry timeout, {}", strategy, err);
+                false
+            }
+        }
+    }
+
+    fn is_timeout_reached(
+        timeout: &str,
+        submitted_at: &DateTime<Utc>,
+        close: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Result<bool> {
+        if timeout.eq_ignore_ascii_case("close") {
+            return Ok(close.is_some_and(|close| now >= close));
+        }
+        Ok(now - *submitted_at >= Self::parse_holding_period(timeout)?)
+    }
+
+    pub async fn sweep_stale_entries(&mut self) {
+        let transactions = self
+            .transactions
+            .get_transactions_with_status(TransactionStatus::Waiting);
+        for transaction in &transactions {
+            let strategy = &transaction.strategy;
+            let symbol = &transaction.symbol;
+            let Some(order_id) = self.transactions.get_open_entry_order(strategy, symbol) else {
+                continue;
+            };
+            let Some(submitted_at) = self
+                .transactions
+                .get_order(&order_id)
+                .await
+                .map(|order| order.entry_time)
+            else {
+                continue;
+            };
+            if self.expired_entries.contains(&order_id)
+                || !self.is_entry_expired(strategy, &submitted_at)
+            {
+                continue;
+            }
+            info!(
+                "Strategy[{}] cancelling stale entry order: {} for symbol: {}",
+                strategy, order_id, symbol
+            );
+            match self.order_handler.cancel_order(&order_id).await {
+                Err(err) => error!("Failed to cancel stale entry order, error={}", err),
+                _ => {
+                    self.expired_entries.insert(order_id);
+                }
+            }
+        }
+    }
+
+    pub async fn check_kill_switch(&mut self) -> Result<()> {
+        if !self.kill_switch.is_halted() && self.kill_switch.is_flag_file_set() {
+            let cancel_orders = self.kill_switch.default_cancel_orders();
+            let liquidate = self.kill_switch.default_liquidate();
+            self.halt("flag file", cancel_orders, liquidate).await?;
+        }
+        Ok(())
+    }
+
     pub async fn create_position(&mut self, mkt_signal: &MktSignal) -> Result<()> {
+        if self.kill_switch.is_halted() {
+            bail!(
+                "Trading is halted, rejecting new entry, {}",
+                self.kill_switch.get_state()
+            )
+        }
+        if let Some(breach) = self.risk_limits.get_breach() {
+            bail!("Risk limit breached, rejecting new entry, {}", breach)
+        }
         let strategy = &mkt_signal.strategy;
+        let sessions = self.settings.strategies[strategy]
+            .sessions
+            .clone()
+            .unwrap_or_default();
+        if !self.market_session.is_open(&sessions) {
+            return self.handle_closed_market(mkt_signal);
+        }
         let max_positions = self.settings.strategies[strategy].max_positions;
         let current_capacity = self.transactions.count_capacity(strategy);
         if current_capacity >= max_positions as usize {
@@ -82,10 +544,14 @@ impl Engine {
             );
             return Ok(());
         }
-        if let Some(transaction) = self.transactions.get_transaction(&mkt_signal.symbol) {
+        if let Some(transaction) = self
+            .transactions
+            .get_transaction(strategy, &mkt_signal.symbol)
+            .filter(|transaction| transaction.is_open())
+        {
             info!(
                 "Already has an open transaction for strategy: {} symbol: {}",
-                transaction.strategy, transaction.strategy
+                transaction.strategy, transaction.symbol
             );
             return Ok(());
         }
@@ -102,9 +568,10 @@ impl Engine {
         }
         let position_sizing = self.settings.sizing.clone();
         let entry_price = to_num!(mkt_signal.price);
-        let size = Self::size_position(
+        let equity = self.account.equity().await;
+        let mut size = Self::size_position(
             &mkt_signal.symbol,
-            &self.account.equity().await,
+            &equity,
             position_sizing,
             self.settings.strategies.len(),
             &self.mktdata,
@@ -114,6 +581,65 @@ impl Engine {
         let strategy = &mkt_signal.strategy;
         let side = mkt_signal.side;
         let direction = mkt_signal.direction;
+        let mut positions = self.transactions.get_positions().await?;
+        positions.extend(self.transactions.get_pending_entries());
+        let fractionable = self.transactions.get_assets().is_fractionable(symbol);
+        let fractional = self
+            .order_handler
+            .is_fractional_entry(strategy, fractionable);
+        let mut exposure_reason = None;
+        match self.exposure.check(
+            symbol,
+            direction,
+            &size,
+            &entry_price,
+            &equity,
+            &positions,
+            fractional,
+        ) {
+            ExposureDecision::Accept => {}
+            ExposureDecision::Resize(resized, reason) => {
+                warn!(
+                    "Strategy[{}] symbol[{}] resized from {} to {}, {}",
+                    strategy, symbol, size, resized, reason
+                );
+                size = resized;
+                exposure_reason = Some(format!("exposure_resize: {}", reason));
+            }
+            ExposureDecision::Reject(reason) => {
+                bail!(
+                    "Exposure limit breached, rejecting new entry for symbol: {}, {}",
+                    symbol,
+                    reason
+                )
+            }
+        }
+        let amount = self.order_handler.get_entry_amount(
+            strategy,
+            symbol,
+            &size,
+            &entry_price,
+            fractionable,
+        )?;
+        let notional = size.clone() * entry_price.clone();
+        if let Err(err) = self
+            .pre_trade
+            .check_buying_power(&self.account, &notional)
+            .await
+        {
+            bail!(
+                "Buying power check failed, rejecting new entry for symbol: {}, {}",
+                symbol,
+                err
+            )
+        }
+        if let Err(err) = self.pre_trade.check_day_trades(&self.account).await {
+            bail!(
+                "Day trade check failed, rejecting new entry for symbol: {}, {}",
+                symbol,
+                err
+            )
+        }
         info!(
             "Stragegy[{}], Symbol[{}], create a waiting transaction",
             strategy, symbol
@@ -125,41 +651,367 @@ impl Engine {
         {
             bail!("Failed to add waiting transaction, error={}", err)
         };
+        if let Some(reason) = exposure_reason {
+            self.transactions
+                .set_reason(strategy, symbol, &reason)
+                .await?;
+        }
+        self.transactions
+            .add_stop(symbol, strategy, entry_price.clone(), direction)
+            .await?;
+        let exits = ExitLegs {
+            stop_loss: self.transactions.get_initial_stop(strategy, symbol),
+            take_profit: mkt_signal.take_profit.map(|price| to_num!(price)),
+        };
+        let benchmarks = self.get_benchmarks(symbol, entry_price.clone()).await;
+        let client_order_id = self
+            .transactions
+            .prepare_order(strategy, symbol, OrderAction::Create, benchmarks)
+            .await?;
         match self
             .order_handler
             .create_position(
+                strategy,
                 &mkt_signal.symbol,
-                entry_price.clone(),
-                size,
+                entry_price,
+                amount,
                 mkt_signal.side,
+                exits,
+                &client_order_id,
             )
             .await
         {
-            anyhow::Result::Ok(order_id) => {
+            anyhow::Result::Ok(order) => {
                 self.transactions
-                    .add_order(symbol, order_id, side, direction, OrderAction::Create)
+                    .add_order(
+                        strategy,
+                        symbol,
+                        order.id.0,
+                        side,
+                        direction,
+                        OrderAction::Create,
+                    )
                     .await?;
                 info!(
                     "Strategy[{}] symbol[{}] added a waiting order",
                     strategy, symbol
                 );
-                self.transactions
-                    .add_stop(symbol, strategy, entry_price, direction)
+                self.add_exit_legs(strategy, symbol, direction, &order.legs)
+                    .await;
+                Ok(())
+            }
+            Err(err) => {
+                self.transactions.discard_order(&client_order_id).await;
+                if let Err(err) = self
+                    .transactions
+                    .abandon_waiting(strategy, symbol, "entry_failed")
                     .await
+                {
+                    warn!("Failed to cancel waiting transaction, error={}", err);
+                }
+                let message = format!(
+                    "Failed to create new position for symbol: {}, error={}",
+                    mkt_signal.symbol, err
+                );
+                Err(err.context(message))
             }
-            Err(err) => bail!(
-                "Failed to create new position for symbol: {}, error={}",
-                mkt_signal.symbol,
-                err
+        }
+    }
+
+    async fn get_benchmarks(&self, symbol: &str, signal_price: Num) -> Benchmarks {
+        let arrival_price = match self.mktdata.lock().await.get_quote(symbol) {
+            Some((bid, ask)) => (bid + ask) / Num::from(2),
+            None => Num::default(),
+        };
+        Benchmarks {
+            signal_price,
+            arrival_price,
+        }
+    }
+
+    async fn get_exit_benchmarks(&self, transaction: &Transaction) -> Benchmarks {
+        let signal_price = self
+            .transactions
+            .get_stop_price(transaction)
+            .unwrap_or_default();
+        self.get_benchmarks(&transaction.symbol, signal_price).await
+    }
+
+    async fn add_exit_legs(
+        &mut self,
+        strategy: &str,
+        symbol: &str,
+        direction: Direction,
+        legs: &[order::Order],
+    ) {
+        let side = match direction {
+            Direction::Long => Side::Sell,
+            Direction::Short => Side::Buy,
+        };
+        for leg in legs {
+            let action = match leg.type_ {
+                order::Type::Limit => OrderAction::TakeProfit,
+                _ => OrderAction::Protect,
+            };
+            if let Err(err) = self
+                .transactions
+                .add_order(strategy, symbol, leg.id.0, side, direction, action)
+                .await
+            {
+                warn!("Failed to add exit leg order, error={}", err);
+            }
+        }
+    }
+
+    async fn place_exit_oco(&mut self, order_id: Uuid) -> Result<()> {
+        let Some(order) = self.transactions.get_order(&order_id).await else {
+            return Ok(());
+        };
+        let strategy = order.strategy.clone();
+        let symbol = order.symbol.clone();
+        if !self.order_handler.has_exit_oco(&strategy) {
+            return Ok(());
+        }
+        let Some(transaction) = self
+            .transactions
+            .get_transaction(&strategy, &symbol)
+            .filter(|transaction| transaction.status == TransactionStatus::Confirmed)
+            .cloned()
+        else {
+            return Ok(());
+        };
+        if !self
+            .transactions
+            .get_exit_orders(&strategy, &symbol)
+            .is_empty()
+        {
+            return Ok(());
+        }
+        let Some(stop_price) = self.transactions.get_initial_stop(&strategy, &symbol) else {
+            bail!("No stop price for oco exit on symbol: {}", symbol)
+        };
+        let benchmarks = self.get_exit_benchmarks(&transaction).await;
+        let client_order_id = self
+            .transactions
+            .prepare_order(&strategy, &symbol, OrderAction::TakeProfit, benchmarks)
+            .await?;
+        match self
+            .order_handler
+            .place_exit_oco(
+                &strategy,
+                &symbol,
+                &transaction.quantity,
+                transaction.direction,
+                &transaction.entry_price,
+                &stop_price,
+                &client_order_id,
+            )
+            .await
+        {
+            std::result::Result::Ok(order) => {
+                let mut legs = vec![order.clone()];
+                legs.extend(order.legs);
+                self.add_exit_legs(&strategy, &symbol, transaction.direction, &legs)
+                    .await;
+                Ok(())
+            }
+            Err(err) => {
+                self.transactions.discard_order(&client_order_id).await;
+                Err(err)
+            }
+        }
+    }
+
+    fn handle_closed_market(&mut self, mkt_signal: &MktSignal) -> Result<()> {
+        let strategy = &mkt_signal.strategy;
+        let policy = match &self.settings.strategies[strategy].closed_market {
+            Some(policy) => ClosedPolicy::from_str(policy).unwrap_or_else(|err| {
+                warn!("Strategy[{}] {}, rejecting signal", strategy, err);
+                ClosedPolicy::Reject
+            }),
+            None => ClosedPolicy::Reject,
+        };
+        match policy {
+            ClosedPolicy::Queue => {
+                self.queued_signals.retain(|queued| {
+                    queued.strategy != mkt_signal.strategy || queued.symbol != mkt_signal.symbol
+                });
+                self.queued_signals.push(mkt_signal.clone());
+                info!(
+                    "Strategy[{}] symbol[{}] queued until market open at {:?}",
+                    strategy,
+                    mkt_signal.symbol,
+                    self.market_session.get_next_open()
+                );
+                Ok(())
+            }
+            ClosedPolicy::Reject => bail!(
+                "Market closed for strategy: {}, rejecting new entry for symbol: {}",
+                strategy,
+                mkt_signal.symbol
             ),
         }
     }
 
+    pub async fn check_market_session(&mut self) {
+        self.market_session.refresh().await;
+        if self.queued_signals.is_empty() {
+            return;
+        }
+        let (ready, waiting): (Vec<MktSignal>, Vec<MktSignal>) =
+            self.queued_signals.drain(..).partition(|mkt_signal| {
+                let sessions = self.settings.strategies[&mkt_signal.strategy]
+                    .sessions
+                    .clone()
+                    .unwrap_or_default();
+                self.market_session.is_open(&sessions)
+            });
+        self.queued_signals = waiting;
+        for mkt_signal in &ready {
+            info!(
+                "Releasing queued signal for strategy: {} symbol: {}",
+                mkt_signal.strategy, mkt_signal.symbol
+            );
+            if let Err(err) = self.create_position(mkt_signal).await {
+                match BrokerError::class_of(&err) {
+                    Some(ErrorClass::Retryable | ErrorClass::RateLimited) => {
+                        warn!("Queued signal kept for the next check, error={}", err);
+                        self.queued_signals.push(mkt_signal.clone());
+                    }
+                    _ => error!("Queued signal dropped, error={}", err),
+                }
+            }
+        }
+    }
+
+    fn reconcile_interval(&self) -> u64 {
+        self.settings
+            .reconciler
+            .as_ref()
+            .and_then(|config| config.interval_secs)
+            .unwrap_or(reconciler::DEFAULT_INTERVAL_SECS)
+    }
+
+    pub async fn reconcile(&mut self) -> Result<()> {
+        if self
+            .settings
+            .adoption
+            .as_ref()
+            .is_some_and(|config| config.auto)
+        {
+            self.adopt_positions(None).await?;
+        }
+        let config = self.settings.reconciler.clone().unwrap_or_default();
+        let action = ReconcileAction::from_str(&config.action).unwrap_or_else(|err| {
+            warn!("{}, alerting only", err);
+            ReconcileAction::Alert
+        });
+        let discrepancies = self.transactions.reconcile(&config).await?;
+        if discrepancies.is_empty() {
+            debug!("Reconcile found no discrepancies");
+            return Ok(());
+        }
+        warn!(
+            "Reconcile found {} discrepancies, action: {:?}",
+            discrepancies.len(),
+            action
+        );
+        for discrepancy in &discrepancies {
+            warn!("Reconcile report: {}", discrepancy);
+            if action == ReconcileAction::Alert {
+                continue;
+            }
+            if let Err(err) = self.resolve_discrepancy(discrepancy, action).await {
+                error!("Failed to resolve {}, error={}", discrepancy, err);
+            }
+        }
+        Ok(())
+    }
+
+    async fn resolve_discrepancy(
+        &mut self,
+        discrepancy: &Discrepancy,
+        action: ReconcileAction,
+    ) -> Result<()> {
+        match discrepancy {
+            Discrepancy::MissingFill { order_id, .. } => self.handle_fill(*order_id).await,
+            Discrepancy::OrphanLocker { locker_id, .. } => {
+                self.transactions.repair_locker(*locker_id).await;
+                Ok(())
+            }
+            Discrepancy::OrphanPosition { symbol, .. } if action == ReconcileAction::Flatten => {
+                self.order_handler.close_position(symbol).await?;
+                Ok(())
+            }
+            Discrepancy::OrphanPosition { symbol, .. } => {
+                warn!(
+                    "Orphan position for symbol: {} left for manual review or adoption",
+                    symbol
+                );
+                Ok(())
+            }
+            Discrepancy::QuantityMismatch { symbol, broker, .. }
+                if action == ReconcileAction::Flatten && !broker.is_zero() =>
+            {
+                self.transactions.cap_quantity(symbol, broker).await?;
+                for transaction in &self.transactions.get_open_transactions(symbol) {
+                    if !matches!(
+                        transaction.status,
+                        TransactionStatus::PartiallyFilled | TransactionStatus::Confirmed
+                    ) {
+                        continue;
+                    }
+                    if let Some(order_id) = self.handle_liquidate(transaction, "reconcile").await {
+                        self.handle_closing_position(transaction, order_id, "reconcile")
+                            .await
+                    }
+                }
+                Ok(())
+            }
+            Discrepancy::QuantityMismatch { symbol, broker, .. } => {
+                self.transactions.repair_quantity(symbol, broker).await?;
+                self.unsubscribe_if_unused(symbol).await
+            }
+        }
+    }
+
     pub async fn update_status(&mut self) -> Result<()> {
-        let _ = self.account.update_account().await;
+        if self.account.update_account().await.is_ok() {
+            if let Err(err) = self.check_risk_limits().await {
+                error!("Risk limit check failed, error={}", err);
+            }
+        }
+        info!("{}", self.connectors.get_rate_limit_stats());
         self.transactions.print_active_transactions().await
     }
 
+    async fn check_risk_limits(&mut self) -> Result<()> {
+        let equity = self.account.equity().await;
+        let last_equity = self.account.last_equity().await;
+        if self
+            .risk_limits
+            .update_equity(&equity, &last_equity)
+            .await?
+            .is_none()
+        {
+            return Ok(());
+        }
+        match self.risk_limits.breach_action() {
+            BreachAction::Block => info!("Risk limit breached, blocking new entries"),
+            BreachAction::Tighten => {
+                let percent = self.risk_limits.tighten_percent();
+                info!("Risk limit breached, tightening stops to {}%", percent);
+                self.transactions.tighten_stops(percent).await
+            }
+            BreachAction::Flatten => {
+                info!("Risk limit breached, flattening positions");
+                self.cancel_working_orders().await?;
+                self.liquidate_positions("risk_limit").await;
+            }
+        }
+        Ok(())
+    }
+
     async fn size_position(
         symbol: &str,
         total_equity: &Num,
@@ -183,56 +1035,200 @@ impl Engine {
         Ok(position_size)
     }
 
-    async fn handle_closing_order(&mut self, symbol: &str, order_id: Uuid) {
-        self.handle_cancel(symbol, order_id).await;
-        self.transactions.stop_complete(symbol).await
+    async fn unsubscribe_if_unused(&mut self, symbol: &str) -> Result<()> {
+        if !self.transactions.has_open_transaction(symbol) {
+            self.mktdata.lock().await.unsubscribe(symbol).await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_closing_order(&mut self, transaction: &Transaction, order_id: Uuid) {
+        self.handle_cancel(transaction, order_id).await;
+        self.transactions
+            .stop_complete(&transaction.strategy, &transaction.symbol)
+            .await
     }
 
     async fn handle_closing_position(
         &mut self,
-        symbol: &str,
+        transaction: &Transaction,
         order_id: Uuid,
-        direction: Direction,
+        reason: &str,
     ) {
+        let strategy = &transaction.strategy;
+        let symbol = &transaction.symbol;
+        let direction = transaction.direction;
+        if let Err(err) = self
+            .transactions
+            .start_closing(strategy, symbol, reason)
+            .await
+        {
+            warn!("Failed to mark transaction closing, error={}", err);
+        }
         let side = match direction {
             Direction::Long => Side::Sell,
             Direction::Short => Side::Buy,
         };
         if let Err(err) = self
             .transactions
-            .add_order(symbol, order_id, side, direction, OrderAction::Liquidate)
+            .add_order(
+                strategy,
+                symbol,
+                order_id,
+                side,
+                direction,
+                OrderAction::Liquidate,
+            )
             .await
         {
             warn!("Failed to add stop order, error={}", err);
         }
-        self.transactions.stop_complete(symbol).await
+        self.transactions.stop_complete(strategy, symbol).await
+    }
+
+    pub async fn sync_protective_stops(&mut self) {
+        let Some(mut protective_stops) = self.protective_stops.take() else {
+            return;
+        };
+        let now = Utc::now();
+        let transactions = self
+            .transactions
+            .get_transactions_with_status(TransactionStatus::Confirmed);
+        for transaction in &transactions {
+            let strategy = &transaction.strategy;
+            let symbol = &transaction.symbol;
+            let Some(stop_price) = self.transactions.get_stop_price(transaction) else {
+                continue;
+            };
+            let stop_price =
+                protective_stops.get_backstop_price(&stop_price, transaction.direction);
+            let order_id = self.transactions.get_protective_order(strategy, symbol);
+            match protective_stops.find_sync(&transaction.local_id, order_id, &stop_price, now) {
+                StopSync::Place => {
+                    protective_stops.record(transaction.local_id, &stop_price, now);
+                    let side = match transaction.direction {
+                        Direction::Long => Side::Sell,
+                        Direction::Short => Side::Buy,
+                    };
+                    let benchmarks = self.get_exit_benchmarks(transaction).await;
+                    let client_order_id = match self
+                        .transactions
+                        .prepare_order(strategy, symbol, OrderAction::Protect, benchmarks)
+                        .await
+                    {
+                        Err(err) => {
+                            error!("Failed to prepare protective stop, error={}", err);
+                            continue;
+                        }
+                        std::result::Result::Ok(client_order_id) => client_order_id,
+                    };
+                    let order_id = match self
+                        .order_handler
+                        .place_protective_stop(
+                            symbol,
+                            &transaction.quantity,
+                            transaction.direction,
+                            &stop_price,
+                            &client_order_id,
+                        )
+                        .await
+                    {
+                        Err(err) => {
+                            error!("Failed to place protective stop, error={}", err);
+                            self.transactions.discard_order(&client_order_id).await;
+                            continue;
+                        }
+                        std::result::Result::Ok(order_id) => order_id,
+                    };
+                    if let Err(err) = self
+                        .transactions
+                        .add_order(
+                            strategy,
+                            symbol,
+                            order_id,
+                            side,
+                            transaction.direction,
+                            OrderAction::Protect,
+                        )
+                        .await
+                    {
+                        warn!("Failed to add protective stop order, error={}", err);
+                    }
+                }
+                StopSync::Replace(order_id) => {
+                    protective_stops.record(transaction.local_id, &stop_price, now);
+                    match self
+                        .order_handler
+                        .replace_stop(&order_id, &stop_price)
+                        .await
+                    {
+                        Err(err) => error!("Failed to replace protective stop, error={}", err),
+                        std::result::Result::Ok(new_order_id) => {
+                            if let Err(err) = self
+                                .transactions
+                                .replace_order(order_id, new_order_id)
+                                .await
+                            {
+                                warn!("Failed to track protective stop replacement, error={}", err);
+                            }
+                        }
+                    }
+                }
+                StopSync::Keep => (),
+            }
+        }
+        let transaction_ids: Vec<Uuid> = transactions
+            .iter()
+            .map(|transaction| transaction.local_id)
+            .collect();
+        protective_stops.retain(&transaction_ids);
+        self.protective_stops = Some(protective_stops);
     }
 
     pub async fn mktdata_update(&mut self, mktdata_update: &stream::Quote) {
         self.mktdata.lock().await.capture_data(mktdata_update)
     }
 
+    pub async fn trade_update(&mut self, trade: &stream::Trade) {
+        self.mktdata.lock().await.capture_trade(trade)
+    }
+
+    pub async fn bar_update(&mut self, bar: &stream::Bar) {
+        self.mktdata.lock().await.capture_bar(bar)
+    }
+
     pub async fn mktdata_publish(&mut self) {
+        if !self.market_session.is_locker_active() {
+            return;
+        }
         let snapshots = self.mktdata.lock().await.get_snapshots();
         let to_close = self
             .transactions
             .find_transactions_to_close(&snapshots)
             .await;
         for transaction in &to_close {
-            let symbol = transaction.symbol.clone();
+            let strategy = &transaction.strategy;
+            let symbol = &transaction.symbol;
             match transaction.status {
                 TransactionStatus::Waiting => {
-                    let order_id = transaction.orders.first().unwrap();
-                    self.handle_closing_order(&symbol, *order_id).await
+                    match self.transactions.get_open_entry_order(strategy, symbol) {
+                        Some(order_id) => self.handle_closing_order(transaction, order_id).await,
+                        None => self.transactions.activate_stop(strategy, symbol).await,
+                    }
                 }
-                TransactionStatus::Confirmed => match self.handle_liquidate(&symbol).await {
-                    Some(order_id) => {
-                        let direction = transaction.direction;
-                        self.handle_closing_position(&symbol, order_id, direction)
-                            .await
+                TransactionStatus::PartiallyFilled | TransactionStatus::Confirmed => {
+                    match self.handle_liquidate(transaction, "stop").await {
+                        Some(order_id) => {
+                            self.handle_closing_position(transaction, order_id, "stop")
+                                .await
+                        }
+                        None if self.is_awaiting_exit_cancel(strategy, symbol) => (),
+                        None => self.transactions.activate_stop(strategy, symbol).await,
                     }
-                    None => self.transactions.activate_stop(&symbol).await,
-                },
+                }
+                TransactionStatus::Closing => {
+                    warn!("Ignoring mktdata update for closing transaction")
+                }
                 TransactionStatus::Cancelled => {
                     warn!("Ignoring mktdata update for cancelled transaction")
                 }
@@ -243,35 +1239,262 @@ impl Engine {
         }
     }
 
-    async fn handle_cancel(&mut self, symbol: &str, order_id: Uuid) {
-        info!("In handle cancel for symbol: {symbol}");
+    fn is_awaiting_exit_cancel(&self, strategy: &str, symbol: &str) -> bool {
+        self.awaiting_exit_cancels
+            .values()
+            .any(|liquidation| liquidation.strategy == strategy && liquidation.symbol == symbol)
+    }
+
+    async fn resume_liquidation(&mut self, order_id: Uuid) {
+        let Some(liquidation) = self.awaiting_exit_cancels.remove(&order_id) else {
+            return;
+        };
+        let PendingLiquidation {
+            strategy,
+            symbol,
+            reason,
+        } = liquidation;
+        if self
+            .awaiting_exit_cancels
+            .values()
+            .any(|other| other.strategy == strategy && other.symbol == symbol)
+        {
+            return;
+        }
+        let Some(transaction) = self
+            .transactions
+            .get_transaction(&strategy, &symbol)
+            .cloned()
+        else {
+            return;
+        };
+        if transaction.status != TransactionStatus::Closing {
+            return;
+        }
+        info!("Exit orders cancelled for symbol: {symbol}, posting liquidation");
+        match self.post_liquidation(&transaction).await {
+            Some(new_order_id) => {
+                self.handle_closing_position(&transaction, new_order_id, &reason)
+                    .await;
+                self.resumed_liquidations.push(new_order_id);
+            }
+            None => {
+                if let Err(err) = self
+                    .transactions
+                    .reopen(&strategy, &symbol, "liquidate_failed")
+                    .await
+                {
+                    warn!("Failed to reopen transaction, error={}", err);
+                }
+                self.transactions.activate_stop(&strategy, &symbol).await
+            }
+        }
+    }
+
+    // Falls back to polling when the cancel update is missed, e.g. while draining at shutdown
+    pub async fn check_exit_cancels(&mut self) {
+        let order_ids: Vec<Uuid> = self.awaiting_exit_cancels.keys().cloned().collect();
+        for order_id in order_ids {
+            match self.transactions.update_order(order_id).await {
+                anyhow::Result::Ok(order) if order.status == OrderStatus::Filled => {
+                    self.awaiting_exit_cancels.remove(&order_id);
+                }
+                anyhow::Result::Ok(order) if !order.status.is_open() => {
+                    self.resume_liquidation(order_id).await
+                }
+                Err(err) => warn!("Failed to check exit order: {}, error={}", order_id, err),
+                _ => (),
+            }
+        }
+    }
+
+    async fn handle_cancel(&mut self, transaction: &Transaction, order_id: Uuid) {
+        let strategy = &transaction.strategy;
+        let symbol = &transaction.symbol;
+        info!("In handle cancel for strategy: {strategy} symbol: {symbol}");
         match self.order_handler.cancel_order(&order_id).await {
             Err(error) => {
                 error!("Dropping order cancel, failed to send to server, error={error}");
-                self.transactions.activate_stop(symbol).await
+                self.transactions.activate_stop(strategy, symbol).await
             }
-            _ => self.transactions.stop_complete(symbol).await,
+            _ => self.transactions.stop_complete(strategy, symbol).await,
         }
     }
 
-    async fn handle_liquidate(&self, symbol: &str) -> Option<Uuid> {
-        info!("In handle liquidate for symbol: {symbol}");
-        match self.order_handler.liquidate_position(symbol).await {
+    async fn handle_liquidate(&mut self, transaction: &Transaction, reason: &str) -> Option<Uuid> {
+        let strategy = &transaction.strategy;
+        let symbol = &transaction.symbol;
+        info!("In handle liquidate for strategy: {strategy} symbol: {symbol}");
+        if let Some(order_id) = self.transactions.get_open_entry_order(strategy, symbol) {
+            info!("Cancelling unfilled remainder of entry order for symbol: {symbol}");
+            if let Err(error) = self.order_handler.cancel_order(&order_id).await {
+                warn!("Failed to cancel remaining entry order, error={error}");
+            }
+        }
+        let mut awaiting_cancel = false;
+        for order_id in self.transactions.get_exit_orders(strategy, symbol) {
+            info!("Cancelling resting exit order: {order_id} for symbol: {symbol}");
+            match self.order_handler.cancel_order(&order_id).await {
+                Err(error) => warn!("Failed to cancel resting exit order, error={error}"),
+                _ => {
+                    let liquidation = PendingLiquidation {
+                        strategy: strategy.to_string(),
+                        symbol: symbol.to_string(),
+                        reason: reason.to_string(),
+                    };
+                    self.awaiting_exit_cancels.insert(order_id, liquidation);
+                    awaiting_cancel = true;
+                }
+            }
+        }
+        if awaiting_cancel {
+            info!("Liquidation for symbol: {symbol} waiting on exit order cancels");
+            if let Err(err) = self
+                .transactions
+                .start_closing(strategy, symbol, reason)
+                .await
+            {
+                warn!("Failed to mark transaction closing, error={}", err);
+            }
+            self.transactions.stop_complete(strategy, symbol).await;
+            return None;
+        }
+        self.post_liquidation(transaction).await
+    }
+
+    async fn post_liquidation(&mut self, transaction: &Transaction) -> Option<Uuid> {
+        let strategy = &transaction.strategy;
+        let symbol = &transaction.symbol;
+        let quantity = self
+            .transactions
+            .get_unfilled_exit_quantity(strategy, symbol);
+        if !quantity.is_positive() {
+            warn!("Nothing to liquidate for strategy: {strategy} symbol: {symbol}");
+            return None;
+        }
+        let benchmarks = self.get_exit_benchmarks(transaction).await;
+        let client_order_id = match self
+            .transactions
+            .prepare_order(strategy, symbol, OrderAction::Liquidate, benchmarks)
+            .await
+        {
+            Err(error) => {
+                error!("Dropping liquidate, failed to prepare order, error={error}");
+                return None;
+            }
+            std::result::Result::Ok(client_order_id) => client_order_id,
+        };
+        let quote = self.mktdata.lock().await.get_quote(symbol);
+        match self
+            .order_handler
+            .liquidate_position(
+                strategy,
+                symbol,
+                &quantity,
+                transaction.direction,
+                quote,
+                &client_order_id,
+            )
+            .await
+        {
             Err(error) => {
                 error!("Dropping liquidate, failed to send to server, error={error}");
+                self.transactions.discard_order(&client_order_id).await;
                 None
             }
             std::result::Result::Ok(order_id) => Some(order_id),
         }
     }
 
+    pub async fn check_exit_escalations(&mut self) {
+        let now = Utc::now();
+        for (order_id, symbol) in self.order_handler.get_due_escalations(now) {
+            let quote = self.mktdata.lock().await.get_quote(&symbol);
+            match self.order_handler.escalate(&order_id, quote, now).await {
+                Err(err) => error!("Liquidation escalation failed, error={}", err),
+                std::result::Result::Ok(Some(new_order_id)) => {
+                    if let Err(err) = self
+                        .transactions
+                        .replace_order(order_id, new_order_id)
+                        .await
+                    {
+                        warn!("Failed to track re-priced liquidation, error={}", err);
+                    }
+                }
+                std::result::Result::Ok(None) => (),
+            }
+        }
+    }
+
+    async fn escalate_to_market(&mut self, order_id: Uuid) -> Result<()> {
+        let order = self.transactions.update_order(order_id).await?;
+        let Some(transaction) = self
+            .transactions
+            .get_transaction(&order.strategy, &order.symbol)
+            .cloned()
+        else {
+            bail!(
+                "Unable to escalate liquidation, transaction not found for symbol: {}",
+                order.symbol
+            )
+        };
+        let quantity = self
+            .transactions
+            .get_unfilled_exit_quantity(&transaction.strategy, &transaction.symbol);
+        if !quantity.is_positive() {
+            return Ok(());
+        }
+        let benchmarks = self.get_exit_benchmarks(&transaction).await;
+        let client_order_id = self
+            .transactions
+            .prepare_order(
+                &transaction.strategy,
+                &transaction.symbol,
+                OrderAction::Liquidate,
+                benchmarks,
+            )
+            .await?;
+        let new_order_id = match self
+            .order_handler
+            .liquidate_at_market(
+                &transaction.symbol,
+                &quantity,
+                transaction.direction,
+                &client_order_id,
+            )
+            .await
+        {
+            Err(err) => {
+                self.transactions.discard_order(&client_order_id).await;
+                bail!("{}", err)
+            }
+            std::result::Result::Ok(order_id) => order_id,
+        };
+        self.transactions
+            .add_order(
+                &transaction.strategy,
+                &transaction.symbol,
+                new_order_id,
+                order.side,
+                transaction.direction,
+                OrderAction::Liquidate,
+            )
+            .await
+    }
+
     pub async fn order_update(&mut self, order_update: &updates::OrderUpdate) -> Result<()> {
         let order_id = order_update.order.id.0;
         info!("{:?}", order_update.order);
         match order_update.event {
             updates::OrderStatus::New => self.handle_new(order_id).await,
+            updates::OrderStatus::PartialFill => self.handle_partial_fill(order_id).await,
             updates::OrderStatus::Filled => self.handle_fill(order_id).await,
-            updates::OrderStatus::Canceled => self.handle_cancel_reject(order_id).await,
+            updates::OrderStatus::Canceled => {
+                self.handle_cancel_reject(order_id, "cancelled").await
+            }
+            updates::OrderStatus::Rejected => self.handle_cancel_reject(order_id, "rejected").await,
+            updates::OrderStatus::Expired => self.handle_cancel_reject(order_id, "expired").await,
+            updates::OrderStatus::Replaced => self.handle_replace(&order_update.order).await,
             _ => {
                 info!("Not listening to event {:?}", order_update.event);
                 Ok(())
@@ -279,20 +1502,62 @@ impl Engine {
         }
     }
 
-    async fn handle_cancel_reject(&mut self, order_id: Uuid) -> Result<()> {
+    async fn handle_cancel_reject(&mut self, order_id: Uuid, reason: &str) -> Result<()> {
         if let Some(order) = self.transactions.get_order(&order_id).await {
             let symbol = order.symbol.clone();
-            info!("In handle cancel reject for symbol: {}", symbol);
+            let strategy = order.strategy.clone();
+            info!(
+                "In handle cancel reject for symbol: {}, reason: {}",
+                symbol, reason
+            );
 
             match order.action {
-                OrderAction::Create => match self.transactions.cancel_transaction(order_id).await {
-                    anyhow::Result::Err(err) => {
-                        error!("Failed to cancel transaction, error={}", err);
-                        bail!("{}", err)
+                OrderAction::Create => {
+                    let reason = match self.expired_entries.remove(&order_id) {
+                        true => "entry_timeout",
+                        false => reason,
+                    };
+                    match self.transactions.cancel_transaction(order_id, reason).await {
+                        anyhow::Result::Err(err) => {
+                            error!("Failed to cancel transaction, error={}", err);
+                            bail!("{}", err)
+                        }
+                        anyhow::Result::Ok(TransactionStatus::Cancelled) => {
+                            self.unsubscribe_if_unused(&symbol).await?
+                        }
+                        _ => (),
+                    }
+                }
+                OrderAction::Liquidate if self.order_handler.take_market_escalation(&order_id) => {
+                    self.escalate_to_market(order_id).await?
+                }
+                OrderAction::Liquidate => {
+                    if let Err(err) = self.transactions.reopen(&strategy, &symbol, reason).await {
+                        warn!("Failed to reopen transaction, error={}", err);
                     }
-                    _ => self.mktdata.lock().await.unsubscribe(&symbol).await?,
-                },
-                OrderAction::Liquidate => self.transactions.activate_stop(&symbol).await,
+                    self.transactions.activate_stop(&strategy, &symbol).await
+                }
+                OrderAction::Protect | OrderAction::TakeProfit => {
+                    let _ = self.transactions.update_order(order_id).await?;
+                    self.resume_liquidation(order_id).await
+                }
+            }
+        } else {
+            warn!("Order with Id: {}, not found in db", order_id);
+        }
+        Ok(())
+    }
+
+    async fn handle_partial_fill(&mut self, order_id: Uuid) -> Result<()> {
+        if let Some(order) = self.transactions.get_order(&order_id).await {
+            let symbol = order.symbol.clone();
+            info!("In handle partial fill for symbol: {}", symbol);
+
+            match order.action {
+                OrderAction::Create => self.transactions.confirm_transaction(order_id).await?,
+                OrderAction::Liquidate | OrderAction::Protect | OrderAction::TakeProfit => {
+                    let _ = self.transactions.update_order(order_id).await?;
+                }
             }
         } else {
             warn!("Order with Id: {}, not found in db", order_id);
@@ -300,16 +1565,39 @@ impl Engine {
         Ok(())
     }
 
+    async fn handle_replace(&mut self, order: &order::Order) -> Result<()> {
+        let order_id = order.id.0;
+        info!("In handle replace for symbol: {}", order.symbol);
+        let (old_order_id, new_order_id) = if self.transactions.get_order(&order_id).await.is_some()
+        {
+            let new_order_id = self.transactions.find_replacement_order(&order_id).await?;
+            (order_id, new_order_id)
+        } else {
+            let old_order_id = self.transactions.find_replaced_order(order).await?;
+            (old_order_id, order_id)
+        };
+        self.transactions
+            .replace_order(old_order_id, new_order_id)
+            .await
+    }
+
     async fn handle_new(&mut self, order_id: Uuid) -> Result<()> {
         if let Some(order) = self.transactions.get_order(&order_id).await {
             let symbol = order.symbol.clone();
             info!("In handle new for symbol: {}", symbol);
 
-            if let OrderAction::Create = order.action {
-                self.transactions.activate_stop(&symbol).await;
-                self.mktdata.lock().await.subscribe(&symbol).await?;
-            } else {
-                warn!("Didn't add order to locker action: {}", order.action);
+            match order.action {
+                OrderAction::Create => {
+                    let strategy = order.strategy.clone();
+                    self.transactions.activate_stop(&strategy, &symbol).await;
+                    self.mktdata.lock().await.subscribe(&symbol).await?;
+                }
+                OrderAction::Protect | OrderAction::TakeProfit => {
+                    let _ = self.transactions.update_order(order_id).await?;
+                }
+                OrderAction::Liquidate => {
+                    warn!("Didn't add order to locker action: {}", order.action)
+                }
             };
         } else {
             warn!("Order with Id: {}, not found in db", order_id);
@@ -325,12 +1613,33 @@ impl Engine {
             match order.action {
                 OrderAction::Create => {
                     self.transactions.confirm_transaction(order_id).await?;
+                    self.place_exit_oco(order_id).await?;
                 }
                 OrderAction::Liquidate => {
-                    self.mktdata.lock().await.unsubscribe(&symbol).await?;
-                    self.transactions.stop_complete(&symbol).await;
-
-                    self.transactions.close_transaction(order_id).await?
+                    let strategy = order.strategy.clone();
+                    self.order_handler.take_market_escalation(&order_id);
+                    self.transactions.stop_complete(&strategy, &symbol).await;
+                    self.transactions.close_transaction(order_id).await?;
+                    self.unsubscribe_if_unused(&symbol).await?
+                }
+                OrderAction::Protect | OrderAction::TakeProfit => {
+                    let strategy = order.strategy.clone();
+                    let reason = match order.action {
+                        OrderAction::TakeProfit => "take_profit",
+                        _ => "broker_stop",
+                    };
+                    warn!("Exit leg filled for strategy: {strategy} symbol: {symbol}, reason: {reason}");
+                    self.awaiting_exit_cancels.remove(&order_id);
+                    if let Err(err) = self
+                        .transactions
+                        .start_closing(&strategy, &symbol, reason)
+                        .await
+                    {
+                        warn!("Failed to mark transaction closing, error={}", err);
+                    }
+                    self.transactions.stop_complete(&strategy, &symbol).await;
+                    self.transactions.close_transaction(order_id).await?;
+                    self.unsubscribe_if_unused(&symbol).await?
                 }
             };
         } else {
@@ -352,6 +1661,15 @@ impl Engine {
     pub async fn run(engine: Arc<Mutex<Engine>>, shutdown_signal: CancellationToken) -> Result<()> {
         let mut event_subscriber = engine.lock().await.get_event_subscriber()?;
         let mut mktdata_publish_interval = interval(Duration::from_millis(100));
+        let mut kill_switch_interval = interval(Duration::from_secs(5));
+        let mut market_session_interval = interval(Duration::from_secs(30));
+        let mut exit_schedule_interval = interval(Duration::from_secs(15));
+        let mut entry_timeout_interval = interval(Duration::from_secs(15));
+        let mut protective_stop_interval = interval(Duration::from_secs(1));
+        let mut exit_escalation_interval = interval(Duration::from_secs(1));
+        let mut reconcile_interval = interval(Duration::from_secs(
+            engine.lock().await.reconcile_interval(),
+        ));
         tokio::spawn(async move {
             let _ = engine.lock().await.subscribe_to_mktdata().await;
             loop {
@@ -366,6 +1684,14 @@ impl Engine {
                                 debug!("Found a mkdata event: {event:?}");
                                 engine.lock().await.mktdata_update(&event).await;
                             }
+                            anyhow::Result::Ok(Event::Trade(event)) => {
+                                debug!("Found a trade print event: {event:?}");
+                                engine.lock().await.trade_update(&event).await;
+                            }
+                            anyhow::Result::Ok(Event::Bar(event)) => {
+                                debug!("Found a bar event: {event:?}");
+                                engine.lock().await.bar_update(&event).await;
+                            }
                             anyhow::Result::Err(err) => {
                                 error!("Unknown error: {err}");
                                 shutdown_signal.cancel();
@@ -377,6 +1703,33 @@ impl Engine {
                         debug!("Publish mktdata snapshots");
                         let _ = engine.lock().await.mktdata_publish().await;
                     }
+                    _ = kill_switch_interval.tick() => {
+                        if let Err(err) = engine.lock().await.check_kill_switch().await {
+                            error!("Kill switch check failed, error={err}");
+                        }
+                    }
+                    _ = market_session_interval.tick() => {
+                        engine.lock().await.check_market_session().await;
+                    }
+                    _ = exit_schedule_interval.tick() => {
+                        engine.lock().await.check_exit_schedule().await;
+                    }
+                    _ = entry_timeout_interval.tick() => {
+                        engine.lock().await.sweep_stale_entries().await;
+                    }
+                    _ = protective_stop_interval.tick() => {
+                        engine.lock().await.sync_protective_stops().await;
+                    }
+                    _ = exit_escalation_interval.tick() => {
+                        let mut engine = engine.lock().await;
+                        engine.check_exit_escalations().await;
+                        engine.check_exit_cancels().await;
+                    }
+                    _ = reconcile_interval.tick() => {
+                        if let Err(err) = engine.lock().await.reconcile().await {
+                            error!("Reconcile failed, error={err}");
+                        }
+                    }
                     _ = shutdown_signal.cancelled() => {
                         break;
                     }
@@ -387,3 +1740,47 @@ impl Engine {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_holding_period() {
+        assert_eq!(
+            Engine::parse_holding_period("2d").unwrap(),
+            chrono::Duration::days(2)
+        );
+        assert_eq!(
+            Engine::parse_holding_period("90m").unwrap(),
+            chrono::Duration::minutes(90)
+        );
+        assert!(Engine::parse_holding_period("").is_err());
+        assert!(Engine::parse_holding_period("3w").is_err());
+        assert!(Engine::parse_holding_period("h").is_err());
+    }
+
+    #[test]
+    fn test_stale_entry_timeout() {
+        let submitted_at = DateTime::<Utc>::from_str("2024-07-05T14:00:00Z").unwrap();
+        let close = DateTime::<Utc>::from_str("2024-07-05T20:00:00Z").unwrap();
+        let at = |time: &str| DateTime::<Utc>::from_str(time).unwrap();
+
+        let expired = |timeout: &str, now: &str| {
+            Engine::is_timeout_reached(timeout, &submitted_at, Some(close), at(now)).unwrap()
+        };
+        assert!(!expired("30m", "2024-07-05T14:29:00Z"));
+        assert!(expired("30m", "2024-07-05T14:30:00Z"));
+        assert!(!expired("close", "2024-07-05T19:59:00Z"));
+        assert!(expired("CLOSE", "2024-07-05T20:00:00Z"));
+
+        assert!(!Engine::is_timeout_reached(
+            "close",
+            &submitted_at,
+            None,
+            at("2024-07-08T20:00:00Z")
+        )
+        .unwrap());
+        assert!(Engine::is_timeout_reached("soon", &submitted_at, Some(close), close).is_err());
+    }
+}
@@ -0,0 +1,468 @@
+use super::web_clients::Connectors;
+use anyhow::bail;
+use anyhow::Result;
+use apca::api::v2::calendar;
+use chrono::DateTime;
+use chrono::Datelike;
+use chrono::Duration;
+use chrono::NaiveDate;
+use chrono::NaiveTime;
+use chrono::TimeZone;
+use chrono::Utc;
+use chrono::Weekday;
+use chrono_tz::America::New_York;
+use chrono_tz::Tz;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::info;
+use tracing::warn;
+
+use crate::settings::MarketSessionConfig;
+use crate::Settings;
+
+const CALENDAR_DAYS_AHEAD: i64 = 30;
+const AFTER_HOURS_LENGTH: i64 = 4;
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Session {
+    PreMarket,
+    #[default]
+    Regular,
+    AfterHours,
+    Closed,
+}
+
+impl FromStr for Session {
+    type Err = String;
+
+    fn from_str(val: &str) -> Result<Self, Self::Err> {
+        match val.to_lowercase().as_str() {
+            "pre" | "pre_market" => std::result::Result::Ok(Session::PreMarket),
+            "regular" => std::result::Result::Ok(Session::Regular),
+            "after" | "after_hours" => std::result::Result::Ok(Session::AfterHours),
+            "closed" => std::result::Result::Ok(Session::Closed),
+            _ => Err(format!("Failed to parse session, unknown: {}", val)),
+        }
+    }
+}
+
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum ClosedPolicy {
+    Queue,
+    #[default]
+    Reject,
+}
+
+impl FromStr for ClosedPolicy {
+    type Err = String;
+
+    fn from_str(val: &str) -> Result<Self, Self::Err> {
+        match val.to_lowercase().as_str() {
+            "queue" => std::result::Result::Ok(ClosedPolicy::Queue),
+            "reject" => std::result::Result::Ok(ClosedPolicy::Reject),
+            _ => Err(format!(
+                "Failed to parse closed market policy, unknown: {}",
+                val
+            )),
+        }
+    }
+}
+
+// Open and close are exchange local (US/Eastern) times, as returned by the calendar endpoint
+#[derive(Debug, Clone, Deserialize)]
+pub struct TradingDay {
+    pub date: NaiveDate,
+    pub open: NaiveTime,
+    pub close: NaiveTime,
+    pub session_close: NaiveTime,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CalendarOverride {
+    pub date: NaiveDate,
+    pub open: Option<NaiveTime>,
+    pub close: Option<NaiveTime>,
+    pub session_close: Option<NaiveTime>,
+}
+
+impl TradingDay {
+    fn new(date: NaiveDate, open: NaiveTime, close: NaiveTime) -> Self {
+        TradingDay {
+            date,
+            open,
+            close,
+            session_close: close + Duration::hours(AFTER_HOURS_LENGTH),
+        }
+    }
+}
+
+impl From<calendar::OpenClose> for TradingDay {
+    fn from(day: calendar::OpenClose) -> Self {
+        TradingDay::new(day.date, day.open, day.close)
+    }
+}
+
+pub struct MarketSession {
+    connectors: Arc<Connectors>,
+    config: MarketSessionConfig,
+    calendar: HashMap<NaiveDate, TradingDay>,
+    overrides: HashMap<NaiveDate, CalendarOverride>,
+    refreshed_on: Option<NaiveDate>,
+}
+
+impl MarketSession {
+    pub fn new(settings: &Settings, connectors: &Arc<Connectors>) -> Self {
+        MarketSession {
+            connectors: Arc::clone(connectors),
+            config: settings.market_session.clone().unwrap_or_default(),
+            calendar: HashMap::default(),
+            overrides: HashMap::default(),
+            refreshed_on: None,
+        }
+    }
+
+    pub async fn startup(&mut self) -> Result<()> {
+        self.refresh().await;
+        match self.connectors.get_clock().await {
+            anyhow::Result::Ok(clock) => info!(
+                "Market clock open[{}], next open[{}], next close[{}], session[{}]",
+                clock.open,
+                clock.next_open,
+                clock.next_close,
+                self.get_session(&Utc::now())
+            ),
+            Err(err) => warn!("Failed to fetch market clock, error={}", err),
+        }
+        Ok(())
+    }
+
+    pub async fn refresh(&mut self) {
+        let today = Utc::now().date_naive();
+        if self.refreshed_on == Some(today) {
+            return;
+        }
+        let request = calendar::CalendarReq {
+            start: today - Duration::days(1),
+            end: today + Duration::days(CALENDAR_DAYS_AHEAD),
+        };
+        match self.connectors.get_calendar(&request).await {
+            anyhow::Result::Ok(days) => {
+                info!("Loaded market calendar with {} trading days", days.len());
+                self.calendar = days
+                    .into_iter()
+                    .map(|day| (day.date, TradingDay::from(day)))
+                    .collect();
+                self.refreshed_on = Some(today);
+            }
+            Err(err) => {
+                warn!("Failed to fetch market calendar, using calendar file, error={err}");
+                self.calendar.clear();
+                match self.read_calendar_file() {
+                    anyhow::Result::Ok(overrides) => {
+                        info!("Loaded {} calendar overrides from file", overrides.len());
+                        self.overrides = overrides.into_iter().map(|day| (day.date, day)).collect();
+                    }
+                    Err(err) => warn!("Failed to read calendar file, error={err}"),
+                }
+            }
+        };
+    }
+
+    fn read_calendar_file(&self) -> Result<Vec<CalendarOverride>> {
+        let Some(path) = &self.config.calendar_file else {
+            bail!("No calendar file configured")
+        };
+        let mut file = File::open(path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let overrides: Vec<CalendarOverride> = serde_json::from_str(&contents)?;
+        anyhow::Result::Ok(overrides)
+    }
+
+    fn to_eastern(time: &DateTime<Utc>) -> DateTime<Tz> {
+        time.with_timezone(&New_York)
+    }
+
+    fn from_eastern(date: NaiveDate, time: NaiveTime) -> DateTime<Utc> {
+        New_York
+            .from_local_datetime(&date.and_time(time))
+            .earliest()
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    pub fn eastern_date(time: &DateTime<Utc>) -> NaiveDate {
+        Self::to_eastern(time).date_naive()
+    }
+
+    fn get_trading_day(&self, date: NaiveDate) -> Option<TradingDay> {
+        if !self.calendar.is_empty() {
+            return self.calendar.get(&date).cloned();
+        }
+        if let Some(day) = self.overrides.get(&date) {
+            return match (day.open, day.close) {
+                (Some(open), Some(close)) => {
+                    let mut trading_day = TradingDay::new(date, open, close);
+                    if let Some(session_close) = day.session_close {
+                        trading_day.session_close = session_close;
+                    }
+                    Some(trading_day)
+                }
+                _ => None,
+            };
+        }
+        match date.weekday() {
+            Weekday::Sat | Weekday::Sun => None,
+            _ => Some(TradingDay::new(
+                date,
+                NaiveTime::from_hms_opt(9, 30, 0).unwrap(),
+                NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+            )),
+        }
+    }
+
+    pub fn get_session(&self, now: &DateTime<Utc>) -> Session {
+        let local = Self::to_eastern(now);
+        let Some(day) = self.get_trading_day(local.date_naive()) else {
+            return Session::Closed;
+        };
+        let time = local.time();
+        let pre_market_open = NaiveTime::from_hms_opt(4, 0, 0).unwrap();
+        if time >= day.open && time < day.close {
+            Session::Regular
+        } else if time >= pre_market_open && time < day.open {
+            Session::PreMarket
+        } else if time >= day.close && time < day.session_close {
+            Session::AfterHours
+        } else {
+            Session::Closed
+        }
+    }
+
+    // On early close days the cutoff keeps its lead on the close, the locker stops at the close
+    fn get_flatten_time(
+        &self,
+        cutoff: NaiveTime,
+        entry_time: &DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        let entry_date = Self::eastern_date(entry_time);
+        let regular_close = NaiveTime::from_hms_opt(16, 0, 0).unwrap();
+        (0..=CALENDAR_DAYS_AHEAD)
+            .filter_map(|days| self.get_trading_day(entry_date + Duration::days(days)))
+            .map(|day| {
+                let cutoff = match day.close < regular_close {
+                    true => day.close - (regular_close - cutoff).max(Duration::zero()),
+                    false => cutoff,
+                };
+                Self::from_eastern(day.date, cutoff)
+            })
+            .find(|flatten| flatten > entry_time)
+    }
+
+    pub fn is_flatten_due(&self, cutoff: NaiveTime, entry_time: &DateTime<Utc>) -> bool {
+        self.get_flatten_time(cutoff, entry_time)
+            .is_some_and(|flatten| Utc::now() >= flatten)
+    }
+
+    fn parse_sessions(sessions: &[String]) -> Vec<Session> {
+        if sessions.is_empty() {
+            return vec![Session::Regular];
+        }
+        sessions
+            .iter()
+            .filter_map(|session| match Session::from_str(session) {
+                std::result::Result::Ok(session) => Some(session),
+                Err(err) => {
+                    warn!("{}, ignoring", err);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    pub fn is_open(&self, sessions: &[String]) -> bool {
+        let session = self.get_session(&Utc::now());
+        Self::parse_sessions(sessions).contains(&session)
+    }
+
+    pub fn is_locker_active(&self) -> bool {
+        let sessions = self.config.locker_sessions.clone().unwrap_or_default();
+        self.is_open(&sessions)
+    }
+
+    pub fn get_close_after(&self, entry_time: &DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let entry_date = Self::eastern_date(entry_time);
+        (0..=CALENDAR_DAYS_AHEAD)
+            .filter_map(|days| self.get_trading_day(entry_date + Duration::days(days)))
+            .map(|day| Self::from_eastern(day.date, day.close))
+            .find(|close| close > entry_time)
+    }
+
+    pub fn get_next_open(&self) -> Option<DateTime<Utc>> {
+        let today = Utc::now().date_naive();
+        (0..=CALENDAR_DAYS_AHEAD)
+            .filter_map(|days| self.get_trading_day(today + Duration::days(days)))
+            .map(|day| Self::from_eastern(day.date, day.open))
+            .find(|open| *open > Utc::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_calendar() -> HashMap<NaiveDate, TradingDay> {
+        let days = vec![
+            TradingDay::new(
+                NaiveDate::from_ymd_opt(2024, 7, 3).unwrap(),
+                NaiveTime::from_hms_opt(9, 30, 0).unwrap(),
+                NaiveTime::from_hms_opt(13, 0, 0).unwrap(),
+            ),
+            TradingDay::new(
+                NaiveDate::from_ymd_opt(2024, 7, 5).unwrap(),
+                NaiveTime::from_hms_opt(9, 30, 0).unwrap(),
+                NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+            ),
+        ];
+        days.into_iter().map(|day| (day.date, day)).collect()
+    }
+
+    fn build_session(calendar: HashMap<NaiveDate, TradingDay>) -> MarketSession {
+        MarketSession {
+            connectors: Connectors::new("key", "secret", false, None, None, Default::default())
+                .unwrap(),
+            config: MarketSessionConfig::default(),
+            calendar,
+            overrides: HashMap::default(),
+            refreshed_on: None,
+        }
+    }
+
+    fn get_session(calendar: HashMap<NaiveDate, TradingDay>, now: &str) -> Session {
+        build_session(calendar).get_session(&DateTime::from_str(now).unwrap())
+    }
+
+    #[test]
+    fn test_sessions_with_early_close_and_holiday() {
+        let calendar = build_calendar();
+        assert_eq!(
+            get_session(calendar.clone(), "2024-07-03T12:00:00Z"),
+            Session::PreMarket
+        );
+        assert_eq!(
+            get_session(calendar.clone(), "2024-07-03T15:00:00Z"),
+            Session::Regular
+        );
+        assert_eq!(
+            get_session(calendar.clone(), "2024-07-03T18:00:00Z"),
+            Session::AfterHours
+        );
+        assert_eq!(
+            get_session(calendar, "2024-07-04T15:00:00Z"),
+            Session::Closed
+        );
+    }
+
+    #[test]
+    fn test_weekday_hours_without_calendar() {
+        assert_eq!(
+            get_session(HashMap::default(), "2024-01-08T15:00:00Z"),
+            Session::Regular
+        );
+        assert_eq!(
+            get_session(HashMap::default(), "2024-01-06T15:00:00Z"),
+            Session::Closed
+        );
+    }
+
+    #[test]
+    fn test_close_after_entry_skips_holiday() {
+        let session = build_session(build_calendar());
+        let close = |entry: &str| session.get_close_after(&DateTime::from_str(entry).unwrap());
+        assert_eq!(
+            close("2024-07-03T14:00:00Z"),
+            Some(DateTime::from_str("2024-07-03T17:00:00Z").unwrap())
+        );
+        assert_eq!(
+            close("2024-07-03T18:00:00Z"),
+            Some(DateTime::from_str("2024-07-05T20:00:00Z").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_flatten_cutoff_keeps_lead_on_early_close() {
+        let session = build_session(build_calendar());
+        let cutoff = NaiveTime::from_hms_opt(15, 45, 0).unwrap();
+        let flatten =
+            |entry: &str| session.get_flatten_time(cutoff, &DateTime::from_str(entry).unwrap());
+        assert_eq!(
+            flatten("2024-07-03T14:00:00Z"),
+            Some(DateTime::<Utc>::from_str("2024-07-03T16:45:00Z").unwrap())
+        );
+        assert_eq!(
+            flatten("2024-07-05T14:00:00Z"),
+            Some(DateTime::<Utc>::from_str("2024-07-05T19:45:00Z").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_entry_after_cutoff_flattens_next_session() {
+        let session = build_session(build_calendar());
+        let cutoff = NaiveTime::from_hms_opt(15, 45, 0).unwrap();
+        let flatten =
+            |entry: &str| session.get_flatten_time(cutoff, &DateTime::from_str(entry).unwrap());
+        assert_eq!(
+            flatten("2024-07-03T17:30:00Z"),
+            Some(DateTime::<Utc>::from_str("2024-07-05T19:45:00Z").unwrap())
+        );
+        assert_eq!(
+            flatten("2024-07-03T22:00:00Z"),
+            Some(DateTime::<Utc>::from_str("2024-07-05T19:45:00Z").unwrap())
+        );
+        assert_eq!(flatten("2024-07-05T20:00:00Z"), None);
+    }
+
+    #[test]
+    fn test_eastern_date_rolls_over_at_new_york_midnight() {
+        let date = |time: &str| MarketSession::eastern_date(&DateTime::from_str(time).unwrap());
+        let day = NaiveDate::from_ymd_opt(2024, 7, 3).unwrap();
+        assert_eq!(date("2024-07-04T00:30:00Z"), day);
+        assert_eq!(date("2024-07-04T03:59:00Z"), day);
+        assert_eq!(date("2024-07-04T04:00:00Z"), day.succ_opt().unwrap());
+
+        let day = NaiveDate::from_ymd_opt(2024, 3, 9).unwrap();
+        assert_eq!(date("2024-03-10T04:30:00Z"), day);
+        assert_eq!(date("2024-03-10T05:00:00Z"), day.succ_opt().unwrap());
+        let day = NaiveDate::from_ymd_opt(2024, 11, 3).unwrap();
+        assert_eq!(date("2024-11-03T04:30:00Z"), day);
+        assert_eq!(date("2024-11-04T04:30:00Z"), day);
+    }
+
+    #[test]
+    fn test_after_hours_end_follows_calendar_close() {
+        let calendar = build_calendar();
+        assert_eq!(
+            get_session(calendar.clone(), "2024-07-03T20:30:00Z"),
+            Session::AfterHours
+        );
+        assert_eq!(
+            get_session(calendar.clone(), "2024-07-03T21:30:00Z"),
+            Session::Closed
+        );
+        assert_eq!(
+            get_session(calendar, "2024-07-05T23:30:00Z"),
+            Session::AfterHours
+        );
+    }
+}
@@ -6,19 +6,63 @@ use chrono::DateTime;
 use chrono::Duration;
 use chrono::Utc;
 use num_decimal::Num;
+use serde::Deserialize;
 use std::collections::HashMap;
 use std::fmt;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::vec::Vec;
 use tokio::sync::Mutex;
 use tracing::info;
 use tracing::warn;
 
+use crate::events::Direction;
+
+#[derive(Debug, PartialEq, Clone, Copy, Default, Deserialize)]
+#[serde(try_from = "String")]
+pub enum PriceSource {
+    #[default]
+    MidQuote,
+    LastTrade,
+    BidAsk,
+    BarClose,
+}
+
+impl FromStr for PriceSource {
+    type Err = String;
+
+    fn from_str(val: &str) -> Result<Self, Self::Err> {
+        match val.to_lowercase().as_str() {
+            "mid" | "mid_quote" => std::result::Result::Ok(PriceSource::MidQuote),
+            "trade" | "last_trade" => std::result::Result::Ok(PriceSource::LastTrade),
+            "bid_ask" => std::result::Result::Ok(PriceSource::BidAsk),
+            "bar" | "bar_close" => std::result::Result::Ok(PriceSource::BarClose),
+            _ => Err(format!("Failed to parse price source, unknown: {}", val)),
+        }
+    }
+}
+
+impl TryFrom<String> for PriceSource {
+    type Error = String;
+
+    fn try_from(val: String) -> Result<Self, Self::Error> {
+        PriceSource::from_str(&val)
+    }
+}
+
+impl fmt::Display for PriceSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct Snapshot {
     pub bid_price: Num,
     pub sell_price: Num,
     pub mid_price: Num,
+    pub last_trade_price: Option<Num>,
+    pub bar_close_price: Option<Num>,
     pub last_seen: DateTime<Utc>,
 }
 
@@ -30,25 +74,52 @@ impl fmt::Display for Snapshot {
             self.bid_price.round_with(2),
             self.sell_price.round_with(2),
             self.mid_price.round_with(2),
-        )
+        )?;
+        if let Some(price) = &self.last_trade_price {
+            write!(f, ", trade[{}]", price.round_with(2))?;
+        }
+        if let Some(price) = &self.bar_close_price {
+            write!(f, ", bar[{}]", price.round_with(2))?;
+        }
+        fmt::Result::Ok(())
     }
 }
 
 impl Snapshot {
-    pub fn new(bid: Num, ask: Num) -> Self {
-        let mid = (ask.clone() - bid.clone()) / 2 + bid.clone();
+    pub fn new() -> Self {
         Snapshot {
-            bid_price: bid,
-            sell_price: ask,
-            mid_price: mid,
             last_seen: Utc::now(),
+            ..Default::default()
         }
     }
 
     pub fn update(&mut self, bid: Num, ask: Num) {
-        let last_seen = self.last_seen;
-        *self = Self::new(bid, ask);
-        self.last_seen = last_seen;
+        let mid = (ask.clone() - bid.clone()) / 2 + bid.clone();
+        self.bid_price = bid;
+        self.sell_price = ask;
+        self.mid_price = mid;
+    }
+
+    pub fn update_trade(&mut self, price: Num) {
+        self.last_trade_price = Some(price);
+    }
+
+    pub fn update_bar(&mut self, close: Num) {
+        self.bar_close_price = Some(close);
+    }
+
+    pub fn get_price(&self, source: PriceSource, direction: Direction) -> Option<Num> {
+        let has_quote = !self.bid_price.is_zero() && !self.sell_price.is_zero();
+        match source {
+            PriceSource::MidQuote if has_quote => Some(self.mid_price.clone()),
+            PriceSource::BidAsk if has_quote => match direction {
+                Direction::Long => Some(self.bid_price.clone()),
+                Direction::Short => Some(self.sell_price.clone()),
+            },
+            PriceSource::LastTrade => self.last_trade_price.clone(),
+            PriceSource::BarClose => self.bar_close_price.clone(),
+            _ => None,
+        }
     }
 
     pub fn is_periodic_check(&mut self) -> bool {
@@ -139,22 +210,102 @@ impl MktData {
         to_check
     }
 
-    pub fn capture_data(&mut self, mktdata_update: &stream::Quote) {
-        let symbol = &mktdata_update.symbol;
-        let bid = &mktdata_update.ask_price;
-        let ask = &mktdata_update.bid_price;
-        if let Some(wrapped_snapshot) = &mut self.snapshots.get_mut(symbol) {
-            match wrapped_snapshot {
-                Some(snapshot) => {
-                    snapshot.update(bid.clone(), ask.clone());
-                }
-                None => {
-                    let snapshot = Snapshot::new(bid.clone(), ask.clone());
-                    self.snapshots.insert(symbol.clone(), Some(snapshot));
-                }
+    pub fn get_quote(&self, symbol: &str) -> Option<(Num, Num)> {
+        let snapshot = self.snapshots.get(symbol)?.as_ref()?;
+        if snapshot.bid_price.is_zero() || snapshot.sell_price.is_zero() {
+            return None;
+        }
+        Some((snapshot.bid_price.clone(), snapshot.sell_price.clone()))
+    }
+
+    fn get_snapshot_mut(&mut self, symbol: &str) -> Option<&mut Snapshot> {
+        match self.snapshots.get_mut(symbol) {
+            Some(wrapped_snapshot) => Some(wrapped_snapshot.get_or_insert_with(Snapshot::new)),
+            None => {
+                warn!("Symbol[{}] not found in mktdata update", symbol);
+                None
             }
-        } else {
-            warn!("Symbol[{}] not found in mktdata update", symbol);
         }
     }
+
+    pub fn capture_data(&mut self, mktdata_update: &stream::Quote) {
+        let bid = &mktdata_update.bid_price;
+        let ask = &mktdata_update.ask_price;
+        if let Some(snapshot) = self.get_snapshot_mut(&mktdata_update.symbol) {
+            snapshot.update(bid.clone(), ask.clone());
+        }
+    }
+
+    pub fn capture_trade(&mut self, trade: &stream::Trade) {
+        if let Some(snapshot) = self.get_snapshot_mut(&trade.symbol) {
+            snapshot.update_trade(trade.trade_price.clone());
+        }
+    }
+
+    pub fn capture_bar(&mut self, bar: &stream::Bar) {
+        if let Some(snapshot) = self.get_snapshot_mut(&bar.symbol) {
+            snapshot.update_bar(bar.close_price.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_price_source() {
+        assert_eq!(PriceSource::from_str("mid").unwrap(), PriceSource::MidQuote);
+        assert_eq!(
+            PriceSource::from_str("Last_Trade").unwrap(),
+            PriceSource::LastTrade
+        );
+        assert_eq!(
+            PriceSource::from_str("bid_ask").unwrap(),
+            PriceSource::BidAsk
+        );
+        assert_eq!(PriceSource::from_str("bar").unwrap(), PriceSource::BarClose);
+        assert!(PriceSource::from_str("vwap").is_err());
+
+        let source: PriceSource = serde_json::from_str(r#""trade""#).unwrap();
+        assert_eq!(source, PriceSource::LastTrade);
+        assert!(serde_json::from_str::<PriceSource>(r#""vwap""#).is_err());
+    }
+
+    #[test]
+    fn test_get_price() {
+        let mut snapshot = Snapshot::new();
+        assert_eq!(
+            snapshot.get_price(PriceSource::MidQuote, Direction::Long),
+            None
+        );
+        assert_eq!(
+            snapshot.get_price(PriceSource::LastTrade, Direction::Long),
+            None
+        );
+
+        snapshot.update(Num::from(100), Num::from(102));
+        snapshot.update_trade(Num::from(103));
+        snapshot.update_bar(Num::from(99));
+        assert_eq!(
+            snapshot.get_price(PriceSource::MidQuote, Direction::Long),
+            Some(Num::from(101))
+        );
+        assert_eq!(
+            snapshot.get_price(PriceSource::BidAsk, Direction::Long),
+            Some(Num::from(100))
+        );
+        assert_eq!(
+            snapshot.get_price(PriceSource::BidAsk, Direction::Short),
+            Some(Num::from(102))
+        );
+        assert_eq!(
+            snapshot.get_price(PriceSource::LastTrade, Direction::Short),
+            Some(Num::from(103))
+        );
+        assert_eq!(
+            snapshot.get_price(PriceSource::BarClose, Direction::Long),
+            Some(Num::from(99))
+        );
+    }
 }
@@ -1,74 +1,216 @@
+use anyhow::bail;
 use anyhow::Result;
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use tokio::time::sleep;
+use tokio::time::timeout;
+use tokio::time::Duration;
 use tokio_util::sync::CancellationToken;
 use tracing::error;
 use tracing::info;
+use tracing::warn;
 
 mod data;
 mod engine;
 mod external_process;
+mod market_session;
 mod mktdata;
 mod order_handler;
 mod technical_signals;
 mod web_clients;
 
+use super::events::AdminCommand;
 use super::events::MktSignal;
 use super::Event;
 use crate::Settings;
 use engine::Engine;
 use external_process::ExternalProcess;
 
+pub use data::execution::ExecutionReports;
+pub use data::execution::ExecutionStats;
+pub use data::execution::ReportFilter;
+pub use mktdata::PriceSource;
+
+const DEFAULT_DRAIN_SECS: u64 = 10;
+
+pub struct Credentials {
+    pub key: String,
+    pub secret: String,
+}
+
 pub struct Platform {
-    engine: Arc<Mutex<Engine>>,
+    engines: HashMap<String, Arc<Mutex<Engine>>>,
+    routes: HashMap<String, String>,
+    adoption_account: Option<String>,
+    drain_secs: u64,
     shutdown_signal: CancellationToken,
 }
 
 impl Platform {
     pub async fn new(
         settings: Settings,
-        key: &str,
-        secret: &str,
-        is_live: bool,
+        credentials: &HashMap<String, Credentials>,
         shutdown_signal: CancellationToken,
     ) -> Result<Self> {
         if let Some(launch_process) = &settings.launch_process {
             ExternalProcess::launch_cloud_proxy(launch_process)?;
         };
-        let engine = Engine::new(
-            settings.clone(),
-            key,
-            secret,
-            is_live,
-            shutdown_signal.clone(),
-        )
-        .await?;
+        let accounts = settings.get_accounts();
+        let mut routes = HashMap::new();
+        for strategy in settings.strategies.keys() {
+            match settings.get_account_for_strategy(strategy) {
+                Some(account) if accounts.contains_key(&account) => {
+                    routes.insert(strategy.clone(), account);
+                }
+                Some(account) => bail!(
+                    "Strategy: {} mapped to unknown account: {}",
+                    strategy,
+                    account
+                ),
+                None => bail!("Strategy: {} is not mapped to an account", strategy),
+            }
+        }
+
+        let mut engines = HashMap::new();
+        for (name, account) in &accounts {
+            let Some(credentials) = credentials.get(name) else {
+                bail!("No credentials found for account: {}", name)
+            };
+            let engine = Engine::new(
+                settings.for_account(name, account),
+                &credentials.key,
+                &credentials.secret,
+                account.is_live,
+                shutdown_signal.clone(),
+            )
+            .await?;
+            info!(
+                "Initialised engine for account: {}, live: {}",
+                name, account.is_live
+            );
+            engines.insert(name.clone(), engine);
+        }
+        let adoption_account = settings
+            .adoption
+            .as_ref()
+            .and_then(|config| routes.get(&config.strategy).cloned());
+        let drain_secs = settings
+            .shutdown
+            .as_ref()
+            .and_then(|config| config.drain_secs)
+            .unwrap_or(DEFAULT_DRAIN_SECS);
 
         info!("Initialised platform components");
         Ok(Platform {
-            engine,
+            engines,
+            routes,
+            adoption_account,
+            drain_secs,
             shutdown_signal,
         })
     }
 
     pub async fn startup(&self) -> Result<()> {
-        let result = self.engine.lock().await.startup().await;
+        for (name, engine) in &self.engines {
+            if let Err(err) = engine.lock().await.startup().await {
+                bail!("Startup failed for account: {}, error={}", name, err)
+            }
+        }
         info!("Startup completed in the platform");
-        result
+        Ok(())
     }
 
     pub async fn run(&mut self) -> Result<()> {
-        let engine = Arc::clone(&self.engine);
-        Engine::run(engine, self.shutdown_signal.clone()).await
+        for engine in self.engines.values() {
+            Engine::run(Arc::clone(engine), self.shutdown_signal.clone()).await?;
+        }
+        Ok(())
     }
 
     pub async fn create_position(&mut self, mkt_signal: &MktSignal) -> Result<()> {
-        self.engine.lock().await.create_position(mkt_signal).await
+        let Some(engine) = self
+            .routes
+            .get(&mkt_signal.strategy)
+            .and_then(|account| self.engines.get(account))
+        else {
+            bail!(
+                "No account configured for strategy: {}",
+                mkt_signal.strategy
+            )
+        };
+        engine.lock().await.create_position(mkt_signal).await
+    }
+
+    pub async fn admin_command(&mut self, command: &AdminCommand) -> Result<()> {
+        if let AdminCommand::Adopt { .. } = command {
+            let Some(engine) = self
+                .adoption_account
+                .as_ref()
+                .and_then(|account| self.engines.get(account))
+            else {
+                bail!("Adoption requested but no adoption settings configured")
+            };
+            return engine.lock().await.admin_command(command).await;
+        }
+
+        let mut errors = Vec::new();
+        for (name, engine) in &self.engines {
+            if let Err(err) = engine.lock().await.admin_command(command).await {
+                errors.push(format!("account: {}, error={}", name, err));
+            }
+        }
+        if !errors.is_empty() {
+            bail!("Admin command failed, {}", errors.join("; "))
+        }
+        Ok(())
+    }
+
+    pub async fn shutdown(&self) {
+        let mut pending = Vec::new();
+        for (name, engine) in &self.engines {
+            let order_ids = engine.lock().await.shutdown().await;
+            info!(
+                "Account: {} waiting on {} orders before exit",
+                name,
+                order_ids.len()
+            );
+            pending.push((engine, order_ids));
+        }
+
+        let drain = async {
+            for (engine, order_ids) in &pending {
+                while !engine.lock().await.is_drained(order_ids).await {
+                    sleep(Duration::from_millis(500)).await;
+                }
+            }
+        };
+        if timeout(Duration::from_secs(self.drain_secs), drain)
+            .await
+            .is_err()
+        {
+            warn!(
+                "Shutdown drain timed out after {}s, orders may still be working",
+                self.drain_secs
+            );
+        }
+
+        for (name, engine) in &self.engines {
+            if let Err(err) = engine.lock().await.flush().await {
+                error!("Failed to flush state for account: {}, error={}", name, err);
+            }
+        }
+        info!("Shutdown completed in the platform");
     }
 
     pub async fn print_status(&self) {
-        if let Err(err) = self.engine.lock().await.update_status().await {
-            error!("Print status failed to complete, error={}", err);
+        for (name, engine) in &self.engines {
+            if let Err(err) = engine.lock().await.update_status().await {
+                error!(
+                    "Print status failed to complete for account: {}, error={}",
+                    name, err
+                );
+            }
         }
     }
 }
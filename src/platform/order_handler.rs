@@ -3,70 +3,833 @@ use anyhow::Ok;
 use anyhow::Result;
 use apca::api::v2::asset;
 use apca::api::v2::order;
+use chrono::DateTime;
+use chrono::Utc;
 use num_decimal::Num;
+use std::collections::HashMap;
+use std::str::FromStr;
 use std::sync::Arc;
 use tracing::info;
+use tracing::warn;
 use uuid::Uuid;
 
+use super::super::events::Direction;
 use super::super::events::Side;
+use super::web_clients::BrokerError;
 use super::web_clients::Connectors;
-use crate::to_num;
+use super::web_clients::ErrorClass;
+use crate::settings::EntryOrderConfig;
+use crate::settings::ExitOrderConfig;
+use crate::Settings;
+
+const DEFAULT_STOP_OFFSET_BPS: f64 = 100.0;
+const DEFAULT_LIMIT_OFFSET_BPS: f64 = 700.0;
+const DEFAULT_EXIT_OFFSET_BPS: f64 = 5.0;
+const DEFAULT_REPRICE_SECS: u64 = 10;
+const DEFAULT_MAX_REPRICES: u32 = 2;
+const FRACTIONAL_PRECISION: i64 = 10_000;
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum EntryType {
+    Market,
+    Limit,
+    Stop,
+    #[default]
+    StopLimit,
+}
+
+impl FromStr for EntryType {
+    type Err = String;
+
+    fn from_str(val: &str) -> std::result::Result<Self, Self::Err> {
+        match val.to_lowercase().as_str() {
+            "market" => std::result::Result::Ok(EntryType::Market),
+            "limit" => std::result::Result::Ok(EntryType::Limit),
+            "stop" => std::result::Result::Ok(EntryType::Stop),
+            "stop_limit" => std::result::Result::Ok(EntryType::StopLimit),
+            _ => Err(format!(
+                "Failed to parse entry order type, unknown: {}",
+                val
+            )),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum EntryClass {
+    #[default]
+    Simple,
+    Bracket,
+    Oco,
+}
+
+impl FromStr for EntryClass {
+    type Err = String;
+
+    fn from_str(val: &str) -> std::result::Result<Self, Self::Err> {
+        match val.to_lowercase().as_str() {
+            "simple" => std::result::Result::Ok(EntryClass::Simple),
+            "bracket" => std::result::Result::Ok(EntryClass::Bracket),
+            "oco" => std::result::Result::Ok(EntryClass::Oco),
+            _ => Err(format!(
+                "Failed to parse entry order class, unknown: {}",
+                val
+            )),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum EntryAmount {
+    #[default]
+    Shares,
+    Fractional,
+    Notional,
+}
+
+impl FromStr for EntryAmount {
+    type Err = String;
+
+    fn from_str(val: &str) -> std::result::Result<Self, Self::Err> {
+        match val.to_lowercase().as_str() {
+            "shares" => std::result::Result::Ok(EntryAmount::Shares),
+            "fractional" => std::result::Result::Ok(EntryAmount::Fractional),
+            "notional" => std::result::Result::Ok(EntryAmount::Notional),
+            _ => Err(format!(
+                "Failed to parse entry order amount, unknown: {}",
+                val
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExitLegs {
+    pub stop_loss: Option<Num>,
+    pub take_profit: Option<Num>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EntrySpec {
+    entry_type: EntryType,
+    entry_class: EntryClass,
+    entry_amount: EntryAmount,
+    take_profit_r: Option<f64>,
+    stop_offset_bps: f64,
+    limit_offset_bps: f64,
+    time_in_force: order::TimeInForce,
+    extended_hours: bool,
+}
+
+impl Default for EntrySpec {
+    fn default() -> Self {
+        EntrySpec {
+            entry_type: EntryType::StopLimit,
+            entry_class: EntryClass::Simple,
+            entry_amount: EntryAmount::Shares,
+            take_profit_r: None,
+            stop_offset_bps: DEFAULT_STOP_OFFSET_BPS,
+            limit_offset_bps: DEFAULT_LIMIT_OFFSET_BPS,
+            time_in_force: order::TimeInForce::Day,
+            extended_hours: false,
+        }
+    }
+}
+
+impl EntrySpec {
+    pub fn new(config: &EntryOrderConfig) -> Result<Self> {
+        let entry_type = match EntryType::from_str(&config.order_type) {
+            std::result::Result::Ok(entry_type) => entry_type,
+            Err(err) => bail!("{}", err),
+        };
+        let time_in_force = match config.time_in_force.as_deref() {
+            Some(val) => Self::parse_time_in_force(val)?,
+            None => order::TimeInForce::Day,
+        };
+        if config.extended_hours
+            && (entry_type != EntryType::Limit || time_in_force != order::TimeInForce::Day)
+        {
+            bail!("Extended hours entries must be day limit orders")
+        }
+        let entry_class = match config.order_class.as_deref().map(EntryClass::from_str) {
+            Some(std::result::Result::Ok(entry_class)) => entry_class,
+            Some(Err(err)) => bail!("{}", err),
+            None => EntryClass::Simple,
+        };
+        if entry_class == EntryClass::Bracket
+            && (config.extended_hours
+                || !matches!(
+                    time_in_force,
+                    order::TimeInForce::Day | order::TimeInForce::UntilCanceled
+                ))
+        {
+            bail!("Bracket entries must be regular hours day or gtc orders")
+        }
+        if config.take_profit_r.is_some_and(|r| r <= 0.0) {
+            bail!("Take profit R-multiple must be positive")
+        }
+        let entry_amount = match config.amount.as_deref().map(EntryAmount::from_str) {
+            Some(std::result::Result::Ok(entry_amount)) => entry_amount,
+            Some(Err(err)) => bail!("{}", err),
+            None => EntryAmount::Shares,
+        };
+        if entry_amount != EntryAmount::Shares
+            && (entry_class != EntryClass::Simple || time_in_force != order::TimeInForce::Day)
+        {
+            bail!("Fractional and notional entries must be simple day orders")
+        }
+        if entry_amount == EntryAmount::Notional && entry_type != EntryType::Market {
+            bail!("Notional entries must be market orders")
+        }
+        Ok(EntrySpec {
+            entry_type,
+            entry_class,
+            entry_amount,
+            take_profit_r: config.take_profit_r,
+            stop_offset_bps: config.stop_offset_bps.unwrap_or(DEFAULT_STOP_OFFSET_BPS),
+            limit_offset_bps: config.limit_offset_bps.unwrap_or(match entry_type {
+                EntryType::Limit => 0.0,
+                _ => DEFAULT_LIMIT_OFFSET_BPS,
+            }),
+            time_in_force,
+            extended_hours: config.extended_hours,
+        })
+    }
+
+    fn parse_time_in_force(val: &str) -> Result<order::TimeInForce> {
+        match val.to_lowercase().as_str() {
+            "day" => Ok(order::TimeInForce::Day),
+            "gtc" => Ok(order::TimeInForce::UntilCanceled),
+            "ioc" => Ok(order::TimeInForce::ImmediateOrCancel),
+            "fok" => Ok(order::TimeInForce::FillOrKill),
+            "opg" => Ok(order::TimeInForce::UntilMarketOpen),
+            "cls" => Ok(order::TimeInForce::UntilMarketClose),
+            _ => bail!("Failed to parse time in force, unknown: {}", val),
+        }
+    }
+
+    fn offset(price: &Num, bps: f64, side: Side) -> Num {
+        let offset = Num::new((bps * 100.0).round() as i64, 1_000_000);
+        match side {
+            Side::Buy => price.clone() * (Num::from(1) + offset),
+            Side::Sell => price.clone() * (Num::from(1) - offset),
+        }
+    }
+
+    pub fn get_prices(&self, target_price: &Num, side: Side) -> (Option<Num>, Option<Num>) {
+        let limit_price = Self::offset(target_price, self.limit_offset_bps, side).round_with(2);
+        let stop_price = Self::offset(target_price, self.stop_offset_bps, side).round_with(2);
+        match self.entry_type {
+            EntryType::Market => (None, None),
+            EntryType::Limit => (Some(limit_price), None),
+            EntryType::Stop => (None, Some(stop_price)),
+            EntryType::StopLimit => (Some(limit_price), Some(stop_price)),
+        }
+    }
+
+    pub fn get_amount(
+        &self,
+        position_size: &Num,
+        target_price: &Num,
+        fractionable: bool,
+    ) -> Option<order::Amount> {
+        let entry_amount = match fractionable {
+            true => self.entry_amount,
+            false => EntryAmount::Shares,
+        };
+        match entry_amount {
+            EntryAmount::Shares => Some(position_size.round())
+                .filter(|quantity| quantity.is_positive())
+                .map(order::Amount::quantity),
+            EntryAmount::Fractional => {
+                Some((position_size.clone() * FRACTIONAL_PRECISION).trunc() / FRACTIONAL_PRECISION)
+                    .filter(|quantity| quantity.is_positive())
+                    .map(order::Amount::quantity)
+            }
+            EntryAmount::Notional => {
+                Some((position_size.clone() * target_price.clone()).round_with(2))
+                    .filter(|notional| notional.is_positive())
+                    .map(order::Amount::notional)
+            }
+        }
+    }
+
+    pub fn get_take_profit(
+        &self,
+        entry_price: &Num,
+        stop_price: Option<&Num>,
+        target: Option<&Num>,
+        side: Side,
+    ) -> Option<Num> {
+        if let Some(target) = target {
+            return Some(target.round_with(2));
+        }
+        let multiple = Num::new((self.take_profit_r? * 100.0).round() as i64, 100);
+        let stop_price = stop_price?;
+        let risk = match side {
+            Side::Buy => entry_price.clone() - stop_price.clone(),
+            Side::Sell => stop_price.clone() - entry_price.clone(),
+        };
+        if !risk.is_positive() {
+            return None;
+        }
+        let reward = risk * multiple;
+        let take_profit = match side {
+            Side::Buy => entry_price.clone() + reward,
+            Side::Sell => entry_price.clone() - reward,
+        };
+        Some(take_profit.round_with(2))
+    }
+
+    fn order_type(&self) -> order::Type {
+        match self.entry_type {
+            EntryType::Market => order::Type::Market,
+            EntryType::Limit => order::Type::Limit,
+            EntryType::Stop => order::Type::Stop,
+            EntryType::StopLimit => order::Type::StopLimit,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum ExitPolicy {
+    #[default]
+    Market,
+    Escalate,
+}
+
+impl FromStr for ExitPolicy {
+    type Err = String;
+
+    fn from_str(val: &str) -> std::result::Result<Self, Self::Err> {
+        match val.to_lowercase().as_str() {
+            "market" => std::result::Result::Ok(ExitPolicy::Market),
+            "escalate" => std::result::Result::Ok(ExitPolicy::Escalate),
+            _ => Err(format!("Failed to parse exit policy, unknown: {}", val)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExitSpec {
+    policy: ExitPolicy,
+    offset_bps: f64,
+    reprice_secs: u64,
+    max_reprices: u32,
+}
+
+impl ExitSpec {
+    pub fn new(config: &ExitOrderConfig) -> Result<Self> {
+        let policy = match ExitPolicy::from_str(&config.policy) {
+            std::result::Result::Ok(policy) => policy,
+            Err(err) => bail!("{}", err),
+        };
+        let offset_bps = config.offset_bps.unwrap_or(DEFAULT_EXIT_OFFSET_BPS);
+        if offset_bps < 0.0 {
+            bail!("Exit offset must not be negative")
+        }
+        Ok(ExitSpec {
+            policy,
+            offset_bps,
+            reprice_secs: config.reprice_secs.unwrap_or(DEFAULT_REPRICE_SECS),
+            max_reprices: config.max_reprices.unwrap_or(DEFAULT_MAX_REPRICES),
+        })
+    }
+
+    pub fn get_limit_price(&self, direction: Direction, quote: &(Num, Num)) -> Num {
+        let (bid, ask) = quote;
+        let offset = Num::new((self.offset_bps * 100.0).round() as i64, 1_000_000);
+        let price = match direction {
+            Direction::Long => bid.clone() * (Num::from(1) - offset),
+            Direction::Short => ask.clone() * (Num::from(1) + offset),
+        };
+        price.round_with(2)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Escalation {
+    client_order_id: String,
+    strategy: String,
+    symbol: String,
+    direction: Direction,
+    reprices: u32,
+    placed_at: DateTime<Utc>,
+    awaiting_cancel: bool,
+}
 
 pub struct OrderHandler {
     connectors: Arc<Connectors>,
+    entry_specs: HashMap<String, EntrySpec>,
+    exit_specs: HashMap<String, ExitSpec>,
+    exit_targets: HashMap<(String, String), Num>,
+    escalations: HashMap<Uuid, Escalation>,
 }
 
 impl OrderHandler {
-    pub fn new(connectors: &Arc<Connectors>) -> Self {
-        OrderHandler {
+    pub fn new(connectors: &Arc<Connectors>, settings: &Settings) -> Result<Self> {
+        let mut entry_specs = HashMap::new();
+        let mut exit_specs = HashMap::new();
+        for (strategy, config) in &settings.strategies {
+            let exit_spec = match &config.exit {
+                Some(exit) => match ExitSpec::new(exit) {
+                    std::result::Result::Ok(spec) => spec,
+                    Err(err) => bail!("Strategy[{}] invalid exit order, {}", strategy, err),
+                },
+                None => ExitSpec::default(),
+            };
+            exit_specs.insert(strategy.clone(), exit_spec);
+            let spec = match &config.entry {
+                Some(entry) => match EntrySpec::new(entry) {
+                    std::result::Result::Ok(spec) => spec,
+                    Err(err) => bail!("Strategy[{}] invalid entry order, {}", strategy, err),
+                },
+                None => EntrySpec::default(),
+            };
+            entry_specs.insert(strategy.clone(), spec);
+        }
+        Ok(OrderHandler {
             connectors: Arc::clone(connectors),
+            entry_specs,
+            exit_specs,
+            exit_targets: HashMap::new(),
+            escalations: HashMap::new(),
+        })
+    }
+
+    pub fn get_entry_amount(
+        &self,
+        strategy: &str,
+        symbol: &str,
+        position_size: &Num,
+        target_price: &Num,
+        fractionable: bool,
+    ) -> Result<order::Amount> {
+        let spec = self.entry_specs.get(strategy).cloned().unwrap_or_default();
+        match spec.get_amount(position_size, target_price, fractionable) {
+            Some(amount) => Ok(amount),
+            None => bail!(
+                "Position size: {} for symbol: {} rounds to zero, fractionable: {}",
+                position_size,
+                symbol,
+                fractionable
+            ),
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_position(
         &mut self,
+        strategy: &str,
         symbol: &str,
         target_price: Num,
-        position_size: Num,
+        amount: order::Amount,
         side: Side,
-    ) -> Result<Uuid> {
-        let limit_price = match side {
-            Side::Buy => target_price.clone() * to_num!(1.07),
-            Side::Sell => target_price.clone() * to_num!(0.93),
-        };
-        let stop_price = match side {
-            Side::Buy => target_price * to_num!(1.01),
-            Side::Sell => target_price * to_num!(0.99),
+        exits: ExitLegs,
+        client_order_id: &str,
+    ) -> Result<order::Order> {
+        let spec = self.entry_specs.get(strategy).cloned().unwrap_or_default();
+        let (limit_price, stop_price) = spec.get_prices(&target_price, side);
+        let take_profit = spec.get_take_profit(
+            &target_price,
+            exits.stop_loss.as_ref(),
+            exits.take_profit.as_ref(),
+            side,
+        );
+        let (class, take_profit, stop_loss) = match spec.entry_class {
+            EntryClass::Simple => (order::Class::Simple, None, None),
+            EntryClass::Oco => {
+                let key = (strategy.to_string(), symbol.to_string());
+                match take_profit {
+                    Some(take_profit) => self.exit_targets.insert(key, take_profit),
+                    None => self.exit_targets.remove(&key),
+                };
+                (order::Class::Simple, None, None)
+            }
+            EntryClass::Bracket => {
+                let Some(stop_loss) = exits.stop_loss.map(|price| price.round_with(2)) else {
+                    bail!("Bracket entry for symbol {symbol} requires an initial stop price")
+                };
+                match take_profit {
+                    Some(take_profit) => (
+                        order::Class::Bracket,
+                        Some(order::TakeProfit::Limit(take_profit)),
+                        Some(order::StopLoss::Stop(stop_loss)),
+                    ),
+                    None => (
+                        order::Class::OneTriggersOther,
+                        None,
+                        Some(order::StopLoss::Stop(stop_loss)),
+                    ),
+                }
+            }
         };
 
-        let amount = order::Amount::quantity(position_size.round());
         let side = Self::convert_side(side);
         info!(
-            "Placing order for fields limit_price: {}, stop_price: {}, amount: {:?}, side: {:?}",
+            "Placing {:?} {:?} order for fields limit_price: {:?}, stop_price: {:?}, amount: {:?}, side: {:?}, time_in_force: {:?}, take_profit: {:?}, stop_loss: {:?}",
+            spec.entry_class,
+            spec.entry_type,
             limit_price,
             stop_price,
-            position_size.to_i64(),
-            side
+            amount,
+            side,
+            spec.time_in_force,
+            take_profit,
+            stop_loss
         );
 
         let request = order::OrderReqInit {
-            type_: order::Type::StopLimit,
-            limit_price: Some(limit_price.round_with(2)),
-            stop_price: Some(stop_price.round_with(2)),
+            class,
+            type_: spec.order_type(),
+            time_in_force: spec.time_in_force,
+            limit_price,
+            stop_price,
+            take_profit,
+            stop_loss,
+            extended_hours: spec.extended_hours,
+            client_order_id: Some(client_order_id.to_string()),
             ..Default::default()
         }
         .init(symbol, side, amount);
         match self.connectors.place_order(&request).await {
-            Err(error) => bail!("Failed to place order for request: {request:?}, error: {error}"),
+            Err(error) => {
+                let message =
+                    format!("Failed to place order for request: {request:?}, error: {error}");
+                Err(error.context(message))
+            }
+            std::result::Result::Ok(order) => Ok(order),
+        }
+    }
+
+    pub fn is_fractional_entry(&self, strategy: &str, fractionable: bool) -> bool {
+        fractionable
+            && self
+                .entry_specs
+                .get(strategy)
+                .is_some_and(|spec| spec.entry_amount != EntryAmount::Shares)
+    }
+
+    pub fn has_exit_oco(&self, strategy: &str) -> bool {
+        self.entry_specs
+            .get(strategy)
+            .is_some_and(|spec| spec.entry_class == EntryClass::Oco)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn place_exit_oco(
+        &mut self,
+        strategy: &str,
+        symbol: &str,
+        quantity: &Num,
+        direction: Direction,
+        entry_price: &Num,
+        stop_price: &Num,
+        client_order_id: &str,
+    ) -> Result<order::Order> {
+        let spec = self.entry_specs.get(strategy).cloned().unwrap_or_default();
+        if !quantity.fract().is_zero() {
+            bail!("Oco exit for symbol {symbol} requires whole shares, quantity: {quantity}")
+        }
+        let (entry_side, side) = match direction {
+            Direction::Long => (Side::Buy, order::Side::Sell),
+            Direction::Short => (Side::Sell, order::Side::Buy),
+        };
+        let target = self
+            .exit_targets
+            .remove(&(strategy.to_string(), symbol.to_string()));
+        let Some(take_profit) =
+            spec.get_take_profit(entry_price, Some(stop_price), target.as_ref(), entry_side)
+        else {
+            bail!("No take profit target for oco exit on symbol {symbol}")
+        };
+        let stop_price = stop_price.round_with(2);
+        info!(
+            "Placing oco exit symbol: {}, quantity: {}, side: {:?}, take_profit: {}, stop_loss: {}",
+            symbol, quantity, side, take_profit, stop_price
+        );
+        let request = order::OrderReqInit {
+            class: order::Class::OneCancelsOther,
+            type_: order::Type::Limit,
+            time_in_force: order::TimeInForce::UntilCanceled,
+            take_profit: Some(order::TakeProfit::Limit(take_profit)),
+            stop_loss: Some(order::StopLoss::Stop(stop_price)),
+            client_order_id: Some(client_order_id.to_string()),
+            ..Default::default()
+        }
+        .init(symbol, side, order::Amount::quantity(quantity.clone()));
+        match self.connectors.place_order(&request).await {
+            Err(error) => {
+                let message =
+                    format!("Failed to place oco exit for symbol {symbol}, error={error}");
+                Err(error.context(message))
+            }
+            std::result::Result::Ok(order) => Ok(order),
+        }
+    }
+
+    pub async fn liquidate_position(
+        &mut self,
+        strategy: &str,
+        symbol: &str,
+        quantity: &Num,
+        direction: Direction,
+        quote: Option<(Num, Num)>,
+        client_order_id: &str,
+    ) -> Result<Uuid> {
+        let spec = self.exit_specs.get(strategy).cloned().unwrap_or_default();
+        let quote = match (spec.policy, quote) {
+            (ExitPolicy::Escalate, Some(quote)) => quote,
+            (ExitPolicy::Escalate, None) => {
+                warn!("No quote for symbol: {symbol}, liquidating at market");
+                return self
+                    .liquidate_at_market(symbol, quantity, direction, client_order_id)
+                    .await;
+            }
+            (ExitPolicy::Market, _) => {
+                return self
+                    .liquidate_at_market(symbol, quantity, direction, client_order_id)
+                    .await
+            }
+        };
+        let side = match direction {
+            Direction::Long => order::Side::Sell,
+            Direction::Short => order::Side::Buy,
+        };
+        let limit_price = spec.get_limit_price(direction, &quote);
+        let extended_hours = self
+            .entry_specs
+            .get(strategy)
+            .is_some_and(|entry| entry.extended_hours);
+        info!(
+            "Liquidating symbol: {}, quantity: {}, side: {:?}, limit_price: {}",
+            symbol, quantity, side, limit_price
+        );
+        let request = order::OrderReqInit {
+            type_: order::Type::Limit,
+            time_in_force: order::TimeInForce::Day,
+            limit_price: Some(limit_price),
+            extended_hours,
+            client_order_id: Some(client_order_id.to_string()),
+            ..Default::default()
+        }
+        .init(symbol, side, order::Amount::quantity(quantity.clone()));
+        let order_id = match self.connectors.place_order(&request).await {
+            Err(error) => {
+                let message =
+                    format!("Failed to liquidate position for symbol {symbol}, error={error}");
+                return Err(error.context(message));
+            }
+            std::result::Result::Ok(order) => order.id.0,
+        };
+        self.escalations.insert(
+            order_id,
+            Escalation {
+                client_order_id: client_order_id.to_string(),
+                strategy: strategy.to_string(),
+                symbol: symbol.to_string(),
+                direction,
+                reprices: 0,
+                placed_at: Utc::now(),
+                awaiting_cancel: false,
+            },
+        );
+        Ok(order_id)
+    }
+
+    pub fn get_due_escalations(&self, now: DateTime<Utc>) -> Vec<(Uuid, String)> {
+        self.escalations
+            .iter()
+            .filter(|(_, escalation)| {
+                let reprice_secs = self
+                    .exit_specs
+                    .get(&escalation.strategy)
+                    .map(|spec| spec.reprice_secs)
+                    .unwrap_or(DEFAULT_REPRICE_SECS);
+                !escalation.awaiting_cancel
+                    && (now - escalation.placed_at).num_seconds() >= reprice_secs as i64
+            })
+            .map(|(order_id, escalation)| (*order_id, escalation.symbol.clone()))
+            .collect()
+    }
+
+    // Re-prices the working limit, or cancels it once re-pricing is exhausted so the
+    // remainder can go out at market when the cancel is confirmed
+    pub async fn escalate(
+        &mut self,
+        order_id: &Uuid,
+        quote: Option<(Num, Num)>,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Uuid>> {
+        let Some(mut escalation) = self.escalations.remove(order_id) else {
+            return Ok(None);
+        };
+        let spec = self
+            .exit_specs
+            .get(&escalation.strategy)
+            .cloned()
+            .unwrap_or_default();
+        if let Some(quote) = quote.filter(|_| escalation.reprices < spec.max_reprices) {
+            let limit_price = spec.get_limit_price(escalation.direction, &quote);
+            info!(
+                "Re-pricing liquidation order: {} for symbol: {} to {}",
+                order_id, escalation.symbol, limit_price
+            );
+            // Re-prices keep the leg's client id with a suffix, reports group them as one leg
+            let request = order::ChangeReqInit {
+                limit_price: Some(limit_price),
+                client_order_id: Some(format!(
+                    "{}-r{}",
+                    escalation.client_order_id,
+                    escalation.reprices + 1
+                )),
+                ..Default::default()
+            }
+            .init();
+            let new_order_id = match self
+                .connectors
+                .replace_order(&order::Id(*order_id), &request)
+                .await
+            {
+                Err(error) => {
+                    // A rejected re-price will not succeed on the next tick either
+                    if BrokerError::class_of(&error) != Some(ErrorClass::Terminal) {
+                        self.escalations.insert(*order_id, escalation);
+                    }
+                    let message = format!(
+                        "Failed to re-price liquidation for id {}, error={}",
+                        order_id, error
+                    );
+                    return Err(error.context(message));
+                }
+                std::result::Result::Ok(order) => order.id.0,
+            };
+            escalation.reprices += 1;
+            escalation.placed_at = now;
+            self.escalations.insert(new_order_id, escalation);
+            return Ok(Some(new_order_id));
+        }
+        info!(
+            "Escalating liquidation order: {} for symbol: {} to market",
+            order_id, escalation.symbol
+        );
+        escalation.awaiting_cancel = true;
+        let result = self.cancel_order(order_id).await;
+        self.escalations.insert(*order_id, escalation);
+        result.map(|_| None)
+    }
+
+    pub fn take_market_escalation(&mut self, order_id: &Uuid) -> bool {
+        self.escalations
+            .remove(order_id)
+            .is_some_and(|escalation| escalation.awaiting_cancel)
+    }
+
+    pub async fn liquidate_at_market(
+        &self,
+        symbol: &str,
+        quantity: &Num,
+        direction: Direction,
+        client_order_id: &str,
+    ) -> Result<Uuid> {
+        let side = match direction {
+            Direction::Long => order::Side::Sell,
+            Direction::Short => order::Side::Buy,
+        };
+        let amount = order::Amount::quantity(quantity.clone());
+        info!(
+            "Liquidating symbol: {}, quantity: {}, side: {:?}",
+            symbol, quantity, side
+        );
+        let request = order::OrderReqInit {
+            type_: order::Type::Market,
+            client_order_id: Some(client_order_id.to_string()),
+            ..Default::default()
+        }
+        .init(symbol, side, amount);
+        match self.connectors.place_order(&request).await {
+            Err(error) => {
+                let message =
+                    format!("Failed to liquidate position for symbol {symbol}, error={error}");
+                Err(error.context(message))
+            }
+            std::result::Result::Ok(order) => Ok(order.id.0),
+        }
+    }
+
+    pub async fn place_protective_stop(
+        &self,
+        symbol: &str,
+        quantity: &Num,
+        direction: Direction,
+        stop_price: &Num,
+        client_order_id: &str,
+    ) -> Result<Uuid> {
+        let side = match direction {
+            Direction::Long => order::Side::Sell,
+            Direction::Short => order::Side::Buy,
+        };
+        let amount = order::Amount::quantity(quantity.clone());
+        // Fractional orders are only accepted as day orders
+        let time_in_force = match quantity.fract().is_zero() {
+            true => order::TimeInForce::UntilCanceled,
+            false => order::TimeInForce::Day,
+        };
+        info!(
+            "Placing protective stop symbol: {}, quantity: {}, side: {:?}, stop_price: {}",
+            symbol, quantity, side, stop_price
+        );
+        let request = order::OrderReqInit {
+            type_: order::Type::Stop,
+            time_in_force,
+            stop_price: Some(stop_price.clone()),
+            client_order_id: Some(client_order_id.to_string()),
+            ..Default::default()
+        }
+        .init(symbol, side, amount);
+        match self.connectors.place_order(&request).await {
+            Err(error) => {
+                let message =
+                    format!("Failed to place protective stop for symbol {symbol}, error={error}");
+                Err(error.context(message))
+            }
             std::result::Result::Ok(order) => Ok(order.id.0),
         }
     }
 
-    pub async fn liquidate_position(&self, symbol: &str) -> Result<Uuid> {
+    pub async fn replace_stop(&self, order_id: &Uuid, stop_price: &Num) -> Result<Uuid> {
+        info!(
+            "Replacing protective stop order: {}, stop_price: {}",
+            order_id, stop_price
+        );
+        let request = order::ChangeReqInit {
+            stop_price: Some(stop_price.clone()),
+            ..Default::default()
+        }
+        .init();
+        match self
+            .connectors
+            .replace_order(&order::Id(*order_id), &request)
+            .await
+        {
+            Err(error) => {
+                let message = format!(
+                    "Failed to replace protective stop for id {}, error={}",
+                    order_id, error
+                );
+                Err(error.context(message))
+            }
+            std::result::Result::Ok(order) => Ok(order.id.0),
+        }
+    }
+
+    pub async fn close_position(&self, symbol: &str) -> Result<Uuid> {
         let symbol = asset::Symbol::Sym(symbol.to_string());
         match self.connectors.close_position(&symbol).await {
             Err(error) => {
-                bail!("Failed to liquidate position for symbol {symbol}, error={error}")
+                let message =
+                    format!("Failed to liquidate position for symbol {symbol}, error={error}");
+                Err(error.context(message))
             }
             std::result::Result::Ok(order) => Ok(order.id.0),
         }
@@ -90,3 +853,133 @@ impl OrderHandler {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_spec_prices() -> Result<()> {
+        let price = Num::from(100);
+        let (limit, stop) = EntrySpec::default().get_prices(&price, Side::Buy);
+        assert_eq!(limit, Some(Num::from(107)));
+        assert_eq!(stop, Some(Num::from(101)));
+
+        let spec = EntrySpec::new(&EntryOrderConfig {
+            order_type: "limit".to_string(),
+            limit_offset_bps: Some(25.0),
+            ..Default::default()
+        })?;
+        let (limit, stop) = spec.get_prices(&price, Side::Sell);
+        assert_eq!(limit, Some(Num::new(9975, 100)));
+        assert_eq!(stop, None);
+
+        let spec = EntrySpec::new(&EntryOrderConfig {
+            order_type: "stop".to_string(),
+            extended_hours: true,
+            ..Default::default()
+        });
+        assert!(spec.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_spec_take_profit() -> Result<()> {
+        let spec = EntrySpec::new(&EntryOrderConfig {
+            order_type: "limit".to_string(),
+            order_class: Some("bracket".to_string()),
+            take_profit_r: Some(2.0),
+            ..Default::default()
+        })?;
+        let entry = Num::from(100);
+        let stop = Num::from(95);
+        assert_eq!(
+            spec.get_take_profit(&entry, Some(&stop), None, Side::Buy),
+            Some(Num::from(110))
+        );
+        assert_eq!(
+            spec.get_take_profit(&entry, Some(&Num::from(105)), None, Side::Sell),
+            Some(Num::from(90))
+        );
+        assert_eq!(
+            spec.get_take_profit(&entry, Some(&stop), Some(&Num::from(120)), Side::Buy),
+            Some(Num::from(120))
+        );
+        assert_eq!(
+            spec.get_take_profit(&entry, Some(&Num::from(105)), None, Side::Buy),
+            None
+        );
+
+        let spec = EntrySpec::new(&EntryOrderConfig {
+            order_type: "limit".to_string(),
+            order_class: Some("bracket".to_string()),
+            time_in_force: Some("ioc".to_string()),
+            ..Default::default()
+        });
+        assert!(spec.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_spec_amount() -> Result<()> {
+        let size = Num::new(12345678, 10_000_000);
+        let price = Num::from(400);
+        let spec = EntrySpec::new(&EntryOrderConfig {
+            order_type: "limit".to_string(),
+            amount: Some("fractional".to_string()),
+            ..Default::default()
+        })?;
+        assert_eq!(
+            spec.get_amount(&size, &price, true),
+            Some(order::Amount::quantity(Num::new(12345, 10_000)))
+        );
+        assert_eq!(
+            spec.get_amount(&size, &price, false),
+            Some(order::Amount::quantity(Num::from(1)))
+        );
+        assert_eq!(spec.get_amount(&Num::new(4, 10), &price, false), None);
+
+        let spec = EntrySpec::new(&EntryOrderConfig {
+            order_type: "market".to_string(),
+            amount: Some("notional".to_string()),
+            ..Default::default()
+        })?;
+        assert_eq!(
+            spec.get_amount(&size, &price, true),
+            Some(order::Amount::notional(Num::new(49383, 100)))
+        );
+
+        let spec = EntrySpec::new(&EntryOrderConfig {
+            order_type: "limit".to_string(),
+            order_class: Some("bracket".to_string()),
+            amount: Some("fractional".to_string()),
+            ..Default::default()
+        });
+        assert!(spec.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_exit_spec_limit_price() -> Result<()> {
+        let spec = ExitSpec::new(&ExitOrderConfig {
+            policy: "escalate".to_string(),
+            offset_bps: Some(10.0),
+            ..Default::default()
+        })?;
+        let quote = (Num::from(100), Num::new(10010, 100));
+        assert_eq!(
+            spec.get_limit_price(Direction::Long, &quote),
+            Num::new(999, 10)
+        );
+        assert_eq!(
+            spec.get_limit_price(Direction::Short, &quote),
+            Num::new(10020, 100)
+        );
+        assert!(ExitSpec::new(&ExitOrderConfig {
+            policy: "twap".to_string(),
+            ..Default::default()
+        })
+        .is_err());
+        Ok(())
+    }
+}
@@ -1,47 +1,129 @@
-use anyhow::bail;
-use anyhow::Result;
 use apca::Client;
+use apca::RequestError;
 use http_endpoint::Endpoint;
-use std::thread;
-use std::time::Duration;
+use tokio::time::sleep;
+use tokio::time::Duration;
 use tokio_util::sync::CancellationToken;
 use tracing::warn;
 
+use super::rate_limiter::Priority;
+use super::rate_limiter::RateLimitStats;
+use super::rate_limiter::RateLimiter;
+use super::retry_policy::jitter;
+use super::retry_policy::BrokerError;
+use super::retry_policy::EndpointError;
+use super::retry_policy::ErrorClass;
+use super::retry_policy::RetryPolicy;
+use crate::settings::RateLimitConfig;
+
 #[derive(Debug)]
 pub(crate) struct HttpClient {
     shutdown_signal: CancellationToken,
+    rate_limiter: RateLimiter,
 }
 
 impl HttpClient {
-    pub fn new(shutdown_signal: CancellationToken) -> Self {
-        HttpClient { shutdown_signal }
+    pub fn new(shutdown_signal: CancellationToken, rate_limit: Option<RateLimitConfig>) -> Self {
+        HttpClient {
+            shutdown_signal,
+            rate_limiter: RateLimiter::new(rate_limit),
+        }
+    }
+
+    pub fn get_rate_limit_stats(&self) -> RateLimitStats {
+        self.rate_limiter.get_stats()
     }
 
-    pub async fn send_request<E>(&self, client: &Client, input: &E::Input) -> Result<E::Output>
+    async fn issue<E>(
+        &self,
+        client: &Client,
+        input: &E::Input,
+        priority: Priority,
+    ) -> std::result::Result<E::Output, RequestError<E::Error>>
     where
         E: Endpoint,
+        E::Error: EndpointError,
     {
-        let mut retry = 5;
+        self.rate_limiter.acquire(priority).await;
+        let result = client.issue::<E>(input).await;
+        match &result {
+            Err(RequestError::Endpoint(err)) if err.is_rate_limited() => {
+                self.rate_limiter.on_rate_limited()
+            }
+            Err(_) => (),
+            Ok(_) => self.rate_limiter.on_success(),
+        }
+        result
+    }
+
+    pub async fn send_request<E>(
+        &self,
+        client: &Client,
+        input: &E::Input,
+        policy: RetryPolicy,
+    ) -> Result<E::Output, BrokerError>
+    where
+        E: Endpoint,
+        E::Error: EndpointError,
+    {
+        let mut attempt = 0;
         loop {
-            match client.issue::<E>(input).await {
-                Err(apca::RequestError::Endpoint(err)) => {
-                    warn!("Request failed, endpoint error: {err}");
-                }
-                Err(apca::RequestError::Hyper(err)) => {
-                    warn!("Request failed, hyper error: {err}");
-                }
-                Err(apca::RequestError::Io(err)) => {
-                    warn!("Request failed, io error: {err}");
-                }
+            attempt += 1;
+            let err = match self.issue::<E>(client, input, policy.priority).await {
                 Ok(payload) => return Ok(payload),
+                Err(err) => err,
+            };
+            let error = BrokerError::new(std::any::type_name::<E>(), &err, attempt);
+            let class = error.class;
+            if class == ErrorClass::Terminal || attempt >= policy.max_attempts {
+                return Err(error);
+            }
+            let wait = match class {
+                ErrorClass::RateLimited => Default::default(),
+                _ => policy.backoff(attempt, jitter()),
             };
-            if retry == 0 {
-                self.shutdown_signal.cancel();
-                bail!("No retry attempts left, exiting app")
+            warn!(
+                "Request failed, {} error={}, retrying in {:?}",
+                class, error.message, wait
+            );
+            if !self.backoff(wait).await {
+                return Err(error);
             }
-            retry -= 1;
-            warn!("Retry order posting retries left: {retry}");
-            thread::sleep(Duration::from_secs(1));
         }
     }
+
+    pub async fn backoff(&self, wait: Duration) -> bool {
+        tokio::select! {
+            _ = self.shutdown_signal.cancelled() => false,
+            _ = sleep(wait) => true,
+        }
+    }
+
+    // Requests which are not safe to repeat are sent once, the caller decides how to recover
+    pub async fn try_send_request<E>(
+        &self,
+        client: &Client,
+        input: &E::Input,
+        priority: Priority,
+    ) -> std::result::Result<E::Output, RequestError<E::Error>>
+    where
+        E: Endpoint,
+        E::Error: EndpointError,
+    {
+        self.issue::<E>(client, input, priority).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_backoff_ends_on_shutdown() {
+        let shutdown_signal = CancellationToken::new();
+        let http_client = HttpClient::new(shutdown_signal.clone(), None);
+        assert!(http_client.backoff(Duration::from_millis(1)).await);
+        shutdown_signal.cancel();
+        assert!(!http_client.backoff(Duration::from_secs(60)).await);
+    }
 }
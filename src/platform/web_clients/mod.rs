@@ -3,6 +3,8 @@ use anyhow::Result;
 use apca::api::v2::account;
 use apca::api::v2::asset;
 use apca::api::v2::assets;
+use apca::api::v2::calendar;
+use apca::api::v2::clock;
 use apca::api::v2::order;
 use apca::api::v2::order::Id;
 use apca::api::v2::orders;
@@ -16,14 +18,31 @@ use std::sync::Arc;
 use tokio::sync::broadcast;
 use tokio_util::sync::CancellationToken;
 use tracing::info;
+use tracing::warn;
 use url::Url;
 use uuid::Uuid;
 
 mod http_client;
+mod order_relations;
+mod rate_limiter;
+mod retry_policy;
+mod simulator;
 mod websocket;
 
 use super::Event;
+use crate::settings::RateLimitConfig;
+use crate::settings::ShadowConfig;
 use http_client::HttpClient;
+use order_relations::GetRelations;
+pub use order_relations::OrderRelations;
+use rate_limiter::Priority;
+pub use rate_limiter::RateLimitStats;
+use retry_policy::classify;
+use retry_policy::jitter;
+pub use retry_policy::BrokerError;
+pub use retry_policy::ErrorClass;
+use retry_policy::RetryPolicy;
+use simulator::Simulator;
 use websocket::WebSocket;
 
 #[derive(Debug)]
@@ -32,6 +51,7 @@ pub struct Connectors {
     publisher: broadcast::Sender<Event>,
     http_client: HttpClient,
     websocket: WebSocket,
+    simulator: Option<Simulator>,
 }
 
 impl Connectors {
@@ -39,6 +59,8 @@ impl Connectors {
         key: &str,
         secret: &str,
         is_live: bool,
+        shadow: Option<ShadowConfig>,
+        rate_limit: Option<RateLimitConfig>,
         shutdown_signal: CancellationToken,
     ) -> Result<Arc<Self>> {
         let api_base_url = match is_live {
@@ -49,18 +71,31 @@ impl Connectors {
         let api_info = ApiInfo::from_parts(api_base_url, key, secret)?;
         let client = Client::new(api_info);
         let (publisher, _subscriber) = broadcast::channel(150);
-        let http_client = HttpClient::new(shutdown_signal.clone());
+        let http_client = HttpClient::new(shutdown_signal.clone(), rate_limit);
         let websocket = WebSocket::new(publisher.clone(), shutdown_signal.clone());
+        let simulator =
+            shadow.map(|config| Simulator::new(config, publisher.clone(), shutdown_signal));
         Ok(Arc::new(Connectors {
             client,
             publisher,
             http_client,
             websocket,
+            simulator,
         }))
     }
 
     pub async fn startup(&self) -> Result<()> {
-        self.websocket.startup(&self.client).await
+        match &self.simulator {
+            Some(simulator) => {
+                simulator.startup();
+                self.websocket.startup(&self.client, false).await
+            }
+            None => self.websocket.startup(&self.client, true).await,
+        }
+    }
+
+    pub fn get_rate_limit_stats(&self) -> RateLimitStats {
+        self.http_client.get_rate_limit_stats()
     }
 
     pub fn get_subscriber(&self) -> broadcast::Receiver<Event> {
@@ -69,55 +104,74 @@ impl Connectors {
 
     pub async fn get_assets(&self, request: &assets::AssetsReq) -> Result<Vec<asset::Asset>> {
         info!("Request get_assets");
-        match self
+        Ok(self
             .http_client
-            .send_request::<assets::Get>(&self.client, request)
-            .await
-        {
-            anyhow::Result::Err(err) => bail!("Call to get_assets failed, error={}", err),
-            val => val,
-        }
+            .send_request::<assets::Get>(&self.client, request, RetryPolicy::STATUS)
+            .await?)
     }
 
     pub async fn get_account_details(&self) -> Result<account::Account> {
         info!("Request get_account_details");
-        match self
+        Ok(self
             .http_client
-            .send_request::<account::Get>(&self.client, &())
-            .await
-        {
-            anyhow::Result::Err(err) => bail!("Call to get_account_details failed, error={}", err),
-            val => val,
-        }
+            .send_request::<account::Get>(&self.client, &(), RetryPolicy::STATUS)
+            .await?)
+    }
+
+    pub async fn get_clock(&self) -> Result<clock::Clock> {
+        info!("Request get_clock");
+        Ok(self
+            .http_client
+            .send_request::<clock::Get>(&self.client, &(), RetryPolicy::STATUS)
+            .await?)
+    }
+
+    pub async fn get_calendar(
+        &self,
+        request: &calendar::CalendarReq,
+    ) -> Result<Vec<calendar::OpenClose>> {
+        info!("Request get_calendar");
+        Ok(self
+            .http_client
+            .send_request::<calendar::Get>(&self.client, request, RetryPolicy::STATUS)
+            .await?)
     }
 
     pub async fn get_order(&self, order_id: Uuid) -> Result<order::Order> {
-        let _request = orders::OrdersReq::default();
+        if let Some(simulator) = &self.simulator {
+            return simulator.get_order(order_id).await;
+        }
         info!("Request get_order");
-        match self
+        Ok(self
             .http_client
-            .send_request::<order::Get>(&self.client, &Id(order_id))
-            .await
-        {
-            anyhow::Result::Err(err) => bail!("Call to get_order failed, error={}", err),
-            val => val,
+            .send_request::<order::Get>(&self.client, &Id(order_id), RetryPolicy::STATUS)
+            .await?)
+    }
+
+    pub async fn get_order_relations(&self, order_id: Uuid) -> Result<OrderRelations> {
+        if let Some(simulator) = &self.simulator {
+            return simulator.get_order_relations(order_id).await;
         }
+        info!("Request get_order_relations");
+        Ok(self
+            .http_client
+            .send_request::<GetRelations>(&self.client, &Id(order_id), RetryPolicy::STATUS)
+            .await?)
     }
 
     pub async fn get_orders(&self) -> Result<Vec<order::Order>> {
+        if let Some(simulator) = &self.simulator {
+            return simulator.get_orders().await;
+        }
         let request = orders::OrdersReq {
             status: orders::Status::All,
             ..Default::default()
         };
         info!("Request get_orders");
-        match self
+        Ok(self
             .http_client
-            .send_request::<orders::Get>(&self.client, &request)
-            .await
-        {
-            anyhow::Result::Err(err) => bail!("Call to get_orders failed, error={}", err),
-            val => val,
-        }
+            .send_request::<orders::Get>(&self.client, &request, RetryPolicy::STATUS)
+            .await?)
     }
 
     pub async fn get_position(
@@ -125,76 +179,178 @@ impl Connectors {
         symbol: &str,
         exchange: asset::Exchange,
     ) -> Result<position::Position> {
+        if let Some(simulator) = &self.simulator {
+            return simulator.get_position(symbol).await;
+        }
         let symbol_exchange: asset::Symbol = asset::Symbol::SymExchg(symbol.to_string(), exchange);
         info!("Request get_position");
-        match self
+        Ok(self
             .http_client
-            .send_request::<position::Get>(&self.client, &symbol_exchange)
-            .await
-        {
-            anyhow::Result::Err(err) => bail!("Call to get_position failed, error={}", err),
-            val => val,
-        }
+            .send_request::<position::Get>(&self.client, &symbol_exchange, RetryPolicy::STATUS)
+            .await?)
     }
 
     pub async fn get_positions(&self) -> Result<Vec<position::Position>> {
+        if let Some(simulator) = &self.simulator {
+            return simulator.get_positions().await;
+        }
         info!("Request get_positions");
-        match self
+        Ok(self
             .http_client
-            .send_request::<positions::Get>(&self.client, &())
-            .await
-        {
-            anyhow::Result::Err(err) => bail!("Call to get_positions failed, error={}", err),
-            val => val,
+            .send_request::<positions::Get>(&self.client, &(), RetryPolicy::STATUS)
+            .await?)
+    }
+
+    pub async fn get_order_by_client_id(
+        &self,
+        client_order_id: &str,
+    ) -> Result<Option<order::Order>> {
+        if let Some(simulator) = &self.simulator {
+            return simulator.get_order_by_client_id(client_order_id).await;
+        }
+        info!("Request get_order_by_client_id");
+        let policy = RetryPolicy::ORDER;
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match self
+                .http_client
+                .try_send_request::<order::GetByClientId>(
+                    &self.client,
+                    &client_order_id.to_string(),
+                    policy.priority,
+                )
+                .await
+            {
+                Ok(order) => return Ok(Some(order)),
+                Err(apca::RequestError::Endpoint(order::GetByClientIdError::NotFound(_))) => {
+                    return Ok(None)
+                }
+                Err(err)
+                    if attempt >= policy.max_attempts || classify(&err) == ErrorClass::Terminal =>
+                {
+                    return Err(BrokerError::new("get_order_by_client_id", &err, attempt).into())
+                }
+                Err(err) => {
+                    warn!("Order lookup by client id failed, error: {err}");
+                    if !self
+                        .http_client
+                        .backoff(policy.backoff(attempt, jitter()))
+                        .await
+                    {
+                        return Err(
+                            BrokerError::new("get_order_by_client_id", &err, attempt).into()
+                        );
+                    }
+                }
+            }
         }
     }
 
+    // An order post is only repeated once the client order id is confirmed unknown to the broker
     pub async fn place_order(&self, request: &order::OrderReq) -> Result<order::Order> {
+        if let Some(simulator) = &self.simulator {
+            return simulator.place_order(request).await;
+        }
         info!("Request place_order");
-        match self
-            .http_client
-            .send_request::<order::Post>(&self.client, request)
-            .await
-        {
-            anyhow::Result::Err(err) => bail!("Call to place_order failed, error={}", err),
-            val => val,
+        let policy = RetryPolicy::ORDER;
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let error = match self
+                .http_client
+                .try_send_request::<order::Post>(&self.client, request, policy.priority)
+                .await
+            {
+                Ok(order) => return Ok(order),
+                Err(error) => error,
+            };
+            let class = classify(&error);
+            let Some(client_order_id) = &request.client_order_id else {
+                return Err(BrokerError::new("place_order", &error, attempt).into());
+            };
+            // A resubmission is rejected as a duplicate when the lost attempt did land
+            if class != ErrorClass::Terminal || attempt > 1 {
+                if !self
+                    .http_client
+                    .backoff(policy.backoff(attempt, jitter()))
+                    .await
+                {
+                    return Err(BrokerError::new("place_order", &error, attempt).into());
+                }
+                if let Some(order) = self.get_order_by_client_id(client_order_id).await? {
+                    info!(
+                        "Found order: {} by client id: {}",
+                        order.id.0, client_order_id
+                    );
+                    return Ok(order);
+                }
+            }
+            if class == ErrorClass::Terminal || attempt >= policy.max_attempts {
+                return Err(BrokerError::new("place_order", &error, attempt).into());
+            }
+            warn!(
+                "Retry order posting for client id: {client_order_id}, attempts left: {}",
+                policy.max_attempts - attempt
+            );
         }
     }
 
     pub async fn cancel_order(&self, id: &order::Id) -> Result<()> {
+        if let Some(simulator) = &self.simulator {
+            return simulator.cancel_order(id).await;
+        }
         info!("Request cancel_order");
+        self.http_client
+            .send_request::<order::Delete>(&self.client, id, RetryPolicy::CANCEL)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn replace_order(
+        &self,
+        id: &order::Id,
+        request: &order::ChangeReq,
+    ) -> Result<order::Order> {
+        if let Some(simulator) = &self.simulator {
+            return simulator.replace_order(id, request).await;
+        }
+        info!("Request replace_order");
         match self
             .http_client
-            .send_request::<order::Delete>(&self.client, id)
+            .try_send_request::<order::Patch>(
+                &self.client,
+                &(*id, request.clone()),
+                Priority::Order,
+            )
             .await
         {
-            anyhow::Result::Err(err) => bail!("Call to cancel_order failed, error={}", err),
-            _ => Ok(()),
+            Err(err) => Err(BrokerError::new("replace_order", &err, 1).into()),
+            Ok(order) => Ok(order),
         }
     }
 
     pub async fn close_position(&self, symbol: &asset::Symbol) -> Result<order::Order> {
+        if let Some(simulator) = &self.simulator {
+            return simulator.close_position(symbol).await;
+        }
         info!("Request close_position");
         match self
             .http_client
-            .send_request::<position::Delete>(&self.client, symbol)
+            .try_send_request::<position::Delete>(&self.client, symbol, Priority::Order)
             .await
         {
-            anyhow::Result::Err(err) => bail!("Call to close_position failed, error={}", err),
-            val => val,
+            Err(err) => Err(BrokerError::new("close_position", &err, 1).into()),
+            Ok(order) => Ok(order),
         }
     }
 
     pub async fn get_historical_bars(&self, request: &bars::BarsReq) -> Result<bars::Bars> {
         info!("Request get_historical_bars");
-        match self
+        Ok(self
             .http_client
-            .send_request::<bars::Get>(&self.client, request)
-            .await
-        {
-            anyhow::Result::Err(err) => bail!("Call to get_historical_bars failed, error={}", err),
-            val => val,
-        }
+            .send_request::<bars::Get>(&self.client, request, RetryPolicy::MARKET_DATA)
+            .await?)
     }
 
     pub async fn subscribe_to_symbols(&self, symbols: stream::SymbolList) -> Result<()> {
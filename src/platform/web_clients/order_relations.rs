@@ -0,0 +1,39 @@
+use apca::api::v2::order;
+use apca::ApiError;
+use http_endpoint::Str;
+use serde::Deserialize;
+
+// Links between a replaced order and its replacement, apca leaves these fields out of its order
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct OrderRelations {
+    #[serde(rename = "replaced_by")]
+    pub replaced_by: Option<order::Id>,
+    #[serde(rename = "replaces")]
+    pub replaces: Option<order::Id>,
+}
+
+http_endpoint::EndpointDef! {
+    pub GetRelations(order::Id),
+    Ok => OrderRelations, [
+        /* 200 */ OK,
+    ],
+    Err => GetRelationsError, [
+        /* 401 */ UNAUTHORIZED => AuthenticationFailed,
+        /* 404 */ NOT_FOUND => NotFound,
+        /* 429 */ TOO_MANY_REQUESTS => RateLimitExceeded,
+    ],
+    ConversionErr => serde_json::Error,
+    ApiErr => ApiError,
+
+    fn path(input: &Self::Input) -> Str {
+        format!("/v2/orders/{}", input.0).into()
+    }
+
+    fn parse(body: &[u8]) -> Result<Self::Output, Self::ConversionError> {
+        serde_json::from_slice::<Self::Output>(body)
+    }
+
+    fn parse_err(body: &[u8]) -> Result<Self::ApiError, Vec<u8>> {
+        serde_json::from_slice::<Self::ApiError>(body).map_err(|_| body.to_vec())
+    }
+}
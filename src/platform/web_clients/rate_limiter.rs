@@ -0,0 +1,198 @@
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::sleep;
+use tokio::time::Instant;
+use tracing::warn;
+
+use crate::settings::RateLimitConfig;
+
+const DEFAULT_REQUESTS_PER_MINUTE: u32 = 200;
+const DEFAULT_BURST: u32 = 20;
+const DEFAULT_RESERVED: u32 = 5;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+const QUEUE_DEPTH_WARNING: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Priority {
+    Order,
+    Status,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RateLimitStats {
+    pub queued_orders: usize,
+    pub queued_status: usize,
+    pub max_queue_depth: usize,
+    pub rate_limited: u64,
+    pub tokens: f64,
+}
+
+impl fmt::Display for RateLimitStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Rate limiter queued_orders[{}], queued_status[{}], max_queue_depth[{}], rate_limited[{}], tokens[{:.1}]",
+            self.queued_orders,
+            self.queued_status,
+            self.max_queue_depth,
+            self.rate_limited,
+            self.tokens
+        )
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+    paused_until: Option<Instant>,
+    backoff: Duration,
+    stats: RateLimitStats,
+}
+
+impl Bucket {
+    fn queue(&mut self, priority: Priority) -> &mut usize {
+        match priority {
+            Priority::Order => &mut self.stats.queued_orders,
+            Priority::Status => &mut self.stats.queued_status,
+        }
+    }
+}
+
+// Token bucket shared by all broker requests of an account, status polling
+// leaves a reserve of tokens so orders and cancels are never starved
+#[derive(Debug)]
+pub struct RateLimiter {
+    capacity: f64,
+    refill_per_sec: f64,
+    reserved: f64,
+    bucket: Mutex<Bucket>,
+}
+
+struct QueueGuard<'a> {
+    limiter: &'a RateLimiter,
+    priority: Priority,
+}
+
+impl Drop for QueueGuard<'_> {
+    fn drop(&mut self) {
+        let mut bucket = self.limiter.bucket.lock().unwrap();
+        *bucket.queue(self.priority) -= 1;
+    }
+}
+
+impl RateLimiter {
+    pub fn new(config: Option<RateLimitConfig>) -> Self {
+        let config = config.unwrap_or(RateLimitConfig {
+            requests_per_minute: DEFAULT_REQUESTS_PER_MINUTE,
+            burst: None,
+            reserved: None,
+        });
+        let capacity = config.burst.unwrap_or(DEFAULT_BURST).max(1) as f64;
+        RateLimiter {
+            capacity,
+            refill_per_sec: config.requests_per_minute.max(1) as f64 / 60.0,
+            reserved: (config.reserved.unwrap_or(DEFAULT_RESERVED) as f64).min(capacity - 1.0),
+            bucket: Mutex::new(Bucket {
+                tokens: capacity,
+                refilled_at: Instant::now(),
+                paused_until: None,
+                backoff: INITIAL_BACKOFF,
+                stats: RateLimitStats::default(),
+            }),
+        }
+    }
+
+    fn try_acquire(&self, priority: Priority, now: Instant) -> Option<Duration> {
+        let mut bucket = self.bucket.lock().unwrap();
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        bucket.refilled_at = now;
+        if let Some(paused_until) = bucket.paused_until.filter(|until| *until > now) {
+            return Some(paused_until - now);
+        }
+        let (floor, blocked) = match priority {
+            Priority::Order => (1.0, false),
+            Priority::Status => (1.0 + self.reserved, bucket.stats.queued_orders > 0),
+        };
+        if !blocked && bucket.tokens >= floor {
+            bucket.tokens -= 1.0;
+            return None;
+        }
+        let wait = (floor - bucket.tokens).max(0.0) / self.refill_per_sec;
+        Some(Duration::from_secs_f64(wait.max(0.01)))
+    }
+
+    pub async fn acquire(&self, priority: Priority) {
+        {
+            let mut bucket = self.bucket.lock().unwrap();
+            *bucket.queue(priority) += 1;
+            let depth = bucket.stats.queued_orders + bucket.stats.queued_status;
+            bucket.stats.max_queue_depth = bucket.stats.max_queue_depth.max(depth);
+            if depth == QUEUE_DEPTH_WARNING {
+                warn!("Broker request queue depth reached {}", depth);
+            }
+        }
+        let _guard = QueueGuard {
+            limiter: self,
+            priority,
+        };
+        while let Some(wait) = self.try_acquire(priority, Instant::now()) {
+            sleep(wait).await;
+        }
+    }
+
+    pub fn on_success(&self) {
+        self.bucket.lock().unwrap().backoff = INITIAL_BACKOFF;
+    }
+
+    pub fn on_rate_limited(&self) {
+        let mut bucket = self.bucket.lock().unwrap();
+        let backoff = bucket.backoff;
+        warn!(
+            "Broker rate limit exceeded, pausing requests for {:?}",
+            backoff
+        );
+        bucket.tokens = 0.0;
+        bucket.paused_until = Some(Instant::now() + backoff);
+        bucket.backoff = (backoff * 2).min(MAX_BACKOFF);
+        bucket.stats.rate_limited += 1;
+    }
+
+    pub fn get_stats(&self) -> RateLimitStats {
+        let bucket = self.bucket.lock().unwrap();
+        RateLimitStats {
+            tokens: bucket.tokens,
+            ..bucket.stats.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_status_requests_leave_reserve_for_orders() {
+        let limiter = RateLimiter::new(Some(RateLimitConfig {
+            requests_per_minute: 60,
+            burst: Some(3),
+            reserved: Some(1),
+        }));
+        let now = Instant::now();
+        assert!(limiter.try_acquire(Priority::Status, now).is_none());
+        assert!(limiter.try_acquire(Priority::Status, now).is_none());
+        assert!(limiter.try_acquire(Priority::Status, now).is_some());
+        assert!(limiter.try_acquire(Priority::Order, now).is_none());
+        assert!(limiter.try_acquire(Priority::Order, now).is_some());
+
+        limiter.on_rate_limited();
+        let later = now + Duration::from_millis(500);
+        assert!(limiter.try_acquire(Priority::Order, later).is_some());
+        assert_eq!(limiter.get_stats().rate_limited, 1);
+        limiter.acquire(Priority::Order).await;
+        assert_eq!(limiter.get_stats().queued_orders, 0);
+    }
+}
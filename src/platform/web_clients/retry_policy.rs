@@ -0,0 +1,208 @@
+use apca::api::v2::account;
+use apca::api::v2::assets;
+use apca::api::v2::calendar;
+use apca::api::v2::clock;
+use apca::api::v2::order;
+use apca::api::v2::orders;
+use apca::api::v2::position;
+use apca::api::v2::positions;
+use apca::data::v2::bars;
+use apca::RequestError;
+use std::fmt;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use super::order_relations::GetRelationsError;
+use super::rate_limiter::Priority;
+
+pub trait EndpointError: fmt::Display {
+    fn is_rate_limited(&self) -> bool;
+    fn is_server_error(&self) -> bool;
+}
+
+macro_rules! endpoint_error {
+    ($($error:ty),*) => {
+        $(
+            impl EndpointError for $error {
+                fn is_rate_limited(&self) -> bool {
+                    matches!(self, Self::RateLimitExceeded(_))
+                }
+
+                fn is_server_error(&self) -> bool {
+                    matches!(self, Self::UnexpectedStatus(status, _) if status.is_server_error())
+                }
+            }
+        )*
+    };
+}
+
+endpoint_error!(
+    account::GetError,
+    assets::GetError,
+    calendar::GetError,
+    clock::GetError,
+    order::GetError,
+    order::GetByClientIdError,
+    order::PostError,
+    order::PatchError,
+    order::DeleteError,
+    orders::GetError,
+    position::GetError,
+    position::DeleteError,
+    positions::GetError,
+    bars::GetError,
+    GetRelationsError
+);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorClass {
+    Retryable,
+    Terminal,
+    RateLimited,
+}
+
+impl fmt::Display for ErrorClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorClass::Retryable => write!(f, "retryable"),
+            ErrorClass::Terminal => write!(f, "terminal"),
+            ErrorClass::RateLimited => write!(f, "rate_limited"),
+        }
+    }
+}
+
+pub fn classify<E: EndpointError>(error: &RequestError<E>) -> ErrorClass {
+    match error {
+        RequestError::Hyper(_) | RequestError::Io(_) => ErrorClass::Retryable,
+        RequestError::Endpoint(err) if err.is_rate_limited() => ErrorClass::RateLimited,
+        RequestError::Endpoint(err) if err.is_server_error() => ErrorClass::Retryable,
+        RequestError::Endpoint(_) => ErrorClass::Terminal,
+    }
+}
+
+#[derive(Debug)]
+pub struct BrokerError {
+    pub call: &'static str,
+    pub class: ErrorClass,
+    pub attempts: u32,
+    pub message: String,
+}
+
+impl BrokerError {
+    pub fn new<E: EndpointError>(
+        call: &'static str,
+        error: &RequestError<E>,
+        attempts: u32,
+    ) -> Self {
+        BrokerError {
+            call,
+            class: classify(error),
+            attempts,
+            message: error.to_string(),
+        }
+    }
+
+    pub fn class_of(error: &anyhow::Error) -> Option<ErrorClass> {
+        error.downcast_ref::<BrokerError>().map(|error| error.class)
+    }
+}
+
+impl fmt::Display for BrokerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Call to {} failed after {} attempts, {} error={}",
+            self.call, self.attempts, self.class, self.message
+        )
+    }
+}
+
+impl std::error::Error for BrokerError {}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub priority: Priority,
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub const STATUS: RetryPolicy = RetryPolicy {
+        priority: Priority::Status,
+        max_attempts: 4,
+        initial_backoff: Duration::from_millis(250),
+        max_backoff: Duration::from_secs(4),
+    };
+
+    pub const MARKET_DATA: RetryPolicy = RetryPolicy {
+        priority: Priority::Status,
+        max_attempts: 5,
+        initial_backoff: Duration::from_millis(500),
+        max_backoff: Duration::from_secs(8),
+    };
+
+    pub const ORDER: RetryPolicy = RetryPolicy {
+        priority: Priority::Order,
+        max_attempts: 5,
+        initial_backoff: Duration::from_millis(250),
+        max_backoff: Duration::from_secs(4),
+    };
+
+    pub const CANCEL: RetryPolicy = RetryPolicy {
+        priority: Priority::Order,
+        max_attempts: 5,
+        initial_backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_secs(2),
+    };
+
+    pub fn backoff(&self, attempt: u32, jitter: f64) -> Duration {
+        let base = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff);
+        base.mul_f64(0.5 + 0.5 * jitter.clamp(0.0, 1.0))
+    }
+}
+
+pub fn jitter() -> f64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    (nanos % 1_000) as f64 / 1_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_and_backoff() {
+        let io: RequestError<order::PostError> =
+            RequestError::Io(std::io::Error::other("connection reset"));
+        assert_eq!(classify(&io), ErrorClass::Retryable);
+        let limited: RequestError<account::GetError> =
+            RequestError::Endpoint(account::GetError::RateLimitExceeded(Err(Vec::new())));
+        assert_eq!(classify(&limited), ErrorClass::RateLimited);
+        let rejected: RequestError<order::PostError> =
+            RequestError::Endpoint(order::PostError::NotPermitted(Err(Vec::new())));
+        assert_eq!(classify(&rejected), ErrorClass::Terminal);
+
+        let policy = RetryPolicy::STATUS;
+        assert_eq!(policy.backoff(1, 1.0), Duration::from_millis(250));
+        assert_eq!(policy.backoff(3, 0.0), Duration::from_millis(500));
+        assert_eq!(policy.backoff(10, 1.0), Duration::from_secs(4));
+    }
+
+    #[test]
+    fn test_class_survives_context() {
+        let rejected: RequestError<order::PostError> =
+            RequestError::Endpoint(order::PostError::NotPermitted(Err(Vec::new())));
+        let error = anyhow::Error::from(BrokerError::new("place_order", &rejected, 1))
+            .context("Failed to place order");
+        assert_eq!(BrokerError::class_of(&error), Some(ErrorClass::Terminal));
+        assert_eq!(BrokerError::class_of(&anyhow::anyhow!("local")), None);
+    }
+}
@@ -0,0 +1,821 @@
+use anyhow::bail;
+use anyhow::Ok;
+use anyhow::Result;
+use apca::api::v2::asset;
+use apca::api::v2::order;
+use apca::api::v2::position;
+use apca::api::v2::updates;
+use apca::data::v2::stream;
+use chrono::Utc;
+use num_decimal::Num;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use tracing::error;
+use tracing::info;
+use tracing::warn;
+use uuid::Uuid;
+
+use super::order_relations::OrderRelations;
+use super::Event;
+use crate::settings::ShadowConfig;
+use crate::to_num;
+
+#[derive(Debug, Default)]
+struct SimPosition {
+    quantity: Num,
+    average_entry_price: Num,
+    current_price: Num,
+}
+
+#[derive(Debug, Default)]
+struct SimState {
+    orders: HashMap<Uuid, order::Order>,
+    triggered: HashSet<Uuid>,
+    positions: HashMap<String, SimPosition>,
+    brackets: HashMap<Uuid, Vec<Uuid>>,
+    siblings: HashMap<Uuid, Vec<Uuid>>,
+    replacements: HashMap<Uuid, Uuid>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Simulator {
+    config: ShadowConfig,
+    publisher: broadcast::Sender<Event>,
+    state: Arc<Mutex<SimState>>,
+    shutdown_signal: CancellationToken,
+}
+
+impl Simulator {
+    pub fn new(
+        config: ShadowConfig,
+        publisher: broadcast::Sender<Event>,
+        shutdown_signal: CancellationToken,
+    ) -> Self {
+        Simulator {
+            config,
+            publisher,
+            state: Arc::new(Mutex::new(SimState::default())),
+            shutdown_signal,
+        }
+    }
+
+    pub fn startup(&self) {
+        let mut subscriber = self.publisher.subscribe();
+        let publisher = self.publisher.clone();
+        let state = Arc::clone(&self.state);
+        let slippage = to_num!(self.config.slippage_bps / 10000.0);
+        let shutdown_signal = self.shutdown_signal.clone();
+        info!(
+            "Shadow simulator started with slippage {} bps",
+            self.config.slippage_bps
+        );
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    event = subscriber.recv() => {
+                        match event {
+                            std::result::Result::Ok(Event::Quote(quote)) => {
+                                let updates = state.lock().await.on_quote(&quote, &slippage);
+                                for update in updates {
+                                    Self::publish(&publisher, update);
+                                }
+                            }
+                            std::result::Result::Ok(_) => (),
+                            Err(RecvError::Lagged(err)) => warn!("Simulator skipping {} messages", err),
+                            Err(RecvError::Closed) => break,
+                        }
+                    }
+                    _ = shutdown_signal.cancelled() => {
+                        break;
+                    }
+                }
+            }
+            info!("Shutting down shadow simulator");
+        });
+    }
+
+    fn publish(publisher: &broadcast::Sender<Event>, update: updates::OrderUpdate) {
+        if let Err(err) = publisher.send(Event::OrderUpdate(update)) {
+            error!("Failed to publish simulated order update, error={err:?}");
+        }
+    }
+
+    fn get_symbol(symbol: &asset::Symbol) -> Result<String> {
+        match symbol {
+            asset::Symbol::Sym(symbol)
+            | asset::Symbol::SymExchg(symbol, _)
+            | asset::Symbol::SymExchgCls(symbol, _, _) => Ok(symbol.clone()),
+            asset::Symbol::Id(id) => bail!("Simulator does not support asset id: {:?}", id),
+        }
+    }
+
+    fn build_order(
+        symbol: &str,
+        amount: order::Amount,
+        side: order::Side,
+        type_: order::Type,
+        limit_price: Option<Num>,
+        stop_price: Option<Num>,
+    ) -> order::Order {
+        order::Order {
+            id: order::Id(Uuid::new_v4()),
+            client_order_id: Uuid::new_v4().to_string(),
+            status: order::Status::New,
+            created_at: Utc::now(),
+            updated_at: Some(Utc::now()),
+            submitted_at: Some(Utc::now()),
+            filled_at: None,
+            expired_at: None,
+            canceled_at: None,
+            asset_class: asset::Class::UsEquity,
+            asset_id: asset::Id(Uuid::nil()),
+            symbol: symbol.to_string(),
+            amount,
+            filled_quantity: Num::from(0),
+            type_,
+            class: order::Class::Simple,
+            side,
+            time_in_force: order::TimeInForce::Day,
+            limit_price,
+            stop_price,
+            trail_price: None,
+            trail_percent: None,
+            average_fill_price: None,
+            extended_hours: false,
+            legs: Vec::default(),
+        }
+    }
+
+    async fn submit(&self, order: order::Order) -> order::Order {
+        info!(
+            "Shadow order accepted symbol[{}], side[{:?}], type[{:?}], amount[{:?}]",
+            order.symbol, order.side, order.type_, order.amount
+        );
+        self.state
+            .lock()
+            .await
+            .orders
+            .insert(order.id.0, order.clone());
+        Self::publish(
+            &self.publisher,
+            updates::OrderUpdate {
+                event: updates::OrderStatus::New,
+                order: order.clone(),
+            },
+        );
+        order
+    }
+
+    fn build_legs(symbol: &str, request: &order::OrderReq) -> Vec<order::Order> {
+        let side = match request.side {
+            order::Side::Buy => order::Side::Sell,
+            order::Side::Sell => order::Side::Buy,
+        };
+        let mut legs = Vec::new();
+        if request.class != order::Class::OneCancelsOther {
+            if let Some(order::TakeProfit::Limit(limit_price)) = &request.take_profit {
+                legs.push(Self::build_order(
+                    symbol,
+                    request.amount.clone(),
+                    side,
+                    order::Type::Limit,
+                    Some(limit_price.clone()),
+                    None,
+                ));
+            }
+        }
+        let (type_, limit_price, stop_price) = match &request.stop_loss {
+            Some(order::StopLoss::Stop(stop_price)) => (order::Type::Stop, None, stop_price),
+            Some(order::StopLoss::StopLimit(stop_price, limit_price)) => (
+                order::Type::StopLimit,
+                Some(limit_price.clone()),
+                stop_price,
+            ),
+            None => return legs,
+        };
+        legs.push(Self::build_order(
+            symbol,
+            request.amount.clone(),
+            side,
+            type_,
+            limit_price,
+            Some(stop_price.clone()),
+        ));
+        legs
+    }
+
+    pub async fn place_order(&self, request: &order::OrderReq) -> Result<order::Order> {
+        let symbol = Self::get_symbol(&request.symbol)?;
+        if let Some(client_order_id) = &request.client_order_id {
+            if self
+                .get_order_by_client_id(client_order_id)
+                .await?
+                .is_some()
+            {
+                bail!("Shadow order client id: {} must be unique", client_order_id)
+            }
+        }
+        let is_oco = request.class == order::Class::OneCancelsOther;
+        let limit_price = match (&request.take_profit, is_oco) {
+            (Some(order::TakeProfit::Limit(limit_price)), true) => Some(limit_price.clone()),
+            _ => request.limit_price.clone(),
+        };
+        let mut order = Self::build_order(
+            &symbol,
+            request.amount.clone(),
+            request.side,
+            request.type_,
+            limit_price,
+            request.stop_price.clone(),
+        );
+        order.class = request.class;
+        if let Some(client_order_id) = &request.client_order_id {
+            order.client_order_id = client_order_id.clone();
+        }
+        let mut legs = Self::build_legs(&symbol, request);
+        for leg in legs.iter_mut() {
+            leg.class = request.class;
+            if !is_oco {
+                leg.status = order::Status::Held;
+            }
+        }
+        order.legs = legs.clone();
+        if !legs.is_empty() {
+            let mut state = self.state.lock().await;
+            let mut group: Vec<Uuid> = legs.iter().map(|leg| leg.id.0).collect();
+            if is_oco {
+                group.push(order.id.0);
+            } else {
+                state.brackets.insert(order.id.0, group.clone());
+            }
+            for leg in &legs {
+                state.orders.insert(leg.id.0, leg.clone());
+            }
+            if group.len() > 1 {
+                for id in &group {
+                    let others = group.iter().filter(|other| *other != id).cloned();
+                    state.siblings.insert(*id, others.collect());
+                }
+            }
+        }
+        let order = self.submit(order).await;
+        if is_oco {
+            for leg in legs {
+                Self::publish(
+                    &self.publisher,
+                    updates::OrderUpdate {
+                        event: updates::OrderStatus::New,
+                        order: leg,
+                    },
+                );
+            }
+        }
+        Ok(order)
+    }
+
+    pub async fn cancel_order(&self, id: &order::Id) -> Result<()> {
+        let mut state = self.state.lock().await;
+        let Some(order) = state.orders.get_mut(&id.0) else {
+            bail!("Shadow order: {} not found", id.0)
+        };
+        if !SimState::is_open(order) {
+            bail!("Shadow order: {} is not open", id.0)
+        }
+        SimState::cancel(order);
+        Self::publish(
+            &self.publisher,
+            updates::OrderUpdate {
+                event: updates::OrderStatus::Canceled,
+                order: order.clone(),
+            },
+        );
+        for update in state.cancel_linked(id.0) {
+            Self::publish(&self.publisher, update);
+        }
+        Ok(())
+    }
+
+    pub async fn replace_order(
+        &self,
+        id: &order::Id,
+        request: &order::ChangeReq,
+    ) -> Result<order::Order> {
+        let mut replacement = {
+            let mut state = self.state.lock().await;
+            let Some(order) = state.orders.get_mut(&id.0) else {
+                bail!("Shadow order: {} not found", id.0)
+            };
+            if !SimState::is_open(order) {
+                bail!("Shadow order: {} is not open", id.0)
+            }
+            order.status = order::Status::Replaced;
+            order.updated_at = Some(Utc::now());
+            Self::publish(
+                &self.publisher,
+                updates::OrderUpdate {
+                    event: updates::OrderStatus::Replaced,
+                    order: order.clone(),
+                },
+            );
+            let mut replacement = order.clone();
+            replacement.id = order::Id(Uuid::new_v4());
+            state.relink(id.0, replacement.id.0);
+            state.replacements.insert(id.0, replacement.id.0);
+            replacement
+        };
+        replacement.client_order_id = request
+            .client_order_id
+            .clone()
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        replacement.status = order::Status::New;
+        replacement.created_at = Utc::now();
+        if let Some(quantity) = &request.quantity {
+            replacement.amount = order::Amount::quantity(quantity.clone());
+        }
+        if let Some(time_in_force) = request.time_in_force {
+            replacement.time_in_force = time_in_force;
+        }
+        if request.limit_price.is_some() {
+            replacement.limit_price = request.limit_price.clone();
+        }
+        if request.stop_price.is_some() {
+            replacement.stop_price = request.stop_price.clone();
+        }
+        Ok(self.submit(replacement).await)
+    }
+
+    pub async fn close_position(&self, symbol: &asset::Symbol) -> Result<order::Order> {
+        let symbol = Self::get_symbol(symbol)?;
+        let quantity = match self.state.lock().await.positions.get(&symbol) {
+            Some(position) if !position.quantity.is_zero() => position.quantity.clone(),
+            _ => bail!("Shadow position for symbol: {} not found", symbol),
+        };
+        let side = if quantity.is_positive() {
+            order::Side::Sell
+        } else {
+            order::Side::Buy
+        };
+        let quantity = if quantity.is_negative() {
+            -quantity
+        } else {
+            quantity
+        };
+        let order = Self::build_order(
+            &symbol,
+            order::Amount::quantity(quantity),
+            side,
+            order::Type::Market,
+            None,
+            None,
+        );
+        Ok(self.submit(order).await)
+    }
+
+    pub async fn get_order(&self, order_id: Uuid) -> Result<order::Order> {
+        match self.state.lock().await.orders.get(&order_id) {
+            Some(order) => Ok(order.clone()),
+            None => bail!("Shadow order: {} not found", order_id),
+        }
+    }
+
+    pub async fn get_order_by_client_id(
+        &self,
+        client_order_id: &str,
+    ) -> Result<Option<order::Order>> {
+        Ok(self
+            .state
+            .lock()
+            .await
+            .orders
+            .values()
+            .find(|order| order.client_order_id == client_order_id)
+            .cloned())
+    }
+
+    pub async fn get_order_relations(&self, order_id: Uuid) -> Result<OrderRelations> {
+        let state = self.state.lock().await;
+        if !state.orders.contains_key(&order_id) {
+            bail!("Shadow order: {} not found", order_id)
+        }
+        Ok(OrderRelations {
+            replaced_by: state.replacements.get(&order_id).copied().map(order::Id),
+            replaces: state
+                .replacements
+                .iter()
+                .find(|(_, new_id)| **new_id == order_id)
+                .map(|(old_id, _)| order::Id(*old_id)),
+        })
+    }
+
+    pub async fn get_orders(&self) -> Result<Vec<order::Order>> {
+        Ok(self.state.lock().await.orders.values().cloned().collect())
+    }
+
+    pub async fn get_position(&self, symbol: &str) -> Result<position::Position> {
+        match self.state.lock().await.positions.get(symbol) {
+            Some(position) if !position.quantity.is_zero() => {
+                Ok(Self::build_position(symbol, position))
+            }
+            _ => bail!("Shadow position for symbol: {} not found", symbol),
+        }
+    }
+
+    pub async fn get_positions(&self) -> Result<Vec<position::Position>> {
+        Ok(self
+            .state
+            .lock()
+            .await
+            .positions
+            .iter()
+            .filter(|(_, position)| !position.quantity.is_zero())
+            .map(|(symbol, position)| Self::build_position(symbol, position))
+            .collect())
+    }
+
+    fn build_position(symbol: &str, position: &SimPosition) -> position::Position {
+        let (side, quantity) = if position.quantity.is_negative() {
+            (position::Side::Short, -position.quantity.clone())
+        } else {
+            (position::Side::Long, position.quantity.clone())
+        };
+        let cost_basis = position.average_entry_price.clone() * quantity.clone();
+        let market_value = position.current_price.clone() * quantity.clone();
+        let pnl = match side {
+            position::Side::Long => market_value.clone() - cost_basis.clone(),
+            position::Side::Short => cost_basis.clone() - market_value.clone(),
+        };
+        position::Position {
+            asset_id: asset::Id(Uuid::nil()),
+            symbol: symbol.to_string(),
+            exchange: asset::Exchange::Unknown,
+            asset_class: asset::Class::UsEquity,
+            average_entry_price: position.average_entry_price.clone(),
+            quantity: quantity.clone(),
+            quantity_available: quantity,
+            side,
+            market_value: Some(market_value),
+            cost_basis,
+            unrealized_gain_total: Some(pnl),
+            unrealized_gain_total_percent: None,
+            unrealized_gain_today: None,
+            unrealized_gain_today_percent: None,
+            current_price: Some(position.current_price.clone()),
+            last_day_price: None,
+            change_today: None,
+        }
+    }
+}
+
+impl SimState {
+    fn is_open(order: &order::Order) -> bool {
+        matches!(
+            order.status,
+            order::Status::New | order::Status::Accepted | order::Status::PartiallyFilled
+        )
+    }
+
+    fn cancel(order: &mut order::Order) {
+        order.status = order::Status::Canceled;
+        order.canceled_at = Some(Utc::now());
+        order.updated_at = order.canceled_at;
+    }
+
+    fn relink(&mut self, old_id: Uuid, new_id: Uuid) {
+        let swap = |ids: &mut Vec<Uuid>| {
+            ids.iter_mut()
+                .filter(|id| **id == old_id)
+                .for_each(|id| *id = new_id)
+        };
+        self.brackets.values_mut().for_each(swap);
+        self.siblings.values_mut().for_each(swap);
+        if let Some(legs) = self.brackets.remove(&old_id) {
+            self.brackets.insert(new_id, legs);
+        }
+        if let Some(others) = self.siblings.remove(&old_id) {
+            self.siblings.insert(new_id, others);
+        }
+    }
+
+    fn cancel_siblings(&mut self, order_id: Uuid) -> Vec<updates::OrderUpdate> {
+        let mut updates = Vec::new();
+        for id in self.siblings.remove(&order_id).unwrap_or_default() {
+            self.siblings.remove(&id);
+            let Some(order) = self.orders.get_mut(&id) else {
+                continue;
+            };
+            if Self::is_open(order) || order.status == order::Status::Held {
+                Self::cancel(order);
+                updates.push(updates::OrderUpdate {
+                    event: updates::OrderStatus::Canceled,
+                    order: order.clone(),
+                });
+            }
+        }
+        updates
+    }
+
+    fn cancel_linked(&mut self, order_id: Uuid) -> Vec<updates::OrderUpdate> {
+        let mut updates = self.cancel_siblings(order_id);
+        for id in self.brackets.remove(&order_id).unwrap_or_default() {
+            updates.extend(self.cancel_siblings(id));
+            let Some(order) = self.orders.get_mut(&id) else {
+                continue;
+            };
+            if order.status == order::Status::Held {
+                Self::cancel(order);
+                updates.push(updates::OrderUpdate {
+                    event: updates::OrderStatus::Canceled,
+                    order: order.clone(),
+                });
+            }
+        }
+        updates
+    }
+
+    fn release_legs(&mut self, order_id: Uuid) -> Vec<updates::OrderUpdate> {
+        let mut updates = self.cancel_siblings(order_id);
+        for id in self.brackets.remove(&order_id).unwrap_or_default() {
+            let Some(order) = self.orders.get_mut(&id) else {
+                continue;
+            };
+            if order.status == order::Status::Held {
+                order.status = order::Status::New;
+                order.updated_at = Some(Utc::now());
+                updates.push(updates::OrderUpdate {
+                    event: updates::OrderStatus::New,
+                    order: order.clone(),
+                });
+            }
+        }
+        updates
+    }
+
+    fn get_fill_price(
+        &mut self,
+        order: &order::Order,
+        quote: &stream::Quote,
+        slippage: &Num,
+    ) -> Option<Num> {
+        let is_buy = order.side == order::Side::Buy;
+        let touch = if is_buy {
+            quote.ask_price.clone()
+        } else {
+            quote.bid_price.clone()
+        };
+        if touch.is_zero() {
+            return None;
+        }
+        let stop_hit = |stop: &Num| {
+            if is_buy {
+                touch >= *stop
+            } else {
+                touch <= *stop
+            }
+        };
+        match (order.type_, &order.stop_price) {
+            (order::Type::Stop | order::Type::StopLimit, Some(stop))
+                if !self.triggered.contains(&order.id.0) =>
+            {
+                if !stop_hit(stop) {
+                    return None;
+                }
+                self.triggered.insert(order.id.0);
+            }
+            _ => (),
+        }
+        let price = if is_buy {
+            touch * (to_num!(1.0) + slippage.clone())
+        } else {
+            touch * (to_num!(1.0) - slippage.clone())
+        };
+        match (order.type_, &order.limit_price) {
+            (order::Type::Limit | order::Type::StopLimit, Some(limit)) => {
+                if is_buy && quote.ask_price > *limit || !is_buy && quote.bid_price < *limit {
+                    None
+                } else if is_buy && price > *limit || !is_buy && price < *limit {
+                    Some(limit.clone())
+                } else {
+                    Some(price)
+                }
+            }
+            _ => Some(price),
+        }
+    }
+
+    fn apply_fill(&mut self, symbol: &str, side: order::Side, quantity: &Num, price: &Num) {
+        let position = self.positions.entry(symbol.to_string()).or_default();
+        let signed = match side {
+            order::Side::Buy => quantity.clone(),
+            order::Side::Sell => -quantity.clone(),
+        };
+        let new_quantity = position.quantity.clone() + signed.clone();
+        let is_increase =
+            position.quantity.is_zero() || position.quantity.is_positive() == signed.is_positive();
+        if new_quantity.is_zero() {
+            position.average_entry_price = Num::from(0);
+        } else if is_increase {
+            position.average_entry_price = (position.average_entry_price.clone()
+                * position.quantity.clone()
+                + price.clone() * signed)
+                / new_quantity.clone();
+        } else if position.quantity.is_positive() != new_quantity.is_positive() {
+            position.average_entry_price = price.clone();
+        }
+        position.quantity = new_quantity;
+        position.current_price = price.clone();
+    }
+
+    fn on_quote(&mut self, quote: &stream::Quote, slippage: &Num) -> Vec<updates::OrderUpdate> {
+        if let Some(position) = self.positions.get_mut(&quote.symbol) {
+            if !quote.bid_price.is_zero() && !quote.ask_price.is_zero() {
+                position.current_price = (quote.bid_price.clone() + quote.ask_price.clone()) / 2;
+            }
+        }
+        let candidates: Vec<order::Order> = self
+            .orders
+            .values()
+            .filter(|order| order.symbol == quote.symbol && Self::is_open(order))
+            .cloned()
+            .collect();
+        let mut updates = Vec::new();
+        for mut order in candidates {
+            if !self.orders.get(&order.id.0).is_some_and(Self::is_open) {
+                continue;
+            }
+            let Some(price) = self.get_fill_price(&order, quote, slippage) else {
+                continue;
+            };
+            let price = price.round_with(4);
+            let quantity = match &order.amount {
+                order::Amount::Quantity { quantity } => quantity.clone(),
+                order::Amount::Notional { notional } => notional.clone() / price.clone(),
+            };
+            self.apply_fill(&order.symbol, order.side, &quantity, &price);
+            order.status = order::Status::Filled;
+            order.filled_quantity = quantity.clone();
+            order.average_fill_price = Some(price.clone());
+            order.filled_at = Some(Utc::now());
+            order.updated_at = order.filled_at;
+            info!(
+                "Shadow fill symbol[{}], side[{:?}], quantity[{}], price[{}]",
+                order.symbol, order.side, quantity, price
+            );
+            self.orders.insert(order.id.0, order.clone());
+            let order_id = order.id.0;
+            updates.push(updates::OrderUpdate {
+                event: updates::OrderStatus::Filled,
+                order,
+            });
+            updates.extend(self.release_legs(order_id));
+        }
+        updates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_quote(bid: f64, ask: f64) -> stream::Quote {
+        stream::Quote {
+            symbol: "AAPL".to_string(),
+            bid_price: to_num!(bid),
+            bid_size: Num::from(100),
+            ask_price: to_num!(ask),
+            ask_size: Num::from(100),
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_stop_limit_entry_then_market_exit() {
+        let mut state = SimState::default();
+        let slippage = to_num!(0.001);
+        let entry = Simulator::build_order(
+            "AAPL",
+            order::Amount::quantity(10),
+            order::Side::Buy,
+            order::Type::StopLimit,
+            Some(to_num!(107.0)),
+            Some(to_num!(101.0)),
+        );
+        state.orders.insert(entry.id.0, entry.clone());
+
+        assert!(state
+            .on_quote(&build_quote(99.9, 100.0), &slippage)
+            .is_empty());
+        let updates = state.on_quote(&build_quote(101.9, 102.0), &slippage);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].order.average_fill_price, Some(to_num!(102.102)));
+        assert_eq!(state.positions["AAPL"].quantity, Num::from(10));
+
+        let exit = Simulator::build_order(
+            "AAPL",
+            order::Amount::quantity(10),
+            order::Side::Sell,
+            order::Type::Market,
+            None,
+            None,
+        );
+        state.orders.insert(exit.id.0, exit);
+        let updates = state.on_quote(&build_quote(105.0, 105.1), &slippage);
+        assert_eq!(updates[0].order.average_fill_price, Some(to_num!(104.895)));
+        assert!(state.positions["AAPL"].quantity.is_zero());
+    }
+
+    #[tokio::test]
+    async fn test_bracket_legs_release_and_cancel() -> Result<()> {
+        let (publisher, _subscriber) = broadcast::channel(16);
+        let simulator =
+            Simulator::new(ShadowConfig::default(), publisher, CancellationToken::new());
+        let request = order::OrderReqInit {
+            class: order::Class::Bracket,
+            type_: order::Type::Limit,
+            limit_price: Some(to_num!(100.0)),
+            take_profit: Some(order::TakeProfit::Limit(to_num!(110.0))),
+            stop_loss: Some(order::StopLoss::Stop(to_num!(95.0))),
+            ..Default::default()
+        }
+        .init("AAPL", order::Side::Buy, order::Amount::quantity(10));
+        let order = simulator.place_order(&request).await?;
+        assert_eq!(order.legs.len(), 2);
+        assert!(order
+            .legs
+            .iter()
+            .all(|leg| leg.status == order::Status::Held));
+
+        let slippage = Num::from(0);
+        let mut state = simulator.state.lock().await;
+        assert!(state
+            .on_quote(&build_quote(105.0, 105.1), &slippage)
+            .is_empty());
+        let updates = state.on_quote(&build_quote(99.9, 100.0), &slippage);
+        assert_eq!(updates.len(), 3);
+        assert!(updates[1..]
+            .iter()
+            .all(|update| update.order.status == order::Status::New));
+
+        let updates = state.on_quote(&build_quote(110.0, 110.1), &slippage);
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].order.type_, order::Type::Limit);
+        assert_eq!(updates[1].order.status, order::Status::Canceled);
+        assert!(state.positions["AAPL"].quantity.is_zero());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_order_id_lookup_and_uniqueness() -> Result<()> {
+        let (publisher, _subscriber) = broadcast::channel(16);
+        let simulator =
+            Simulator::new(ShadowConfig::default(), publisher, CancellationToken::new());
+        let client_order_id = format!("{}-create-0", Uuid::new_v4().simple());
+        let request = order::OrderReqInit {
+            type_: order::Type::Limit,
+            limit_price: Some(to_num!(100.0)),
+            client_order_id: Some(client_order_id.clone()),
+            ..Default::default()
+        }
+        .init("AAPL", order::Side::Buy, order::Amount::quantity(10));
+        assert!(simulator
+            .get_order_by_client_id(&client_order_id)
+            .await?
+            .is_none());
+        let order = simulator.place_order(&request).await?;
+        assert_eq!(order.client_order_id, client_order_id);
+        let found = simulator.get_order_by_client_id(&client_order_id).await?;
+        assert_eq!(found.map(|found| found.id), Some(order.id));
+        assert!(simulator.place_order(&request).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replacement_relations() -> Result<()> {
+        let (publisher, _subscriber) = broadcast::channel(16);
+        let simulator =
+            Simulator::new(ShadowConfig::default(), publisher, CancellationToken::new());
+        let request = order::OrderReqInit {
+            type_: order::Type::Limit,
+            limit_price: Some(to_num!(100.0)),
+            ..Default::default()
+        }
+        .init("AAPL", order::Side::Buy, order::Amount::quantity(10));
+        let order = simulator.place_order(&request).await?;
+        let change = order::ChangeReqInit {
+            limit_price: Some(to_num!(101.0)),
+            ..Default::default()
+        }
+        .init();
+        let replacement = simulator.replace_order(&order.id, &change).await?;
+        let relations = simulator.get_order_relations(order.id.0).await?;
+        assert_eq!(relations.replaced_by, Some(replacement.id));
+        assert!(relations.replaces.is_none());
+        let relations = simulator.get_order_relations(replacement.id.0).await?;
+        assert_eq!(relations.replaces, Some(order.id));
+        Ok(())
+    }
+}
@@ -49,20 +49,24 @@ impl WebSocket {
         }
     }
 
-    pub async fn startup(&self, client: &Client) -> Result<()> {
+    pub async fn startup(&self, client: &Client, order_updates: bool) -> Result<()> {
         if let Err(err) = self.subscribe_to_data_stream(client).await {
             bail!("{:?}", err)
         }
 
-        if let Err(err) = self.subscribe_to_order_updates(client).await {
-            bail!("{:?}", err)
+        if order_updates {
+            if let Err(err) = self.subscribe_to_order_updates(client).await {
+                bail!("{:?}", err)
+            }
         }
         Ok(())
     }
 
     pub async fn subscribe_to_mktdata(&self, symbols: stream::SymbolList) -> Result<()> {
         let mut data = stream::MarketData::default();
-        data.set_quotes(symbols);
+        data.set_quotes(symbols.clone());
+        data.set_trades(symbols.clone());
+        data.set_bars(symbols);
 
         let _ = self
             .subscript_publisher
@@ -77,6 +81,7 @@ impl WebSocket {
     pub async fn unsubscribe_from_mktdata(&self, symbols: stream::SymbolList) -> Result<()> {
         let mut data = stream::MarketData::default();
         data.set_quotes(symbols.clone());
+        data.set_trades(symbols.clone());
         data.set_bars(symbols);
 
         let _ = self
@@ -6,6 +6,8 @@ use std::collections::HashMap;
 
 use anyhow::Result;
 
+use crate::platform::PriceSource;
+
 #[derive(Default, Clone, Debug, Deserialize)]
 pub struct Settings {
     pub gcp_subscription: String,
@@ -19,6 +21,19 @@ pub struct Settings {
     pub sizing: PositionSizing,
     pub strategies: HashMap<String, StrategyConfig>,
     pub stops: HashMap<String, Stop>,
+    pub kill_switch: Option<KillSwitchConfig>,
+    pub admin: Option<AdminConfig>,
+    pub risk_limits: Option<RiskLimitsConfig>,
+    pub exposure: Option<ExposureConfig>,
+    pub pre_trade: Option<PreTradeConfig>,
+    pub market_session: Option<MarketSessionConfig>,
+    pub reconciler: Option<ReconcilerConfig>,
+    pub adoption: Option<AdoptionConfig>,
+    pub shadow: Option<ShadowConfig>,
+    pub accounts: Option<HashMap<String, AccountConfig>>,
+    pub shutdown: Option<ShutdownConfig>,
+    pub protective_stop: Option<ProtectiveStopConfig>,
+    pub rate_limit: Option<RateLimitConfig>,
 }
 
 #[derive(Default, Clone, Debug, Deserialize)]
@@ -28,6 +43,7 @@ pub struct DatabaseConfig {
     pub host: String,
     pub user: String,
     pub password: Option<String>,
+    pub schema: Option<String>,
 }
 
 #[derive(Default, Clone, Debug, Deserialize)]
@@ -36,6 +52,99 @@ pub struct ProcessLaunchSettings {
     pub args: Vec<String>,
 }
 
+#[derive(Default, Clone, Debug, Deserialize)]
+pub struct KillSwitchConfig {
+    pub flag_file: Option<String>,
+    pub cancel_orders: bool,
+    pub liquidate: bool,
+}
+
+#[derive(Default, Clone, Debug, Deserialize)]
+pub struct AdminConfig {
+    pub token: Option<String>,
+}
+
+#[derive(Default, Clone, Debug, Deserialize)]
+pub struct RiskLimitsConfig {
+    pub max_daily_loss: Option<f64>,
+    pub max_daily_loss_pc: Option<f64>,
+    pub max_drawdown_pc: Option<f64>,
+    pub breach_action: String,
+    pub tighten_pc: Option<f64>,
+}
+
+#[derive(Default, Clone, Debug, Deserialize)]
+pub struct ExposureConfig {
+    pub max_gross_pc: Option<f64>,
+    pub max_net_pc: Option<f64>,
+    pub max_symbol_notional: Option<f64>,
+    pub max_sector_pc: Option<f64>,
+    pub sector_file: Option<String>,
+    pub on_breach: String,
+}
+
+#[derive(Default, Clone, Debug, Deserialize)]
+pub struct PreTradeConfig {
+    pub buying_power_buffer_pc: f64,
+    pub pdt_equity_threshold: Option<f64>,
+    pub max_day_trades: Option<u64>,
+    pub overnight_only: bool,
+}
+
+#[derive(Default, Clone, Debug, Deserialize)]
+pub struct MarketSessionConfig {
+    pub calendar_file: Option<String>,
+    pub locker_sessions: Option<Vec<String>>,
+}
+
+#[derive(Default, Clone, Debug, Deserialize)]
+pub struct ReconcilerConfig {
+    pub interval_secs: Option<u64>,
+    pub grace_secs: Option<i64>,
+    pub action: String,
+}
+
+#[derive(Default, Clone, Debug, Deserialize)]
+pub struct AdoptionConfig {
+    pub strategy: String,
+    pub anchor: String,
+    pub auto: bool,
+}
+
+#[derive(Default, Clone, Debug, Deserialize)]
+pub struct ShadowConfig {
+    pub slippage_bps: f64,
+    pub schema: String,
+}
+
+#[derive(Default, Clone, Debug, Deserialize)]
+pub struct RateLimitConfig {
+    pub requests_per_minute: u32,
+    pub burst: Option<u32>,
+    pub reserved: Option<u32>,
+}
+
+#[derive(Default, Clone, Debug, Deserialize)]
+pub struct ShutdownConfig {
+    pub policy: String,
+    pub drain_secs: Option<u64>,
+}
+
+#[derive(Default, Clone, Debug, Deserialize)]
+pub struct ProtectiveStopConfig {
+    pub buffer_bps: f64,
+    pub min_change_bps: f64,
+    pub throttle_secs: u64,
+}
+
+#[derive(Default, Clone, Debug, Deserialize)]
+pub struct AccountConfig {
+    pub is_live: bool,
+    pub key_env: String,
+    pub secret_env: String,
+    pub schema: Option<String>,
+}
+
 #[derive(Default, Clone, Debug, Deserialize)]
 pub struct PositionSizing {
     pub risk: f32,
@@ -46,6 +155,35 @@ pub struct PositionSizing {
 pub struct StrategyConfig {
     pub max_positions: i8,
     pub locker: String,
+    pub price_source: Option<PriceSource>,
+    pub sessions: Option<Vec<String>>,
+    pub closed_market: Option<String>,
+    pub flatten_at: Option<String>,
+    pub max_holding_period: Option<String>,
+    pub entry_timeout: Option<String>,
+    pub account: Option<String>,
+    pub entry: Option<EntryOrderConfig>,
+    pub exit: Option<ExitOrderConfig>,
+}
+
+#[derive(Default, Clone, Debug, Deserialize)]
+pub struct ExitOrderConfig {
+    pub policy: String,
+    pub offset_bps: Option<f64>,
+    pub reprice_secs: Option<u64>,
+    pub max_reprices: Option<u32>,
+}
+
+#[derive(Default, Clone, Debug, Deserialize)]
+pub struct EntryOrderConfig {
+    pub order_type: String,
+    pub stop_offset_bps: Option<f64>,
+    pub limit_offset_bps: Option<f64>,
+    pub time_in_force: Option<String>,
+    pub extended_hours: bool,
+    pub order_class: Option<String>,
+    pub take_profit_r: Option<f64>,
+    pub amount: Option<String>,
 }
 
 #[derive(Default, Clone, Debug, Deserialize)]
@@ -54,6 +192,51 @@ pub struct Stop {
     pub multiplier: f64,
 }
 
+pub const DEFAULT_ACCOUNT: &str = "default";
+
+impl Settings {
+    pub fn get_accounts(&self) -> HashMap<String, AccountConfig> {
+        match &self.accounts {
+            Some(accounts) if !accounts.is_empty() => accounts.clone(),
+            _ => HashMap::from([(
+                DEFAULT_ACCOUNT.to_string(),
+                AccountConfig {
+                    is_live: self.account_type == "live",
+                    key_env: "API_KEY".to_string(),
+                    secret_env: "API_SECRET".to_string(),
+                    schema: None,
+                },
+            )]),
+        }
+    }
+
+    pub fn get_account_for_strategy(&self, strategy: &str) -> Option<String> {
+        let config = self.strategies.get(strategy)?;
+        match &self.accounts {
+            Some(accounts) if !accounts.is_empty() => config.account.clone(),
+            _ => Some(DEFAULT_ACCOUNT.to_string()),
+        }
+    }
+
+    pub fn for_account(&self, name: &str, account: &AccountConfig) -> Settings {
+        let mut settings = self.clone();
+        settings
+            .strategies
+            .retain(|strategy, _| self.get_account_for_strategy(strategy).as_deref() == Some(name));
+        if account.schema.is_some() {
+            settings.database.schema = account.schema.clone();
+        }
+        if settings
+            .adoption
+            .as_ref()
+            .is_some_and(|config| !settings.strategies.contains_key(&config.strategy))
+        {
+            settings.adoption = None;
+        }
+        settings
+    }
+}
+
 #[derive(Debug)]
 pub struct Config {}
 
@@ -130,4 +313,41 @@ mod tests {
 
     #[test]
     fn test_build_from_json_no_errors() {}
+
+    #[test]
+    fn test_settings_for_account() {
+        let strategy = |account: &str| StrategyConfig {
+            account: Some(account.to_string()),
+            ..Default::default()
+        };
+        let account = |schema: &str| AccountConfig {
+            schema: Some(schema.to_string()),
+            ..Default::default()
+        };
+        let settings = Settings {
+            strategies: HashMap::from([
+                ("auto01".to_string(), strategy("ira")),
+                ("manual01".to_string(), strategy("personal")),
+            ]),
+            accounts: Some(HashMap::from([
+                ("ira".to_string(), account("ira")),
+                ("personal".to_string(), account("personal")),
+            ])),
+            adoption: Some(AdoptionConfig {
+                strategy: "manual01".to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let accounts = settings.get_accounts();
+        assert_eq!(accounts.len(), 2);
+        let personal = settings.for_account("personal", &accounts["personal"]);
+        assert!(personal.strategies.contains_key("manual01"));
+        assert!(!personal.strategies.contains_key("auto01"));
+        assert_eq!(personal.database.schema.as_deref(), Some("personal"));
+        assert!(personal.adoption.is_some());
+        let ira = settings.for_account("ira", &accounts["ira"]);
+        assert!(ira.strategies.contains_key("auto01"));
+        assert!(ira.adoption.is_none());
+    }
 }